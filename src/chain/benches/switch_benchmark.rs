@@ -0,0 +1,153 @@
+#[macro_use]
+extern crate criterion;
+
+use bin_tools::*;
+use chain::{Block, Chain};
+use chrono::prelude::*;
+use criterion::Criterion;
+use crypto::Hash;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Nonce used for creating unique `BenchBlock` hashes
+static NONCE: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone, Debug)]
+/// Minimal block used for benchmarking
+struct BenchBlock {
+    hash: Hash,
+    parent_hash: Hash,
+    height: u64,
+}
+
+impl BenchBlock {
+    fn new(parent_hash: Hash, height: u64) -> Arc<BenchBlock> {
+        let nonce = NONCE.fetch_add(1, Ordering::Relaxed);
+        let hash = crypto::hash_slice(format!("bench-block-{}", nonce).as_bytes());
+
+        Arc::new(BenchBlock {
+            hash,
+            parent_hash,
+            height,
+        })
+    }
+}
+
+impl Block for BenchBlock {
+    fn genesis() -> Arc<Self> {
+        Arc::new(BenchBlock {
+            hash: Hash::NULL,
+            parent_hash: Hash::NULL,
+            height: 0,
+        })
+    }
+
+    fn block_hash(&self) -> Option<Hash> {
+        Some(self.hash.clone())
+    }
+
+    fn merkle_root(&self) -> Option<Hash> {
+        None
+    }
+
+    fn parent_hash(&self) -> Option<Hash> {
+        Some(self.parent_hash.clone())
+    }
+
+    // Hashes are random nonces so there
+    // is nothing to recompute them from.
+    fn verify_hash(&self) -> bool {
+        true
+    }
+
+    // Timestamps are equal to heights so that
+    // blocks always pass timestamp validation.
+    fn timestamp(&self) -> DateTime<Utc> {
+        Utc.timestamp(self.height as i64, 0)
+    }
+
+    fn height(&self) -> u64 {
+        self.height
+    }
+
+    fn after_write() -> Option<Box<FnMut(Arc<Self>)>> {
+        None
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(72);
+
+        buf.extend_from_slice(&encode_be_u64!(self.height));
+        buf.extend_from_slice(&self.hash.0);
+        buf.extend_from_slice(&self.parent_hash.0);
+
+        buf
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Arc<Self>, &'static str> {
+        if bytes.len() != 72 {
+            return Err("Invalid block length");
+        }
+
+        let height = decode_be_u64!(&bytes[..8]).unwrap();
+        let mut hash = [0; 32];
+        let mut parent_hash = [0; 32];
+
+        hash.copy_from_slice(&bytes[8..40]);
+        parent_hash.copy_from_slice(&bytes[40..72]);
+
+        Ok(Arc::new(BenchBlock {
+            hash: Hash(hash),
+            parent_hash: Hash(parent_hash),
+            height,
+        }))
+    }
+}
+
+/// Builds a chain with a competing fork of the given depth which
+/// has as much work as the canonical chain, and returns it along
+/// with the block which makes the fork outweigh the canonical chain.
+fn setup_fork(depth: u64) -> (Chain<BenchBlock>, Arc<BenchBlock>) {
+    let mut chain = Chain::<BenchBlock>::new(test_helpers::init_tempdb());
+    let root = BenchBlock::new(Hash::NULL, 1);
+    let mut canonical_parent = root.block_hash().unwrap();
+    let mut fork_parent = root.block_hash().unwrap();
+
+    chain.append_block(root).unwrap();
+
+    // Grow both chains in lockstep so the fork is
+    // never outside of the accepted height range.
+    for height in 2..depth + 2 {
+        let block = BenchBlock::new(canonical_parent, height);
+        canonical_parent = block.block_hash().unwrap();
+        chain.append_block(block).unwrap();
+
+        let block = BenchBlock::new(fork_parent, height);
+        fork_parent = block.block_hash().unwrap();
+        chain.append_block(block).unwrap();
+    }
+
+    (chain, BenchBlock::new(fork_parent, depth + 2))
+}
+
+/// Measures the append which makes a fork outweigh the canonical
+/// chain, which includes rewinding the canonical chain down to
+/// the common ancestor and writing the blocks of the fork.
+fn criterion_benchmark(c: &mut Criterion) {
+    // Orphans more than `MIN_HEIGHT` blocks below the
+    // canonical tip are pruned so deeper forks can't be built.
+    for depth in vec![1, 5, 10] {
+        c.bench_function(&format!("switch chains of depth {}", depth), move |b| {
+            b.iter_with_setup(
+                || setup_fork(depth),
+                |(mut chain, tip)| {
+                    chain.append_block(tip).unwrap();
+                    chain
+                },
+            )
+        });
+    }
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -35,18 +35,56 @@ pub trait Block {
     /// Returns the parent hash of the block.
     fn parent_hash(&self) -> Option<Hash>;
 
+    /// Returns `true` if the hash of the block
+    /// matches the contents of the block.
+    fn verify_hash(&self) -> bool;
+
     /// Returns the timestamp of the block.
     fn timestamp(&self) -> DateTime<Utc>;
 
     /// Returns the height of the block.
     fn height(&self) -> u64;
 
+    /// Returns the amount of work the block adds to the chain. Defaults
+    /// to 1 so that the chain with the most work is the longest one.
+    fn total_difficulty(&self) -> u64 {
+        1
+    }
+
+    /// Returns extra metadata attached to the block
+    /// e.g. the miner address or protocol version flags.
+    fn extra_data(&self) -> Option<&[u8]> {
+        None
+    }
+
+    /// Returns the world-state transition carried by the block.
+    fn state_transition(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Returns the id of the chain the parent of the block belongs
+    /// to, if it is different from the chain of the block itself.
+    fn parent_chain_id(&self) -> Option<u64> {
+        None
+    }
+
     /// Callback that executes after a block is written to a chain.
     fn after_write() -> Option<Box<FnMut(Arc<Self>)>>;
 
     /// Serializes the block.
     fn to_bytes(&self) -> Vec<u8>;
 
+    /// Returns the size of the serialized block in bytes.
+    fn size_bytes(&self) -> usize {
+        self.to_bytes().len()
+    }
+
+    /// Returns an estimate of the size of the serialized block
+    /// in bytes which is cheaper to compute than `size_bytes()`.
+    fn estimated_serialized_size(&self) -> usize {
+        256
+    }
+
     /// Deserializes the block
     fn from_bytes(bytes: &[u8]) -> Result<Arc<Self>, &'static str>;
 }
@@ -18,18 +18,27 @@
 
 use crate::block::Block;
 use crate::orphan_type::OrphanType;
+use crate::semaphore::Semaphore;
+use crate::state_db::StateDb;
+use crate::worker_pool::WorkerPool;
 use bin_tools::*;
+use chrono::{DateTime, Utc};
 use crypto::Hash;
 use elastic_array::ElasticArray128;
+use futures::sync::oneshot;
 use hashbrown::{HashMap, HashSet};
 use hashdb::HashDB;
 use lazy_static::*;
 use lru::LruCache;
-use parking_lot::{Mutex, RwLock};
-use persistence::PersistentDb;
-use std::collections::VecDeque;
+use parking_lot::{Condvar, Mutex, RwLock};
+use persistence::{DbOp, PersistentDb};
+use std::collections::{BTreeSet, VecDeque};
 use std::hash::Hash as HashTrait;
+use std::marker::PhantomData;
+use std::mem;
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum ChainErr {
@@ -37,31 +46,310 @@ pub enum ChainErr {
     AlreadyInChain,
 
     /// The parent of the given block is invalid
-    InvalidParent,
+    InvalidParent { block: Hash, parent: Hash },
 
     /// The given block does not have a parent hash
     NoParentHash,
 
-    /// Bad block height
-    BadHeight,
+    /// Bad block height. The expected range is inclusive.
+    BadHeight {
+        block: Hash,
+        got: u64,
+        expected_min: u64,
+        expected_max: u64,
+    },
 
     /// The block with the given hash is not written in the ledger
-    NoSuchBlock,
+    NoSuchBlock(Hash),
 
     /// The orphan pool is full.
     TooManyOrphans,
+
+    /// The block has been marked as invalid.
+    MarkedInvalid,
+
+    /// The height of the chain would overflow.
+    HeightOverflow,
+
+    /// The stored height does not match the number
+    /// of canonical blocks that are actually stored.
+    HeightMismatch { stored: u64, actual: u64 },
+
+    /// The serialized block is larger than the maximum block size.
+    BlockTooLarge,
+
+    /// The block timestamp is too far in the future or not
+    /// greater than the median timestamp of its ancestors.
+    InvalidTimestamp,
+
+    /// The given genesis block is not the one the chain was created with.
+    GenesisMismatch { stored: Hash, given: Hash },
+
+    /// The block conflicts with the checkpoint at the given height.
+    CheckpointViolation { block: Hash, checkpoint_height: u64 },
+
+    /// The disconnected chain with the given head does
+    /// not follow the block it would be attached to.
+    InconsistentFork(Hash),
+}
+
+impl std::fmt::Display for ChainErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            ChainErr::AlreadyInChain => write!(f, "The block already exists in the chain"),
+            ChainErr::InvalidParent {
+                ref block,
+                ref parent,
+            } => write!(f, "Block {:?} has invalid parent {:?}", block, parent),
+            ChainErr::NoParentHash => write!(f, "The block does not have a parent hash"),
+            ChainErr::BadHeight {
+                ref block,
+                got,
+                expected_min,
+                expected_max,
+            } => write!(
+                f,
+                "Block {:?} has height {}, expected between {} and {}",
+                block, got, expected_min, expected_max
+            ),
+            ChainErr::NoSuchBlock(ref block) => write!(f, "No such block: {:?}", block),
+            ChainErr::InconsistentFork(ref head) => {
+                write!(
+                    f,
+                    "The disconnected chain with head {:?} is inconsistent",
+                    head
+                )
+            }
+            ChainErr::TooManyOrphans => write!(f, "The orphan pool is full"),
+            ChainErr::MarkedInvalid => write!(f, "The block has been marked as invalid"),
+            ChainErr::HeightOverflow => write!(f, "The height of the chain would overflow"),
+            ChainErr::HeightMismatch { stored, actual } => write!(
+                f,
+                "Stored height {} does not match the actual height {}",
+                stored, actual
+            ),
+            ChainErr::BlockTooLarge => write!(f, "The block is larger than the maximum block size"),
+            ChainErr::InvalidTimestamp => write!(f, "The block timestamp is invalid"),
+            ChainErr::GenesisMismatch {
+                ref stored,
+                ref given,
+            } => write!(
+                f,
+                "Genesis block {:?} does not match the stored genesis block {:?}",
+                given, stored
+            ),
+            ChainErr::CheckpointViolation {
+                ref block,
+                checkpoint_height,
+            } => write!(
+                f,
+                "Block {:?} conflicts with the checkpoint at height {}",
+                block, checkpoint_height
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ChainErr {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum WaitError {
+    /// The chain did not reach the target height in time.
+    Timeout,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Metrics of a disconnected chain segment.
+pub struct SegmentStats {
+    /// The hash of the head of the segment.
+    pub head_hash: Hash,
+
+    /// The number of tips of the segment.
+    pub tip_count: usize,
+
+    /// The number of blocks in the longest
+    /// path from the head to any tip.
+    pub depth: usize,
+
+    /// The largest height of any tip.
+    pub max_height: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Information about the tip of a non-canonical chain.
+pub struct TipInfo {
+    /// The hash of the tip.
+    pub hash: Hash,
+
+    /// The height of the tip.
+    pub height: u64,
+
+    /// Either `ValidChainTip` or `DisconnectedTip`.
+    pub status: OrphanType,
+
+    /// The number of orphans from the tip down to
+    /// the canonical chain or the disconnected head.
+    pub fork_length: u64,
+}
+
+#[derive(Clone, Debug)]
+/// Record of a switch of the canonical chain to another fork.
+pub struct ReorgRecord<B: Block> {
+    /// The height of the common ancestor of both forks.
+    pub at_height: u64,
+
+    /// The hashes of the blocks removed from the
+    /// canonical chain, ordered oldest-first.
+    pub removed: Vec<Hash>,
+
+    /// The hashes of the blocks added to the
+    /// canonical chain, ordered oldest-first.
+    pub added: Vec<Hash>,
+
+    /// The moment the switch happened.
+    pub timestamp: Instant,
+
+    _block: PhantomData<B>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Proof that an orphan descends from the canonical chain.
+pub struct MerkleProof {
+    /// The hashes of the orphan and its ancestors, ending
+    /// with the hash of the first canonical ancestor.
+    pub block_hashes: Vec<Hash>,
+
+    /// The running tree hash at each level i.e. the hash of the
+    /// previous running hash and the block hash at that level.
+    pub sibling_hashes: Vec<Hash>,
+
+    /// The serialized orphan and its orphan ancestors, which
+    /// commit to the parent of each hash in `block_hashes`.
+    pub headers: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, PartialEq)]
+/// Event emitted when the canonical chain changes.
+pub enum ChainEvent<B> {
+    /// The block has been written to the canonical chain.
+    Connected(Arc<B>),
+
+    /// The block has been removed from the canonical chain.
+    Disconnected(Arc<B>),
+
+    /// The canonical chain has switched to another fork. `depth`
+    /// is the number of blocks removed from the canonical chain.
+    Reorg {
+        old_tip: Hash,
+        new_tip: Hash,
+        depth: u64,
+    },
+
+    /// The canonical chain did not switch to a heavier fork because
+    /// it would have removed more than `max_reorg_depth` blocks.
+    ReorgRejected { candidate_tip: Hash, depth: u64 },
+}
+
+// Derived `Clone` would require `B: Clone`
+impl<B> Clone for ChainEvent<B> {
+    fn clone(&self) -> Self {
+        match *self {
+            ChainEvent::Connected(ref block) => ChainEvent::Connected(block.clone()),
+            ChainEvent::Disconnected(ref block) => ChainEvent::Disconnected(block.clone()),
+            ChainEvent::Reorg {
+                ref old_tip,
+                ref new_tip,
+                depth,
+            } => ChainEvent::Reorg {
+                old_tip: old_tip.clone(),
+                new_tip: new_tip.clone(),
+                depth,
+            },
+            ChainEvent::ReorgRejected {
+                ref candidate_tip,
+                depth,
+            } => ChainEvent::ReorgRejected {
+                candidate_tip: candidate_tip.clone(),
+                depth,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Configurable limits of a `Chain`.
+pub struct ChainConfig {
+    /// The maximum number of canonical blocks that
+    /// can be removed when switching to another fork.
+    pub max_reorg_depth: u64,
+
+    /// How far ahead of the local time a block timestamp may be.
+    pub max_timestamp_drift: chrono::Duration,
+
+    /// The number of ancestors used for computing
+    /// the median timestamp of a block's ancestors.
+    pub median_time_span: usize,
+
+    /// Pairs of heights and the hashes of the canonical blocks
+    /// at those heights. The canonical chain is never forked
+    /// or rewound below the highest reached checkpoint.
+    pub checkpoints: Vec<(u64, Hash)>,
+
+    /// The maximum size of a serialized block in bytes.
+    pub max_block_size: usize,
+}
+
+impl Default for ChainConfig {
+    fn default() -> ChainConfig {
+        ChainConfig {
+            max_reorg_depth: u64::max_value(),
+            max_timestamp_drift: chrono::Duration::seconds(DEFAULT_MAX_TIMESTAMP_DRIFT),
+            median_time_span: DEFAULT_MEDIAN_TIME_SPAN,
+            checkpoints: Vec::new(),
+            max_block_size: DEFAULT_MAX_BLOCK_SIZE,
+        }
+    }
 }
 
+/// The id of a chain that has not been assigned one.
+const DEFAULT_CHAIN_ID: u64 = 0;
+
+/// Default number of blocks that a `ChainRef`
+/// can process at the same time.
+const DEFAULT_PROCESSING_PERMITS: usize = 4;
+
+/// Default number of reorgs kept in the reorg history.
+const DEFAULT_REORG_HISTORY: usize = 10;
+
+/// Default number of seconds a block timestamp
+/// may be ahead of the local time.
+const DEFAULT_MAX_TIMESTAMP_DRIFT: i64 = 2 * 60 * 60;
+
+/// Default number of ancestors whose median timestamp
+/// a block timestamp must be greater than.
+const DEFAULT_MEDIAN_TIME_SPAN: usize = 11;
+
+/// Number of block writes after which
+/// the heights mapping is compacted.
+const HEIGHTS_COMPACTION_INTERVAL: usize = 100;
+
 /// Size of the block cache.
 const BLOCK_CACHE_SIZE: usize = 20;
 
 /// Maximum orphans allowed.
 const MAX_ORPHANS: usize = 100;
 
+/// Maximum blocks with a parent on another
+/// chain allowed across all chain ids.
+const MAX_CROSS_CHAIN_ORPHANS: usize = 100;
+
 /// Blocks with height below the canonical height minus
 /// this number will be rejected.
 const MIN_HEIGHT: u64 = 10;
 
+/// Default maximum size of a serialized block in bytes.
+const DEFAULT_MAX_BLOCK_SIZE: usize = 1_048_576;
+
 /// Blocks with height below the canonical height minus
 /// this number will be rejected.
 const MAX_HEIGHT: u64 = 10;
@@ -72,6 +360,12 @@ lazy_static! {
 
     /// The key to the canonical height of the chain
     static ref CANONICAL_HEIGHT_KEY: Hash = { crypto::hash_slice(b"canonical_height") };
+
+    /// The key to the total work of the canonical chain
+    static ref TOTAL_WORK_KEY: Hash = { crypto::hash_slice(b"total_work") };
+
+    /// The key to the hash of the genesis block of the chain
+    static ref GENESIS_KEY: Hash = { crypto::hash_slice(b"genesis_hash") };
 }
 
 #[derive(Clone)]
@@ -82,16 +376,61 @@ pub struct ChainRef<B: Block> {
 
     /// Block lookup cache.
     block_cache: Arc<Mutex<LruCache<Hash, Arc<B>>>>,
+
+    /// Limits the number of blocks that are processed at the same time.
+    processing_semaphore: Arc<Semaphore>,
+
+    /// Threads on which blocks are asynchronously appended.
+    workers: Arc<WorkerPool>,
+
+    /// The height of the chain along with a condvar
+    /// that is notified whenever it changes.
+    height_notifier: Arc<(Mutex<u64>, Condvar)>,
+
+    /// Canonical block lookup cache, keyed by height.
+    height_cache: Arc<HeightCache<B>>,
+}
+
+/// Cache mapping canonical heights to blocks.
+///
+/// It is shared between a chain and its references
+/// so that rewinds can evict the stale heights.
+struct HeightCache<B>(Mutex<LruCache<u64, Arc<B>>>);
+
+impl<B> std::fmt::Debug for HeightCache<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "HeightCache {{ len: {} }}", self.0.lock().len())
+    }
 }
 
 impl<B: Block> ChainRef<B> {
     pub fn new(chain: Arc<RwLock<Chain<B>>>) -> ChainRef<B> {
+        ChainRef::with_processing_permits(chain, DEFAULT_PROCESSING_PERMITS)
+    }
+
+    /// Creates a new `ChainRef` which processes at
+    /// most `permits` blocks at the same time.
+    pub fn with_processing_permits(chain: Arc<RwLock<Chain<B>>>, permits: usize) -> ChainRef<B> {
+        let (height_notifier, height_cache) = {
+            let chain = chain.read();
+            (chain.height_notifier.clone(), chain.height_cache.clone())
+        };
+
         ChainRef {
             chain,
+            height_notifier,
+            height_cache,
             block_cache: Arc::new(Mutex::new(LruCache::new(BLOCK_CACHE_SIZE))),
+            processing_semaphore: Arc::new(Semaphore::new(permits)),
+            workers: Arc::new(WorkerPool::new(permits)),
         }
     }
 
+    /// Subscribes to the events of the underlying chain.
+    pub fn subscribe(&self) -> Receiver<ChainEvent<B>> {
+        self.chain.write().subscribe()
+    }
+
     /// Attempts to fetch a block by its hash from the cache
     /// and if it doesn't succeed it then attempts to retrieve
     /// it from the database.
@@ -109,32 +448,167 @@ impl<B: Block> ChainRef<B> {
         if let Some(result) = cache_result {
             Some(result)
         } else {
-            let chain_result = {
-                let chain = self.chain.read();
+            let chain = self.chain.read();
+            self.query_and_cache(&chain, hash)
+        }
+    }
 
-                if let Some(result) = chain.query(hash) {
-                    Some(result)
-                } else {
-                    None
-                }
-            };
+    /// Same as `query()` but returns `None` immediately instead
+    /// of blocking if the block is not cached and the chain is
+    /// locked for writing.
+    pub fn try_query(&self, hash: &Hash) -> Option<Arc<B>> {
+        let cache_result = {
+            let mut cache = self.block_cache.lock();
+
+            if let Some(result) = cache.get(hash) {
+                Some(result.clone())
+            } else {
+                None
+            }
+        };
 
-            if let Some(result) = chain_result {
-                let mut cache = self.block_cache.lock();
+        if let Some(result) = cache_result {
+            Some(result)
+        } else {
+            match self.chain.try_read() {
+                Some(chain) => self.query_and_cache(&chain, hash),
+                None => None,
+            }
+        }
+    }
 
-                if cache.get(hash).is_none() {
-                    // Cache result and then return it
-                    cache.put(hash.clone(), result.clone());
-                }
+    /// Queries the given locked chain and caches the result.
+    ///
+    /// The block is cached while the chain is still locked so that
+    /// it cannot be removed from the canonical chain before being
+    /// cached, in which case it would never be evicted.
+    fn query_and_cache(&self, chain: &Chain<B>, hash: &Hash) -> Option<Arc<B>> {
+        let result = chain.query(hash)?;
+        let mut cache = self.block_cache.lock();
+
+        if cache.get(hash).is_none() {
+            // Cache result and then return it
+            cache.put(hash.clone(), result.clone());
+        }
+
+        Some(result)
+    }
 
-                Some(result)
+    /// Returns the canonical block at the given height, looking
+    /// up its hash in the height cache before walking the chain.
+    pub fn query_by_height(&self, height: u64) -> Option<Arc<B>> {
+        let cache_result = {
+            let mut cache = self.height_cache.0.lock();
+
+            if let Some(result) = cache.get(&height) {
+                Some(result.clone())
             } else {
                 None
             }
+        };
+
+        if let Some(result) = cache_result {
+            Some(result)
+        } else {
+            let chain = self.chain.read();
+            let result = chain.query_by_height(height)?;
+
+            // Cache block while the chain is still locked so that
+            // a rewind cannot happen before the block is cached.
+            self.height_cache.0.lock().put(height, result.clone());
+
+            Some(result)
+        }
+    }
+
+    /// Appends the given block to the chain, evicting the blocks
+    /// that are removed from the canonical chain from the cache.
+    pub fn append_block(&self, block: Arc<B>) -> Result<(), ChainErr> {
+        append_and_evict(
+            &self.chain,
+            &self.block_cache,
+            &self.processing_semaphore,
+            block,
+        )
+    }
+
+    /// Returns the tip block of the canonical chain.
+    pub fn canonical_tip(&self) -> Arc<B> {
+        self.chain.read().canonical_tip()
+    }
+
+    /// Returns the height of the canonical chain.
+    pub fn height(&self) -> u64 {
+        self.chain.read().height()
+    }
+
+    /// Blocks until the height of the chain is at least
+    /// `target` or until the given timeout expires.
+    pub fn wait_for_height(&self, target: u64, timeout: Duration) -> Result<(), WaitError> {
+        let deadline = Instant::now() + timeout;
+        let (height, changed) = &*self.height_notifier;
+        let mut height = height.lock();
+
+        while *height < target {
+            let now = Instant::now();
+
+            if now >= deadline {
+                return Err(WaitError::Timeout);
+            }
+
+            changed.wait_for(&mut height, deadline - now);
         }
+
+        Ok(())
     }
 }
 
+impl<B: Block + Send + Sync + 'static> ChainRef<B> {
+    /// Appends the given block to the chain on one of the worker
+    /// threads so that async callers are not blocked while the
+    /// chain is locked. There are as many workers as permits.
+    ///
+    /// The returned receiver resolves to the result of the append.
+    pub fn write_block_async(&self, block: Arc<B>) -> oneshot::Receiver<Result<(), ChainErr>> {
+        let (sender, receiver) = oneshot::channel();
+        let chain = self.chain.clone();
+        let block_cache = self.block_cache.clone();
+        let semaphore = self.processing_semaphore.clone();
+
+        self.workers.execute(move || {
+            let result = append_and_evict(&chain, &block_cache, &semaphore, block);
+
+            // The receiver may have been dropped in
+            // which case there is nothing to do.
+            let _ = sender.send(result);
+        });
+
+        receiver
+    }
+}
+
+/// Appends the given block to the chain and evicts the blocks
+/// it disconnects from the canonical chain from the block cache.
+///
+/// Blocks until a processing permit is available.
+fn append_and_evict<B: Block>(
+    chain: &RwLock<Chain<B>>,
+    block_cache: &Mutex<LruCache<Hash, Arc<B>>>,
+    semaphore: &Semaphore,
+    block: Arc<B>,
+) -> Result<(), ChainErr> {
+    let _permit = semaphore.acquire();
+    let mut chain = chain.write();
+    let (result, disconnected) = chain.append_block_collecting_disconnected(block);
+    let mut block_cache = block_cache.lock();
+
+    for block_hash in disconnected.iter() {
+        block_cache.pop(block_hash);
+    }
+
+    result
+}
+
 #[derive(Debug)]
 /// Generic chain
 pub struct Chain<B: Block> {
@@ -144,6 +618,9 @@ pub struct Chain<B: Block> {
     /// The current height of the chain.
     height: u64,
 
+    /// The work accumulated by the canonical chain.
+    total_work: u128,
+
     /// The tip block of the canonical chain.
     canonical_tip: Arc<B>,
 
@@ -173,10 +650,111 @@ pub struct Chain<B: Block> {
     /// Set containing tips of valid chains that descend
     /// from the canonical chain.
     valid_tips: HashSet<Hash>,
+
+    /// Set containing blocks that have been marked as invalid.
+    invalid_set: HashSet<Hash>,
+
+    /// Mapping between block hashes and the
+    /// hashes of their children in the orphan pool.
+    children_index: HashMap<Hash, Vec<Hash>>,
+
+    /// The id of the chain.
+    chain_id: u64,
+
+    /// Mapping between chain ids and blocks whose
+    /// parents reside on the chain with that id.
+    cross_chain_orphans: HashMap<u64, HashMap<Hash, Arc<B>>>,
+
+    /// World-state store which receives the state
+    /// transitions of canonical blocks.
+    state_db: Option<Arc<Mutex<dyn StateDb>>>,
+
+    /// The most recent reorgs, ordered oldest-first.
+    reorg_history: VecDeque<ReorgRecord<B>>,
+
+    /// The maximum number of reorgs kept in the reorg history.
+    max_reorg_history: usize,
+
+    /// Configurable limits of the chain.
+    config: ChainConfig,
+
+    /// The number of blocks written since the
+    /// heights mapping was last compacted.
+    writes_since_compaction: usize,
+
+    /// The genesis block of the chain.
+    genesis: Arc<B>,
+
+    /// The timestamp of the genesis block.
+    genesis_timestamp: DateTime<Utc>,
+
+    /// The height of the chain along with a condvar
+    /// that is notified whenever it changes.
+    height_notifier: Arc<(Mutex<u64>, Condvar)>,
+
+    /// Canonical block lookup cache shared with chain references.
+    height_cache: Arc<HeightCache<B>>,
+
+    /// Senders of the subscribers to chain events.
+    subscribers: Mutex<Vec<Sender<ChainEvent<B>>>>,
+
+    /// Hashes of the blocks disconnected from the
+    /// canonical chain, only collected while `Some`.
+    disconnected_hashes: Option<Vec<Hash>>,
 }
 
 impl<B: Block> Chain<B> {
-    pub fn new(mut db_ref: PersistentDb) -> Chain<B> {
+    pub fn new(db_ref: PersistentDb) -> Chain<B> {
+        Chain::with_genesis(db_ref, B::genesis())
+    }
+
+    /// Creates a chain which starts from the given
+    /// genesis block with the given configuration.
+    pub fn with_config(db_ref: PersistentDb, genesis: Arc<B>, mut config: ChainConfig) -> Chain<B> {
+        let mut chain = Chain::with_genesis(db_ref, genesis);
+        config.checkpoints.sort_by_key(|(height, _)| *height);
+        chain.config = config;
+        chain
+    }
+
+    /// Creates a chain which starts from the given genesis block,
+    /// storing its hash if the chain is opened for the first time.
+    ///
+    /// Returns `Err(ChainErr::GenesisMismatch)` if the chain
+    /// was created with a different genesis block.
+    pub fn new_with_genesis(
+        mut db_ref: PersistentDb,
+        genesis: Arc<B>,
+    ) -> Result<Chain<B>, ChainErr> {
+        let genesis_hash = genesis.block_hash().unwrap();
+
+        match db_ref.get(&GENESIS_KEY) {
+            Some(stored) => {
+                let mut buf = [0; 32];
+                buf.copy_from_slice(&stored);
+
+                if Hash(buf) != genesis_hash {
+                    return Err(ChainErr::GenesisMismatch {
+                        stored: Hash(buf),
+                        given: genesis_hash,
+                    });
+                }
+            }
+            None => {
+                db_ref.emplace(
+                    GENESIS_KEY.clone(),
+                    ElasticArray128::<u8>::from_slice(&genesis_hash.0),
+                );
+            }
+        }
+
+        Ok(Chain::with_genesis(db_ref, genesis))
+    }
+
+    /// Creates a chain which starts from the given genesis block
+    /// instead of `B::genesis()` without checking it against the
+    /// genesis block the chain was created with.
+    pub fn with_genesis(mut db_ref: PersistentDb, genesis: Arc<B>) -> Chain<B> {
         let tip_db_res = db_ref.get(&TIP_KEY);
         let canonical_tip = match tip_db_res.clone() {
             Some(tip) => {
@@ -186,7 +764,7 @@ impl<B: Block> Chain<B> {
                 let block_bytes = db_ref.get(&Hash(buf)).unwrap();
                 B::from_bytes(&block_bytes).unwrap()
             }
-            None => B::genesis(),
+            None => genesis.clone(),
         };
 
         let height = match db_ref.get(&CANONICAL_HEIGHT_KEY) {
@@ -206,6 +784,18 @@ impl<B: Block> Chain<B> {
 
         let height = height;
 
+        // Chains written before work was tracked
+        // have a total work equal to their height.
+        let total_work = match db_ref.get(&TOTAL_WORK_KEY) {
+            Some(total_work) => {
+                let mut buf = [0; 16];
+                buf.copy_from_slice(&total_work);
+
+                u128::from_be_bytes(buf)
+            }
+            None => height as u128,
+        };
+
         Chain {
             canonical_tip,
             orphan_pool: HashMap::with_capacity(MAX_ORPHANS),
@@ -214,9 +804,25 @@ impl<B: Block> Chain<B> {
             disconnected_heads_mapping: HashMap::with_capacity(MAX_ORPHANS),
             disconnected_heads_heights: HashMap::with_capacity(MAX_ORPHANS),
             disconnected_tips_mapping: HashMap::with_capacity(MAX_ORPHANS),
+            children_index: HashMap::with_capacity(MAX_ORPHANS),
             valid_tips: HashSet::with_capacity(MAX_ORPHANS),
+            invalid_set: HashSet::new(),
+            chain_id: DEFAULT_CHAIN_ID,
+            cross_chain_orphans: HashMap::new(),
+            state_db: None,
+            reorg_history: VecDeque::with_capacity(DEFAULT_REORG_HISTORY),
+            max_reorg_history: DEFAULT_REORG_HISTORY,
+            config: ChainConfig::default(),
+            writes_since_compaction: 0,
+            genesis_timestamp: genesis.timestamp(),
+            genesis,
+            height_notifier: Arc::new((Mutex::new(height), Condvar::new())),
+            height_cache: Arc::new(HeightCache(Mutex::new(LruCache::new(BLOCK_CACHE_SIZE)))),
+            subscribers: Mutex::new(Vec::new()),
+            disconnected_hashes: None,
             max_orphan_height: None,
             height,
+            total_work,
             db: db_ref,
         }
     }
@@ -226,96 +832,147 @@ impl<B: Block> Chain<B> {
     /// Returns `Err(ChainErr::NoSuchBlock)` if there is no block with
     /// the given hash in the canonical chain.
     pub fn rewind(&mut self, block_hash: &Hash) -> Result<(), ChainErr> {
-        if *block_hash == B::genesis().block_hash().unwrap() {
-            unimplemented!();
-        }
+        let new_tip = if *block_hash == self.genesis.block_hash().unwrap() {
+            // There is nothing to rewind on an empty chain
+            if self.height == 0 {
+                return Ok(());
+            }
 
-        if let Some(new_tip) = self.db.get(block_hash) {
-            let new_tip = B::from_bytes(&new_tip).unwrap();
+            self.genesis.clone()
+        } else {
+            match self.db.get(block_hash) {
+                Some(new_tip) => B::from_bytes(&new_tip).unwrap(),
+                None => return Err(ChainErr::NoSuchBlock(block_hash.clone())),
+            }
+        };
+
+        if let Some(checkpoint_height) = self.last_checkpoint_height() {
+            if new_tip.height() < checkpoint_height {
+                return Err(ChainErr::CheckpointViolation {
+                    block: block_hash.clone(),
+                    checkpoint_height,
+                });
+            }
+        }
 
-            // TODO: Make writes and deletes atomic
-            let mut current = self.canonical_tip.clone();
-            let mut inverse_height = 1;
+        // Collect the canonical blocks following the new tip
+        let mut removed = Vec::new();
+        let mut current = self.canonical_tip.clone();
 
-            // Remove canonical tip from the chain
-            // and mark it as a valid chain tip.
-            self.db.remove(&current.block_hash().unwrap());
+        while current.block_hash().unwrap() != *block_hash {
+            let parent_hash = current.parent_hash().unwrap();
 
-            // Add the old tip to the orphan pool
-            self.orphan_pool
-                .insert(current.block_hash().unwrap(), current.clone());
+            removed.push(current);
+            current = if parent_hash == *block_hash {
+                new_tip.clone()
+            } else {
+                B::from_bytes(&self.db.get(&parent_hash).unwrap()).unwrap()
+            };
+        }
 
-            // Mark old tip as a valid chain tip
-            self.validations_mapping
-                .insert(current.block_hash().unwrap(), OrphanType::ValidChainTip);
-            self.valid_tips.insert(current.block_hash().unwrap());
+        self.rewind_blocks(new_tip, removed);
+        Ok(())
+    }
 
-            let cur_height = current.height();
+    /// Rewinds the canonical chain to the given new tip, moving the
+    /// given canonical blocks, ordered from the tip downwards, to the
+    /// orphan pool. The blocks are taken as they are so that callers
+    /// which already decoded them do not read them again.
+    fn rewind_blocks(&mut self, new_tip: Arc<B>, removed: Vec<Arc<B>>) {
+        if removed.is_empty() {
+            return;
+        }
 
-            // Insert to heights mapping
-            if let Some(entries) = self.heights_mapping.get_mut(&cur_height) {
-                entries.insert(current.block_hash().unwrap(), 0);
+        // Stage all ledger deletes so that they are committed atomically
+        let mut batch = Vec::new();
+        let mut total_work = self.total_work;
+
+        for (inverse_height, block) in removed.iter().enumerate() {
+            let block_hash = block.block_hash().unwrap();
+            let cur_height = block.height();
+
+            // Remove block from db
+            batch.push(DbOp::Delete(block_hash.clone()));
+            batch.push(DbOp::Delete(block_height_key(&block_hash)));
+            batch.push(DbOp::Delete(canonical_hash_key(cur_height)));
+            batch.push(DbOp::Delete(extra_data_key(&block_hash)));
+            self.revert_state_transition(block);
+            total_work -= block.total_difficulty() as u128;
+
+            // Add the block to the orphan pool
+            self.orphan_pool.insert(block_hash.clone(), block.clone());
+            self.index_orphan(block);
+
+            // The old tip is marked as a valid chain tip and
+            // its parents as belonging to a valid chain.
+            if inverse_height == 0 {
+                self.validations_mapping
+                    .insert(block_hash.clone(), OrphanType::ValidChainTip);
+                self.valid_tips.insert(block_hash.clone());
             } else {
-                let mut hm = HashMap::new();
-                hm.insert(current.block_hash().unwrap(), 0);
-                self.heights_mapping.insert(cur_height, hm);
+                self.validations_mapping
+                    .insert(block_hash.clone(), OrphanType::BelongsToValidChain);
             }
 
-            // Try to update the maximum orphan height with
-            // the previous canonical tip's height.
-            self.update_max_orphan_height(current.height());
-
-            // Recurse parents and remove them until we
-            // reach the block with the given hash.
-            loop {
-                let parent_hash = current.parent_hash().unwrap();
+            // Insert to heights mapping
+            self.heights_mapping
+                .entry(cur_height)
+                .or_insert_with(HashMap::new)
+                .insert(block_hash, inverse_height as u64);
 
-                if parent_hash == *block_hash {
-                    break;
-                } else {
-                    let parent = B::from_bytes(&self.db.get(&parent_hash).unwrap()).unwrap();
-                    let cur_height = parent.height();
+            // Update max orphan height
+            self.update_max_orphan_height(cur_height);
+        }
 
-                    // Remove parent from db
-                    self.db.remove(&parent_hash);
+        // Evict removed heights from the height cache
+        {
+            let mut height_cache = self.height_cache.0.lock();
 
-                    // Add the parent to the orphan pool
-                    self.orphan_pool
-                        .insert(parent.block_hash().unwrap(), parent.clone());
+            for height in new_tip.height() + 1..=self.height {
+                height_cache.pop(&height);
+            }
+        }
 
-                    // Mark parent as belonging to a valid chain
-                    self.validations_mapping.insert(
-                        parent.block_hash().unwrap(),
-                        OrphanType::BelongsToValidChain,
-                    );
+        self.height = new_tip.height();
+        self.write_canonical_height(new_tip.height(), &mut batch);
+        self.write_total_work(total_work, &mut batch);
 
-                    // Insert to heights mapping
-                    if let Some(entries) = self.heights_mapping.get_mut(&cur_height) {
-                        entries.insert(parent.block_hash().unwrap(), inverse_height);
-                    } else {
-                        let mut hm = HashMap::new();
-                        hm.insert(parent.block_hash().unwrap(), inverse_height);
-                        self.heights_mapping.insert(cur_height, hm);
-                    }
+        // The genesis block is not stored in the ledger
+        if new_tip.height() == 0 {
+            batch.push(DbOp::Delete(TIP_KEY.clone()));
+        } else {
+            batch.push(DbOp::Put(
+                TIP_KEY.clone(),
+                ElasticArray128::<u8>::from_slice(&new_tip.block_hash().unwrap().to_vec()),
+            ));
+        }
 
-                    // Update max orphan height
-                    self.update_max_orphan_height(parent.height());
+        self.db.write_batch(batch);
+        self.notify_height(new_tip.height());
+        self.canonical_tip = new_tip;
 
-                    current = parent;
-                    inverse_height += 1;
-                }
+        for block in removed {
+            if let Some(ref mut disconnected_hashes) = self.disconnected_hashes {
+                disconnected_hashes.push(block.block_hash().unwrap());
             }
 
-            self.height = new_tip.height();
-            self.write_canonical_height(new_tip.height());
-            self.canonical_tip = new_tip;
-
-            Ok(())
-        } else {
-            Err(ChainErr::NoSuchBlock)
+            self.emit(ChainEvent::Disconnected(block));
         }
     }
 
+    /// Appends the given block and returns the hashes of the
+    /// blocks it disconnected from the canonical chain.
+    fn append_block_collecting_disconnected(
+        &mut self,
+        block: Arc<B>,
+    ) -> (Result<(), ChainErr>, Vec<Hash>) {
+        self.disconnected_hashes = Some(Vec::new());
+        let result = self.append_block(block);
+        let disconnected = self.disconnected_hashes.take().unwrap();
+
+        (result, disconnected)
+    }
+
     fn update_max_orphan_height(&mut self, new_height: u64) {
         if self.max_orphan_height.is_none() {
             self.max_orphan_height = Some(new_height);
@@ -328,10 +985,7 @@ impl<B: Block> Chain<B> {
         }
     }
 
-    // TODO: Make writes atomic
-    fn write_block(&mut self, block: Arc<B>) {
-        let block_hash = block.block_hash().unwrap();
-
+    fn write_block(&mut self, block: Arc<B>) -> Result<(), ChainErr> {
         // We can only write a block whose parent
         // hash is the hash of the current canonical
         // tip block.
@@ -340,18 +994,59 @@ impl<B: Block> Chain<B> {
             self.canonical_tip.block_hash().unwrap()
         );
 
+        let block_hash = block.block_hash().unwrap();
+
+        self.emplace_block(block)?;
+        self.prune_orphans();
+
+        // Disconnected chains that now follow the canonical
+        // tip must be consistent with its height.
+        let height = self.height;
+        self.discard_inconsistent_chains(&block_hash, height);
+        Ok(())
+    }
+
+    /// Writes a block on top of the canonical chain without checking
+    /// that it follows the canonical tip. Used to set up test states.
+    #[cfg(test)]
+    fn write_block_unchecked(&mut self, block: Arc<B>) -> Result<(), ChainErr> {
+        self.emplace_block(block)
+    }
+
+    /// Writes the given block to the ledger as the new canonical tip.
+    fn emplace_block(&mut self, block: Arc<B>) -> Result<(), ChainErr> {
+        let block_hash = block.block_hash().unwrap();
+
+        // Increment height
+        let height = self.height.checked_add(1).ok_or(ChainErr::HeightOverflow)?;
+
+        // Blocks are validated against checkpoints before being
+        // appended so a mismatch here means the chain is corrupt.
+        for (checkpoint_height, checkpoint_hash) in self.config.checkpoints.iter() {
+            if *checkpoint_height == height && *checkpoint_hash != block_hash {
+                panic!(
+                    "Canonical block {:?} at height {} does not match checkpoint {:?}",
+                    block_hash, height, checkpoint_hash
+                );
+            }
+        }
+
+        // Stage all ledger writes so that they are committed atomically
+        let mut batch = Vec::new();
+
         // Place block in the ledger
-        self.db.emplace(
+        batch.push(DbOp::Put(
             block_hash.clone(),
             ElasticArray128::<u8>::from_slice(&block.to_bytes()),
-        );
+        ));
 
         // Set new tip block
         self.canonical_tip = block.clone();
-        let mut height = decode_be_u64!(self.db.get(&CANONICAL_HEIGHT_KEY).unwrap()).unwrap();
 
-        // Increment height
-        height += 1;
+        batch.push(DbOp::Put(
+            TIP_KEY.clone(),
+            ElasticArray128::<u8>::from_slice(&block_hash.to_vec()),
+        ));
 
         // Set new height
         self.height = height;
@@ -359,25 +1054,57 @@ impl<B: Block> Chain<B> {
         let encoded_height = encode_be_u64!(height);
 
         // Write new height
-        self.write_canonical_height(height);
+        self.write_canonical_height(height, &mut batch);
 
-        // Write block height
-        let block_height_key = format!("{}.height", hex::encode(block_hash.to_vec()));
-        let block_height_key = crypto::hash_slice(block_height_key.as_bytes());
+        // Write new total work
+        let total_work = self.total_work + block.total_difficulty() as u128;
+        self.write_total_work(total_work, &mut batch);
 
-        self.db.emplace(
-            block_height_key,
+        // Write block height
+        batch.push(DbOp::Put(
+            block_height_key(&block_hash),
             ElasticArray128::<u8>::from_slice(&encoded_height),
-        );
+        ));
+
+        // Write the canonical block hash of the height
+        batch.push(DbOp::Put(
+            canonical_hash_key(height),
+            ElasticArray128::<u8>::from_slice(&block_hash.to_vec()),
+        ));
+
+        // Write block extra data
+        if let Some(extra_data) = block.extra_data() {
+            batch.push(DbOp::Put(
+                extra_data_key(&block_hash),
+                ElasticArray128::<u8>::from_slice(extra_data),
+            ));
+        }
+
+        self.db.write_batch(batch);
+        self.notify_height(height);
+        self.emit(ChainEvent::Connected(block.clone()));
+
+        // Apply block state transition
+        if let (Some(state_db), Some(transition)) = (&self.state_db, block.state_transition()) {
+            state_db.lock().apply_transition(&transition);
+        }
 
         // Remove block from orphan pool
-        self.orphan_pool.remove(&block_hash);
+        if self.orphan_pool.remove(&block_hash).is_some() {
+            self.unindex_orphan(&block);
+        }
 
         // Remove from height mappings
         if let Some(orphans) = self.heights_mapping.get_mut(&block.height()) {
             orphans.remove(&block_hash);
         }
 
+        self.writes_since_compaction += 1;
+
+        if self.writes_since_compaction >= HEIGHTS_COMPACTION_INTERVAL {
+            self.compact_heights_mapping();
+        }
+
         // Remove from valid tips
         self.valid_tips.remove(&block_hash);
 
@@ -450,30 +1177,396 @@ impl<B: Block> Chain<B> {
         if let Some(mut cb) = B::after_write() {
             cb(block);
         }
-    }
 
-    fn write_canonical_height(&mut self, height: u64) {
-        let encoded_height = encode_be_u64!(height);
-        self.db.emplace(
-            CANONICAL_HEIGHT_KEY.clone(),
-            ElasticArray128::<u8>::from_slice(&encoded_height),
-        );
+        Ok(())
     }
 
-    fn write_orphan(&mut self, orphan: Arc<B>, orphan_type: OrphanType, inverse_height: u64) {
-        let orphan_hash = orphan.block_hash().unwrap();
-        let height = orphan.height();
+    /// Removes the orphans whose height is below the canonical height
+    /// minus `MIN_HEIGHT`, as `append_block()` rejects blocks at those
+    /// heights. Their descendants can no longer be connected to the
+    /// canonical chain so they are removed as well, which drops whole
+    /// disconnected chains and valid chains that fork too deep.
+    pub fn prune_orphans(&mut self) {
+        let min_height = self.height.saturating_sub(MIN_HEIGHT);
 
-        match orphan_type {
-            OrphanType::ValidChainTip => {
-                self.valid_tips.insert(orphan.block_hash().unwrap());
-            }
-            _ => {
-                // Do nothing
-            }
+        for orphans in self.cross_chain_orphans.values_mut() {
+            orphans.retain(|_, orphan| orphan.height() >= min_height);
         }
 
-        // Write height mapping
+        self.cross_chain_orphans
+            .retain(|_, orphans| !orphans.is_empty());
+
+        let mut to_remove: Vec<Hash> = self
+            .heights_mapping
+            .iter()
+            .filter(|(height, _)| **height < min_height)
+            .flat_map(|(_, entries)| entries.keys().cloned())
+            .collect();
+
+        if to_remove.is_empty() {
+            return;
+        }
+
+        while let Some(hash) = to_remove.pop() {
+            let orphan = match self.orphan_pool.remove(&hash) {
+                Some(orphan) => orphan,
+                None => continue,
+            };
+
+            let height = orphan.height();
+
+            if let Some(children) = self.children_index.get(&hash) {
+                to_remove.extend(children.iter().cloned());
+            }
+
+            self.validations_mapping.remove(&hash);
+            self.valid_tips.remove(&hash);
+            self.disconnected_tips_mapping.remove(&hash);
+            self.disconnected_heads_mapping.remove(&hash);
+            self.disconnected_heads_heights.remove(&hash);
+            self.unindex_orphan(&orphan);
+
+            // Remove from heights mapping
+            if let Some(entries) = self.heights_mapping.get_mut(&height) {
+                entries.remove(&hash);
+
+                if entries.is_empty() {
+                    self.heights_mapping.remove(&height);
+                }
+            }
+        }
+
+        // Update max orphan height
+        self.max_orphan_height = self.heights_mapping.keys().max().cloned();
+    }
+
+    /// Removes the given valid tips along with their ancestors that
+    /// are not shared with any other valid tip from the orphan pool.
+    ///
+    /// Returns the hashes of all the removed orphans.
+    fn remove_valid_tips(&mut self, tips: &[Hash]) -> Vec<Hash> {
+        let mut removed = Vec::new();
+
+        for tip_hash in tips {
+            self.valid_tips.remove(tip_hash);
+        }
+
+        // Find ancestors which are shared with the remaining tips
+        let mut shared = HashSet::new();
+
+        for tip_hash in self.valid_tips.iter().cloned() {
+            let mut current = tip_hash;
+
+            while let Some(orphan) = self.orphan_pool.get(&current) {
+                let parent_hash = orphan.parent_hash().unwrap();
+
+                shared.insert(current);
+                current = parent_hash;
+            }
+        }
+
+        for tip_hash in tips {
+            let mut current = tip_hash.clone();
+
+            while let Some(orphan) = self.orphan_pool.get(&current).cloned() {
+                if shared.contains(&current) {
+                    break;
+                }
+
+                let height = orphan.height();
+
+                self.orphan_pool.remove(&current);
+                self.validations_mapping.remove(&current);
+                self.unindex_orphan(&orphan);
+
+                // Remove from heights mapping
+                if let Some(entries) = self.heights_mapping.get_mut(&height) {
+                    entries.remove(&current);
+
+                    if entries.is_empty() {
+                        self.heights_mapping.remove(&height);
+                    }
+                }
+
+                removed.push(current);
+                current = orphan.parent_hash().unwrap();
+            }
+        }
+
+        // Update max orphan height
+        self.max_orphan_height = self.heights_mapping.keys().max().cloned();
+
+        removed
+    }
+
+    /// Evicts orphans if the orphan pool is full so that an orphan
+    /// with the given parent can be added. Must only be called once
+    /// the orphan has passed validation.
+    fn make_room_for_orphan(&mut self, parent_hash: &Hash) -> Result<(), ChainErr> {
+        if self.orphan_pool.len() >= MAX_ORPHANS && !self.evict_orphans(parent_hash) {
+            return Err(ChainErr::TooManyOrphans);
+        }
+
+        Ok(())
+    }
+
+    /// Evicts orphans to make room for a new orphan whose parent has
+    /// the given hash. The disconnected chain with the lowest largest
+    /// tip height is evicted or, if there is none, the valid tip with
+    /// the lowest height. Chains containing the parent are kept.
+    ///
+    /// Returns `false` if there is nothing to evict.
+    fn evict_orphans(&mut self, parent_hash: &Hash) -> bool {
+        let parent_root = self.orphan_root(parent_hash);
+        let head = self
+            .disconnected_heads_heights
+            .iter()
+            .filter(|(head_hash, _)| **head_hash != parent_root)
+            .min_by_key(|(_, (largest_height, _))| *largest_height)
+            .map(|(head_hash, _)| head_hash.clone());
+
+        if let Some(head) = head {
+            self.remove_disconnected_chain(&head);
+            return true;
+        }
+
+        let tip = self
+            .valid_tips
+            .iter()
+            .filter(|tip_hash| !self.orphan_chain_contains(tip_hash, parent_hash))
+            .filter_map(|tip_hash| self.orphan_pool.get(tip_hash))
+            .min_by_key(|tip| tip.height())
+            .map(|tip| tip.block_hash().unwrap());
+
+        match tip {
+            Some(tip) => {
+                self.remove_valid_tips(&[tip]);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the disconnected chain with the given head
+    /// and all of its blocks from the orphan pool.
+    fn remove_disconnected_chain(&mut self, head_hash: &Hash) {
+        let mut to_remove = vec![head_hash.clone()];
+
+        while let Some(hash) = to_remove.pop() {
+            let orphan = match self.orphan_pool.remove(&hash) {
+                Some(orphan) => orphan,
+                None => continue,
+            };
+
+            let height = orphan.height();
+
+            if let Some(children) = self.children_index.get(&hash) {
+                to_remove.extend(children.iter().cloned());
+            }
+
+            self.validations_mapping.remove(&hash);
+            self.disconnected_tips_mapping.remove(&hash);
+            self.unindex_orphan(&orphan);
+
+            // Remove from heights mapping
+            if let Some(entries) = self.heights_mapping.get_mut(&height) {
+                entries.remove(&hash);
+
+                if entries.is_empty() {
+                    self.heights_mapping.remove(&height);
+                }
+            }
+        }
+
+        self.disconnected_heads_mapping.remove(head_hash);
+        self.disconnected_heads_heights.remove(head_hash);
+
+        // Update max orphan height
+        self.max_orphan_height = self
+            .heights_mapping
+            .iter()
+            .filter(|(_, entries)| !entries.is_empty())
+            .map(|(height, _)| *height)
+            .max();
+    }
+
+    /// Returns the hash of the oldest ancestor of the orphan with the
+    /// given hash which is in the orphan pool. Returns the given hash
+    /// if it does not belong to an orphan.
+    fn orphan_root(&self, hash: &Hash) -> Hash {
+        let mut current = hash.clone();
+
+        while let Some(orphan) = self.orphan_pool.get(&current) {
+            let parent_hash = orphan.parent_hash().unwrap();
+
+            if !self.orphan_pool.contains_key(&parent_hash) {
+                break;
+            }
+
+            current = parent_hash;
+        }
+
+        current
+    }
+
+    /// Returns `true` if the orphan with the given hash is the
+    /// orphan with the `tip_hash` or one of its ancestors.
+    fn orphan_chain_contains(&self, tip_hash: &Hash, hash: &Hash) -> bool {
+        let mut current = tip_hash.clone();
+
+        while let Some(orphan) = self.orphan_pool.get(&current) {
+            if current == *hash {
+                return true;
+            }
+
+            current = orphan.parent_hash().unwrap();
+        }
+
+        false
+    }
+
+    /// Demotes the valid tip with the given hash, removing it and its
+    /// ancestors from the orphan pool and marking them as invalid.
+    ///
+    /// Returns `Err(ChainErr::NoSuchBlock)` if there is no valid
+    /// tip with the given hash.
+    pub fn mark_tip_as_invalid(&mut self, tip_hash: &Hash) -> Result<(), ChainErr> {
+        if !self.valid_tips.contains(tip_hash) {
+            return Err(ChainErr::NoSuchBlock(tip_hash.clone()));
+        }
+
+        for hash in self.remove_valid_tips(&[tip_hash.clone()]) {
+            self.invalid_set.insert(hash);
+        }
+
+        Ok(())
+    }
+
+    /// Removes stale database entries of blocks that are now in the
+    /// orphan pool, e.g. blocks that have been moved there by a rewind.
+    ///
+    /// Also drops orphans that are no longer tracked by the chain,
+    /// which may be left behind by a past prune.
+    pub fn compact_db(&mut self) {
+        let orphan_hashes: Vec<Hash> = self.orphan_pool.keys().cloned().collect();
+
+        for orphan_hash in orphan_hashes {
+            if self.db.get(&orphan_hash).is_some() {
+                self.db.remove(&orphan_hash);
+            }
+
+            let extra_data_key = extra_data_key(&orphan_hash);
+
+            if self.db.get(&extra_data_key).is_some() {
+                self.db.remove(&extra_data_key);
+            }
+
+            if self.validations_mapping.get(&orphan_hash).is_none() {
+                let orphan = self.orphan_pool.remove(&orphan_hash).unwrap();
+                let height = orphan.height();
+
+                self.unindex_orphan(&orphan);
+
+                // Remove from heights mapping
+                if let Some(entries) = self.heights_mapping.get_mut(&height) {
+                    entries.remove(&orphan_hash);
+
+                    if entries.is_empty() {
+                        self.heights_mapping.remove(&height);
+                    }
+                }
+            }
+        }
+
+        // Update max orphan height
+        self.max_orphan_height = self.heights_mapping.keys().max().cloned();
+    }
+
+    /// Removes the entries of the heights mapping
+    /// for heights that no longer have any orphans.
+    pub fn compact_heights_mapping(&mut self) {
+        self.heights_mapping
+            .retain(|_, orphans| !orphans.is_empty());
+        self.writes_since_compaction = 0;
+    }
+
+    /// Registers a world-state store which is kept in sync
+    /// with the canonical chain from now on.
+    pub fn register_state_db(&mut self, state_db: Arc<Mutex<dyn StateDb>>) {
+        self.state_db = Some(state_db);
+    }
+
+    fn revert_state_transition(&self, block: &Arc<B>) {
+        if let (Some(state_db), Some(transition)) = (&self.state_db, block.state_transition()) {
+            state_db.lock().revert_transition(&transition);
+        }
+    }
+
+    fn write_total_work(&mut self, total_work: u128, batch: &mut Vec<DbOp>) {
+        self.total_work = total_work;
+        batch.push(DbOp::Put(
+            TOTAL_WORK_KEY.clone(),
+            ElasticArray128::<u8>::from_slice(&total_work.to_be_bytes()),
+        ));
+    }
+
+    /// Adds the given orphan to the children of its parent.
+    fn index_orphan(&mut self, orphan: &Arc<B>) {
+        let orphan_hash = orphan.block_hash().unwrap();
+        let children = self
+            .children_index
+            .entry(orphan.parent_hash().unwrap())
+            .or_insert_with(Vec::new);
+
+        if !children.contains(&orphan_hash) {
+            children.push(orphan_hash);
+        }
+    }
+
+    /// Removes the given orphan from the children of its parent.
+    fn unindex_orphan(&mut self, orphan: &Arc<B>) {
+        let orphan_hash = orphan.block_hash().unwrap();
+        let parent_hash = orphan.parent_hash().unwrap();
+
+        if let Some(children) = self.children_index.get_mut(&parent_hash) {
+            children.retain(|child| *child != orphan_hash);
+
+            if children.is_empty() {
+                self.children_index.remove(&parent_hash);
+            }
+        }
+    }
+
+    fn write_canonical_height(&self, height: u64, batch: &mut Vec<DbOp>) {
+        let encoded_height = encode_be_u64!(height);
+        batch.push(DbOp::Put(
+            CANONICAL_HEIGHT_KEY.clone(),
+            ElasticArray128::<u8>::from_slice(&encoded_height),
+        ));
+    }
+
+    /// Wakes up threads waiting for a height. Must be
+    /// called after the new height has been committed.
+    fn notify_height(&self, height: u64) {
+        let (notified_height, changed) = &*self.height_notifier;
+        *notified_height.lock() = height;
+        changed.notify_all();
+    }
+
+    fn write_orphan(&mut self, orphan: Arc<B>, orphan_type: OrphanType, inverse_height: u64) {
+        let orphan_hash = orphan.block_hash().unwrap();
+        let height = orphan.height();
+
+        match orphan_type {
+            OrphanType::ValidChainTip => {
+                self.valid_tips.insert(orphan.block_hash().unwrap());
+            }
+            _ => {
+                // Do nothing
+            }
+        }
+
+        // Write height mapping
         if let Some(height_entry) = self.heights_mapping.get_mut(&height) {
             if height_entry.get(&orphan_hash).is_none() {
                 height_entry.insert(orphan_hash.clone(), inverse_height);
@@ -487,6 +1580,7 @@ impl<B: Block> Chain<B> {
 
         // Write to orphan pool
         self.orphan_pool.insert(orphan_hash.clone(), orphan.clone());
+        self.index_orphan(&orphan);
 
         // Set max orphan height if this is the case
         self.update_max_orphan_height(height);
@@ -517,9 +1611,11 @@ impl<B: Block> Chain<B> {
                         // If the orphan directly follows the canonical
                         // tip, write it to the chain.
                         if orphan.parent_hash().unwrap() == self.canonical_tip.block_hash().unwrap()
+                            && self.height.checked_add(1) == Some(orphan.height())
+                            && self.validate_timestamp(orphan).is_ok()
                         {
                             if !done {
-                                self.write_block(orphan.clone());
+                                self.write_block(orphan.clone()).unwrap();
                             } else {
                                 break;
                             }
@@ -549,6 +1645,14 @@ impl<B: Block> Chain<B> {
                             let canonical_tip = self.canonical_tip.block_hash().unwrap();
 
                             if orphan_parent == canonical_tip {
+                                // Orphans with invalid heights or
+                                // timestamps are never written.
+                                if self.height.checked_add(1) != Some(orphan.height())
+                                    || self.validate_timestamp(orphan).is_err()
+                                {
+                                    continue;
+                                }
+
                                 buf.push((o.clone(), i_h.clone()));
                             } else if prev_valid_tips.contains(&orphan_parent) {
                                 // Mark old tip as belonging to valid chain
@@ -589,7 +1693,7 @@ impl<B: Block> Chain<B> {
                         if !done {
                             if let Some((to_write, _)) = buf.pop() {
                                 let to_write = self.orphan_pool.get(&to_write).unwrap();
-                                self.write_block(to_write.clone());
+                                self.write_block(to_write.clone()).unwrap();
                             }
                         }
 
@@ -617,36 +1721,99 @@ impl<B: Block> Chain<B> {
             .valid_tips
             .contains(&candidate_tip.block_hash().unwrap()));
 
-        // TODO: Possibly add an offset here so we don't switch
-        // chains that often on many chains competing for being
-        // canonical.
-        if candidate_tip.height() > self.height {
-            let mut to_write: VecDeque<Arc<B>> = VecDeque::new();
-            to_write.push_front(candidate_tip.clone());
+        // Blocks lower than the canonical height minus `MIN_HEIGHT`
+        // are never appended so this bounds the walk to the horizon.
+        let max_walk = candidate_tip
+            .height()
+            .saturating_sub(self.height.saturating_sub(MIN_HEIGHT + 1));
+        let mut to_write: VecDeque<Arc<B>> = VecDeque::with_capacity(max_walk as usize);
+        to_write.push_front(candidate_tip.clone());
+
+        // Find the horizon block i.e. the common
+        // ancestor of both the candidate tip and
+        // the canonical tip.
+        let horizon = {
+            let mut current = candidate_tip.parent_hash().unwrap();
+
+            // Recurse parents until we find a canonical block. The
+            // orphan pool is checked first as it is cheaper than the db.
+            while let Some(cur) = self.orphan_pool.get(&current) {
+                to_write.push_front(cur.clone());
+                current = cur.parent_hash().unwrap();
+            }
+
+            if current != self.genesis.block_hash().unwrap() && self.db.get(&current).is_none() {
+                return;
+            }
 
-            // Find the horizon block i.e. the common
-            // ancestor of both the candidate tip and
-            // the canonical tip.
-            let horizon = {
-                let mut current = candidate_tip.parent_hash().unwrap();
+            current
+        };
 
-                // Recurse parents until we find a canonical block
-                loop {
-                    if self.db.get(&current).is_some() {
-                        break;
-                    }
+        // Orphans appended before their ancestors were known
+        // have not had their timestamps validated yet.
+        if to_write.iter().any(|block| {
+            block.block_hash().unwrap() != horizon && self.validate_timestamp(block).is_err()
+        }) {
+            return;
+        }
 
-                    let cur = self.orphan_pool.get(&current).unwrap();
-                    to_write.push_front(cur.clone());
+        // Sum up the work of the canonical chain after the horizon,
+        // keeping the decoded blocks around for rewinding.
+        let mut canonical_work: u128 = 0;
+        let mut current = self.canonical_tip.clone();
+        let mut removed_blocks = Vec::new();
 
-                    current = cur.parent_hash().unwrap();
-                }
+        while current.block_hash().unwrap() != horizon {
+            let parent_hash = current.parent_hash().unwrap();
 
-                current
+            canonical_work += current.total_difficulty() as u128;
+            removed_blocks.push(current);
+            current = if parent_hash == self.genesis.block_hash().unwrap() {
+                self.genesis.clone()
+            } else {
+                self.query(&parent_hash).unwrap()
             };
+        }
+
+        let horizon_height = current.height();
+        let mut removed: Vec<Hash> = removed_blocks
+            .iter()
+            .map(|block| block.block_hash().unwrap())
+            .collect();
+
+        let candidate_work: u128 = to_write
+            .iter()
+            .map(|block| block.total_difficulty() as u128)
+            .sum();
+
+        // TODO: Possibly add an offset here so we don't switch
+        // chains that often on many chains competing for being
+        // canonical.
+        // The canonical chain is never rewound below a checkpoint
+        if let Some(checkpoint_height) = self.last_checkpoint_height() {
+            if horizon_height < checkpoint_height {
+                return;
+            }
+        }
+
+        if candidate_work > canonical_work {
+            // Leave the candidate as a valid tip if
+            // switching to it would rewind too deep.
+            if removed.len() as u64 > self.config.max_reorg_depth {
+                self.emit(ChainEvent::ReorgRejected {
+                    candidate_tip: candidate_tip.block_hash().unwrap(),
+                    depth: removed.len() as u64,
+                });
+
+                return;
+            }
+
+            let old_tip = self.canonical_tip.block_hash().unwrap();
 
             // Rewind to horizon
-            self.rewind(&horizon).unwrap();
+            self.rewind_blocks(current, removed_blocks);
+
+            let mut added = Vec::with_capacity(to_write.len());
 
             // Write the blocks from the candidate chain
             for block in to_write {
@@ -655,70 +1822,292 @@ impl<B: Block> Chain<B> {
                     continue;
                 }
 
-                self.write_block(block);
+                added.push(block.block_hash().unwrap());
+                self.write_block(block).unwrap();
             }
+
+            self.emit(ChainEvent::Reorg {
+                old_tip,
+                new_tip: self.canonical_tip.block_hash().unwrap(),
+                depth: removed.len() as u64,
+            });
+
+            removed.reverse();
+            self.record_reorg(ReorgRecord {
+                at_height: horizon_height,
+                removed,
+                added,
+                timestamp: Instant::now(),
+                _block: PhantomData,
+            });
         }
     }
 
-    /// Attempts to attach a disconnected chain tip to other
-    /// disconnected chains. Returns the final status of the tip.
-    fn attempt_attach(&mut self, tip_hash: &Hash, initial_status: OrphanType) -> OrphanType {
-        let mut status = initial_status;
-        let mut to_attach = Vec::with_capacity(MAX_ORPHANS);
-        let our_head_hash = self.disconnected_tips_mapping.get(tip_hash).unwrap();
+    /// Returns a receiver of the events emitted when the canonical
+    /// chain changes. Events are received in the order they happen.
+    pub fn subscribe(&mut self) -> Receiver<ChainEvent<B>> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.lock().push(sender);
+        receiver
+    }
 
-        // Find a matching disconnected chain head
-        for (head_hash, _) in self.disconnected_heads_mapping.iter() {
-            // Skip our tip
-            if head_hash == our_head_hash || head_hash == tip_hash {
-                continue;
-            }
+    /// Sends the given event to all subscribers,
+    /// dropping the ones that have hung up.
+    fn emit(&self, event: ChainEvent<B>) {
+        self.subscribers
+            .lock()
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
 
-            let head = self.orphan_pool.get(head_hash).unwrap();
+    fn record_reorg(&mut self, record: ReorgRecord<B>) {
+        while self.reorg_history.len() >= self.max_reorg_history {
+            self.reorg_history.pop_front();
+        }
 
-            // Attach chain to our tip
-            if head.parent_hash().unwrap() == *tip_hash {
-                to_attach.push(head_hash.clone());
-                status = OrphanType::BelongsToDisconnected;
-            }
+        if self.max_reorg_history > 0 {
+            self.reorg_history.push_back(record);
         }
+    }
 
-        let cur_head = self
-            .disconnected_tips_mapping
-            .get(tip_hash)
-            .unwrap()
-            .clone();
+    /// Returns the most recent switches of the
+    /// canonical chain, ordered oldest-first.
+    pub fn recent_reorgs(&self) -> &VecDeque<ReorgRecord<B>> {
+        &self.reorg_history
+    }
 
-        // Attach heads
-        for head in to_attach.iter() {
-            let tips = self.disconnected_heads_mapping.remove(head).unwrap();
-            self.disconnected_heads_heights.remove(head).unwrap();
+    /// Sets the maximum number of reorgs kept in the
+    /// reorg history, evicting the oldest ones if needed.
+    pub fn set_max_reorg_history(&mut self, max_reorg_history: usize) {
+        self.max_reorg_history = max_reorg_history;
 
-            let cur_tips =
-                if let Some(cur_tips) = self.disconnected_heads_mapping.get_mut(&cur_head) {
-                    cur_tips
-                } else {
-                    self.disconnected_heads_mapping
-                        .insert(cur_head.clone(), HashSet::new());
-                    self.disconnected_heads_mapping.get_mut(&cur_head).unwrap()
-                };
+        while self.reorg_history.len() > max_reorg_history {
+            self.reorg_history.pop_front();
+        }
+    }
 
-            let mut to_recurse = Vec::with_capacity(tips.len());
+    /// Sets how far ahead of the local time a block timestamp may be.
+    pub fn set_max_timestamp_drift(&mut self, max_timestamp_drift: chrono::Duration) {
+        self.config.max_timestamp_drift = max_timestamp_drift;
+    }
 
-            // Clear our the head from tips set if it exists
-            cur_tips.remove(&cur_head);
-            self.disconnected_tips_mapping.remove(&cur_head);
+    /// Sets the number of ancestors used for computing
+    /// the median timestamp of a block's ancestors.
+    pub fn set_median_time_span(&mut self, median_time_span: usize) {
+        self.config.median_time_span = median_time_span;
+    }
 
-            // Merge tips
-            for tip_hash in tips.iter() {
-                let tip = self.orphan_pool.get(tip_hash).unwrap();
-                let (largest_height, _) = self.disconnected_heads_heights.get(&cur_head).unwrap();
+    /// Returns the median timestamp of the last `median_time_span`
+    /// ancestors of the block with the given parent hash, looking
+    /// them up in both the database and the orphan pool.
+    ///
+    /// Returns `None` if any of the ancestors is unknown.
+    fn median_time_past(&self, parent_hash: &Hash) -> Option<DateTime<Utc>> {
+        let mut timestamps = Vec::with_capacity(self.config.median_time_span);
+        let mut current = parent_hash.clone();
+
+        while timestamps.len() < self.config.median_time_span {
+            let block = if current == self.genesis.block_hash().unwrap() {
+                self.genesis.clone()
+            } else if let Some(orphan) = self.orphan_pool.get(&current) {
+                orphan.clone()
+            } else {
+                self.query(&current)?
+            };
 
-                if let Some(head_mapping) = self.disconnected_tips_mapping.get_mut(tip_hash) {
-                    *head_mapping = cur_head.clone();
-                } else {
-                    self.disconnected_tips_mapping
-                        .insert(tip_hash.clone(), cur_head.clone());
+            timestamps.push(block.timestamp());
+
+            if block.height() == 0 {
+                break;
+            }
+
+            current = block.parent_hash().unwrap();
+        }
+
+        timestamps.sort();
+        timestamps.get(timestamps.len() / 2).cloned()
+    }
+
+    /// Checks the timestamp of the given block against the local
+    /// time and, if its ancestors are known, their median timestamp.
+    fn validate_timestamp(&self, block: &Arc<B>) -> Result<(), ChainErr> {
+        if block.timestamp() > Utc::now() + self.config.max_timestamp_drift {
+            return Err(ChainErr::InvalidTimestamp);
+        }
+
+        // The parents of cross-chain blocks do not reside on this
+        // chain so its median time past does not apply to them.
+        if self.is_cross_chain(block) {
+            return Ok(());
+        }
+
+        if let Some(median) = self.median_time_past(&block.parent_hash().unwrap()) {
+            if block.timestamp() <= median {
+                return Err(ChainErr::InvalidTimestamp);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the height of the highest checkpoint
+    /// that has been reached by the canonical chain.
+    fn last_checkpoint_height(&self) -> Option<u64> {
+        self.config
+            .checkpoints
+            .iter()
+            .map(|(height, _)| *height)
+            .filter(|height| *height <= self.height)
+            .max()
+    }
+
+    /// Rejects blocks which would fork the canonical chain at or below
+    /// the last reached checkpoint and blocks at checkpointed heights
+    /// which are not the checkpointed blocks.
+    fn validate_checkpoints(&self, block: &Arc<B>) -> Result<(), ChainErr> {
+        let block_hash = block.block_hash().unwrap();
+
+        if let Some(checkpoint_height) = self.last_checkpoint_height() {
+            if block.height() <= checkpoint_height {
+                return Err(ChainErr::CheckpointViolation {
+                    block: block_hash,
+                    checkpoint_height,
+                });
+            }
+        }
+
+        for (height, hash) in self.config.checkpoints.iter() {
+            if *height == block.height() && *hash != block_hash {
+                return Err(ChainErr::CheckpointViolation {
+                    block: block_hash,
+                    checkpoint_height: *height,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Walks the disconnected chain with the given head from each of
+    /// its tips, checking that every parent resolves inside the orphan
+    /// pool and that every height is that of the parent plus one.
+    fn validate_disconnected_chain(
+        &self,
+        head_hash: &Hash,
+        parent_height: u64,
+    ) -> Result<(), ChainErr> {
+        let err = || ChainErr::InconsistentFork(head_hash.clone());
+        let tips = self
+            .disconnected_heads_mapping
+            .get(head_hash)
+            .ok_or_else(err)?;
+
+        for tip_hash in tips.iter() {
+            let mut current = self.orphan_pool.get(tip_hash).ok_or_else(err)?;
+
+            while current.block_hash().unwrap() != *head_hash {
+                let parent_hash = current.parent_hash().ok_or_else(err)?;
+                let parent = self.orphan_pool.get(&parent_hash).ok_or_else(err)?;
+
+                if parent.height().checked_add(1) != Some(current.height()) {
+                    return Err(err());
+                }
+
+                current = parent;
+            }
+
+            if parent_height.checked_add(1) != Some(current.height()) {
+                return Err(err());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validates the disconnected chains whose heads follow the
+    /// block with the given hash and height, discarding the
+    /// inconsistent ones along with all of their blocks.
+    fn discard_inconsistent_chains(&mut self, parent_hash: &Hash, parent_height: u64) {
+        let to_discard: Vec<Hash> = self
+            .disconnected_heads_mapping
+            .keys()
+            .filter(|head_hash| {
+                let head = self.orphan_pool.get(head_hash).unwrap();
+
+                head.parent_hash().unwrap() == *parent_hash
+                    && self
+                        .validate_disconnected_chain(head_hash, parent_height)
+                        .is_err()
+            })
+            .cloned()
+            .collect();
+
+        for head_hash in to_discard.iter() {
+            self.remove_disconnected_chain(head_hash);
+        }
+    }
+
+    /// Attempts to attach a disconnected chain tip to other
+    /// disconnected chains. Returns the final status of the tip.
+    fn attempt_attach(&mut self, tip_hash: &Hash, initial_status: OrphanType) -> OrphanType {
+        let tip_height = self.orphan_pool.get(tip_hash).unwrap().height();
+        self.discard_inconsistent_chains(tip_hash, tip_height);
+
+        let mut status = initial_status;
+        let mut to_attach = Vec::with_capacity(MAX_ORPHANS);
+        let our_head_hash = self.disconnected_tips_mapping.get(tip_hash).unwrap();
+
+        // Find a matching disconnected chain head
+        for (head_hash, _) in self.disconnected_heads_mapping.iter() {
+            // Skip our tip
+            if head_hash == our_head_hash || head_hash == tip_hash {
+                continue;
+            }
+
+            let head = self.orphan_pool.get(head_hash).unwrap();
+
+            // Attach chain to our tip
+            if head.parent_hash().unwrap() == *tip_hash {
+                to_attach.push(head_hash.clone());
+                status = OrphanType::BelongsToDisconnected;
+            }
+        }
+
+        let cur_head = self
+            .disconnected_tips_mapping
+            .get(tip_hash)
+            .unwrap()
+            .clone();
+
+        // Attach heads
+        for head in to_attach.iter() {
+            let tips = self.disconnected_heads_mapping.remove(head).unwrap();
+            self.disconnected_heads_heights.remove(head).unwrap();
+
+            let cur_tips =
+                if let Some(cur_tips) = self.disconnected_heads_mapping.get_mut(&cur_head) {
+                    cur_tips
+                } else {
+                    self.disconnected_heads_mapping
+                        .insert(cur_head.clone(), HashSet::new());
+                    self.disconnected_heads_mapping.get_mut(&cur_head).unwrap()
+                };
+
+            let mut to_recurse = Vec::with_capacity(tips.len());
+
+            // Clear our the head from tips set if it exists
+            cur_tips.remove(&cur_head);
+            self.disconnected_tips_mapping.remove(&cur_head);
+
+            // Merge tips
+            for tip_hash in tips.iter() {
+                let tip = self.orphan_pool.get(tip_hash).unwrap();
+                let (largest_height, _) = self.disconnected_heads_heights.get(&cur_head).unwrap();
+
+                if let Some(head_mapping) = self.disconnected_tips_mapping.get_mut(tip_hash) {
+                    *head_mapping = cur_head.clone();
+                } else {
+                    self.disconnected_tips_mapping
+                        .insert(tip_hash.clone(), cur_head.clone());
                 }
 
                 // Update heights entry if new tip height is larger
@@ -750,6 +2139,7 @@ impl<B: Block> Chain<B> {
         status: &mut OrphanType,
     ) {
         assert!(self.valid_tips.contains(&tip.block_hash().unwrap()));
+        self.discard_inconsistent_chains(&tip.block_hash().unwrap(), tip.height());
 
         let iterable = self
             .disconnected_heads_heights
@@ -896,8 +2286,8 @@ impl<B: Block> Chain<B> {
     }
 
     /// Returns an atomic reference to the genesis block in the chain.
-    pub fn genesis() -> Arc<B> {
-        B::genesis()
+    pub fn genesis(&self) -> Arc<B> {
+        self.genesis.clone()
     }
 
     pub fn query(&self, hash: &Hash) -> Option<Arc<B>> {
@@ -908,57 +2298,178 @@ impl<B: Block> Chain<B> {
         }
     }
 
+    /// Returns the extra data of the canonical block with the given hash.
+    pub fn query_extra_data(&self, hash: &Hash) -> Option<Vec<u8>> {
+        self.db.get(&extra_data_key(hash)).map(|data| data.to_vec())
+    }
+
     pub fn query_by_height(&self, height: u64) -> Option<Arc<B>> {
-        unimplemented!();
+        if height > self.height {
+            return None;
+        }
+
+        if height == 0 {
+            return Some(self.genesis.clone());
+        }
+
+        if let Some(hash) = self.db.get(&canonical_hash_key(height)) {
+            let mut buf = [0; 32];
+            buf.copy_from_slice(&hash);
+
+            return self.query(&Hash(buf));
+        }
+
+        // Chains written before the height index was
+        // introduced must be walked from the tip.
+        let mut current = self.canonical_tip.clone();
+
+        // Walk the canonical chain backwards
+        while current.height() > height {
+            current = self.query(&current.parent_hash().unwrap())?;
+        }
+
+        Some(current)
     }
 
+    /// Returns the height of the canonical block with the given hash.
+    ///
+    /// Like `query()`, this returns `None` for blocks in the orphan pool.
     pub fn block_height(&self, hash: &Hash) -> Option<u64> {
-        unimplemented!();
+        if *hash == self.genesis.block_hash().unwrap() {
+            return Some(0);
+        }
+
+        self.db
+            .get(&block_height_key(hash))
+            .map(|height| decode_be_u64!(&height).unwrap())
+    }
+
+    /// Returns the hashes of canonical blocks starting from the tip,
+    /// densely spaced for the last 10 blocks and then exponentially
+    /// sparser, always ending with the genesis block.
+    pub fn block_locator(&self) -> Vec<Hash> {
+        let mut locator = Vec::new();
+        let mut height = self.height;
+        let mut step = 1;
+
+        loop {
+            locator.push(self.query_by_height(height).unwrap().block_hash().unwrap());
+
+            if height == 0 {
+                break;
+            }
+
+            if locator.len() >= 10 {
+                step *= 2;
+            }
+
+            height = height.saturating_sub(step);
+        }
+
+        locator
+    }
+
+    /// Given a locator as returned by `block_locator()`, returns
+    /// the highest hash in it that is on our canonical chain.
+    pub fn find_common_ancestor(&self, hashes: &[Hash]) -> Option<Hash> {
+        hashes
+            .iter()
+            .filter_map(|hash| self.block_height(hash).map(|height| (height, hash)))
+            .max_by_key(|(height, _)| *height)
+            .map(|(_, hash)| hash.clone())
     }
 
     pub fn append_block(&mut self, block: Arc<B>) -> Result<(), ChainErr> {
+        if block.size_bytes() > self.config.max_block_size {
+            return Err(ChainErr::BlockTooLarge);
+        }
+
+        let block_hash = block.block_hash().unwrap();
         let min_height = if self.height > MIN_HEIGHT {
             self.height - MIN_HEIGHT
         } else {
             1
         };
-
-        if block.height() > self.height + MAX_HEIGHT || block.height() < min_height {
-            return Err(ChainErr::BadHeight);
+        let max_height = self.height.saturating_add(MAX_HEIGHT);
+
+        if block.height() > max_height || block.height() < min_height {
+            return Err(ChainErr::BadHeight {
+                block: block_hash,
+                got: block.height(),
+                expected_min: min_height,
+                expected_max: max_height,
+            });
         }
 
-        let block_hash = block.block_hash().unwrap();
+        if self.invalid_set.contains(&block_hash) {
+            return Err(ChainErr::MarkedInvalid);
+        }
 
         // Check for existence
         if self.orphan_pool.get(&block_hash).is_some() || self.db.get(&block_hash).is_some() {
             return Err(ChainErr::AlreadyInChain);
         }
 
+        self.validate_checkpoints(&block)?;
+        self.validate_timestamp(&block)?;
+
+        // Blocks with a parent on another chain are
+        // kept apart from the orphans of this chain.
+        if let Some(parent_chain_id) = block.parent_chain_id() {
+            if parent_chain_id != self.chain_id {
+                let already_stored = self
+                    .cross_chain_orphans
+                    .get(&parent_chain_id)
+                    .map(|orphans| orphans.get(&block_hash).is_some())
+                    .unwrap_or(false);
+
+                if already_stored {
+                    return Err(ChainErr::AlreadyInChain);
+                }
+
+                // The limit is shared by all chain ids since
+                // peers are free to choose any chain id.
+                if self.cross_chain_orphan_total() >= MAX_CROSS_CHAIN_ORPHANS {
+                    return Err(ChainErr::TooManyOrphans);
+                }
+
+                self.cross_chain_orphans
+                    .entry(parent_chain_id)
+                    .or_insert_with(HashMap::new)
+                    .insert(block_hash, block);
+
+                return Ok(());
+            }
+        }
+
         let tip = &self.canonical_tip;
 
         if let Some(parent_hash) = block.parent_hash() {
             // First attempt to place the block after the
             // tip canonical block.
             if parent_hash == tip.block_hash().unwrap() {
+                let next_height = self.height.checked_add(1).ok_or(ChainErr::HeightOverflow)?;
+
                 // The height must be equal to that of the parent plus one
-                if block.height() != self.height + 1 {
-                    return Err(ChainErr::BadHeight);
+                if block.height() != next_height {
+                    return Err(ChainErr::BadHeight {
+                        block: block_hash,
+                        got: block.height(),
+                        expected_min: next_height,
+                        expected_max: next_height,
+                    });
                 }
 
-                let height = block.height();
-
                 // Write block to the chain
-                self.write_block(block);
+                self.write_block(block)?;
 
                 // Process orphans
-                self.process_orphans(height + 1);
+                if let Some(start_height) = next_height.checked_add(1) {
+                    self.process_orphans(start_height);
+                }
 
                 Ok(())
             } else {
-                if self.orphan_pool.len() >= MAX_ORPHANS {
-                    return Err(ChainErr::TooManyOrphans);
-                }
-
                 // If the parent exists and it is not the canonical
                 // tip this means that this block is represents a
                 // potential fork in the chain so we add it to the
@@ -970,9 +2481,16 @@ impl<B: Block> Chain<B> {
 
                         // The height must be equal to that of the parent plus one
                         if height != parent_height + 1 {
-                            return Err(ChainErr::BadHeight);
+                            return Err(ChainErr::BadHeight {
+                                block: block_hash,
+                                got: height,
+                                expected_min: parent_height + 1,
+                                expected_max: parent_height + 1,
+                            });
                         }
 
+                        self.make_room_for_orphan(&parent_hash)?;
+
                         let mut status = OrphanType::ValidChainTip;
                         let mut tip = block.clone();
                         let mut _inverse_height = 0;
@@ -980,11 +2498,9 @@ impl<B: Block> Chain<B> {
                         self.write_orphan(block, OrphanType::ValidChainTip, 0);
                         self.attempt_attach_valid(&mut tip, &mut _inverse_height, &mut status);
 
-                        if let OrphanType::ValidChainTip = status {
-                            // Do nothing
-                        } else {
-                            self.attempt_switch(tip);
-                        }
+                        // The tip may outweigh the canonical
+                        // chain even if it is not longer.
+                        self.attempt_switch(tip);
 
                         Ok(())
                     }
@@ -995,9 +2511,16 @@ impl<B: Block> Chain<B> {
 
                             // The height must be equal to that of the parent plus one
                             if height != parent_block.height() + 1 {
-                                return Err(ChainErr::BadHeight);
+                                return Err(ChainErr::BadHeight {
+                                    block: block_hash,
+                                    got: height,
+                                    expected_min: parent_block.height() + 1,
+                                    expected_max: parent_block.height() + 1,
+                                });
                             }
 
+                            self.make_room_for_orphan(&parent_hash)?;
+
                             let parent_status =
                                 self.validations_mapping.get_mut(&parent_hash).unwrap();
 
@@ -1175,6 +2698,8 @@ impl<B: Block> Chain<B> {
 
                             Ok(())
                         } else {
+                            self.make_room_for_orphan(&parent_hash)?;
+
                             // Add first to disconnected mappings
                             let mut set = HashSet::new();
                             set.insert(block_hash.clone());
@@ -1199,6 +2724,7 @@ impl<B: Block> Chain<B> {
 
                             // Add block to orphan pool
                             self.orphan_pool.insert(block_hash.clone(), block.clone());
+                            self.index_orphan(&block);
 
                             let status =
                                 self.attempt_attach(&block_hash, OrphanType::DisconnectedTip);
@@ -1241,538 +2767,1097 @@ impl<B: Block> Chain<B> {
         }
     }
 
-    pub fn height(&self) -> u64 {
-        self.height
+    /// Returns the id of the chain.
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id
     }
 
-    pub fn canonical_tip(&self) -> Arc<B> {
-        self.canonical_tip.clone()
+    /// Sets the id of the chain.
+    pub fn set_chain_id(&mut self, chain_id: u64) {
+        self.chain_id = chain_id;
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::easy_chain::block::EasyBlock;
-    use chrono::prelude::*;
-    use quickcheck::*;
-    use rand::*;
+    /// Returns the number of stored blocks whose parents
+    /// reside on the chain with the given id.
+    pub fn cross_chain_orphan_count(&self, chain_id: u64) -> usize {
+        match self.cross_chain_orphans.get(&chain_id) {
+            Some(orphans) => orphans.len(),
+            None => 0,
+        }
+    }
 
-    macro_rules! count {
-        () => (0);
-        ($fst:expr) => (1);
-        ($fst:expr, $snd:expr) => (2);
-        ($fst:expr, $snd:expr $(, $v:expr)*) => (1 + count!($snd $(, $v)*));
+    /// Returns the number of stored blocks whose
+    /// parents reside on any other chain.
+    fn cross_chain_orphan_total(&self) -> usize {
+        self.cross_chain_orphans
+            .values()
+            .map(|orphans| orphans.len())
+            .sum()
     }
 
-    macro_rules! set {
-        ($fst:expr $(, $v:expr)*) => ({
-            let mut set = HashSet::with_capacity(count!($fst $(, $v)*));
+    /// Returns `true` if the parent of the given
+    /// block resides on a different chain.
+    fn is_cross_chain(&self, block: &Arc<B>) -> bool {
+        match block.parent_chain_id() {
+            Some(parent_chain_id) => parent_chain_id != self.chain_id,
+            None => false,
+        }
+    }
 
-            set.insert($fst);
-            $(set.insert($v);)*
+    /// Writes a block which follows the canonical tip without
+    /// processing any orphans which may now follow it.
+    ///
+    /// Meant for bulk imports of in-order blocks. Call
+    /// `flush_orphan_processing()` once the import is done.
+    pub fn write_block_raw(&mut self, block: Arc<B>) -> Result<(), ChainErr> {
+        if block.size_bytes() > self.config.max_block_size {
+            return Err(ChainErr::BlockTooLarge);
+        }
 
-            set
-        });
-    }
+        let block_hash = block.block_hash().unwrap();
 
-    use std::hash::Hasher;
-    use std::sync::atomic::{AtomicUsize, Ordering};
+        if self.invalid_set.contains(&block_hash) {
+            return Err(ChainErr::MarkedInvalid);
+        }
 
-    /// Nonce used for creating unique `DummyBlock` hashes
-    static NONCE: AtomicUsize = AtomicUsize::new(0);
+        // Check for existence
+        if self.orphan_pool.get(&block_hash).is_some() || self.db.get(&block_hash).is_some() {
+            return Err(ChainErr::AlreadyInChain);
+        }
 
-    #[derive(Clone, Debug)]
-    /// Dummy block used for testing
-    struct DummyBlock {
-        hash: Hash,
-        parent_hash: Hash,
-        height: u64,
-    }
+        self.validate_checkpoints(&block)?;
+        self.validate_timestamp(&block)?;
 
-    impl DummyBlock {
-        pub fn new(parent_hash: Option<Hash>, height: u64) -> DummyBlock {
-            let hash =
-                crypto::hash_slice(&format!("block-{}", NONCE.load(Ordering::Relaxed)).as_bytes());
-            NONCE.fetch_add(1, Ordering::Relaxed);
-            let parent_hash = parent_hash.unwrap();
+        let parent_hash = block.parent_hash().ok_or(ChainErr::NoParentHash)?;
 
-            DummyBlock {
-                hash,
-                parent_hash,
-                height,
-            }
+        if parent_hash != self.canonical_tip.block_hash().unwrap() {
+            return Err(ChainErr::InvalidParent {
+                block: block_hash,
+                parent: parent_hash,
+            });
         }
-    }
 
-    impl PartialEq for DummyBlock {
-        fn eq(&self, other: &DummyBlock) -> bool {
-            self.block_hash().unwrap() == other.block_hash().unwrap()
+        let next_height = self.height.checked_add(1).ok_or(ChainErr::HeightOverflow)?;
+
+        // The height must be equal to that of the parent plus one
+        if block.height() != next_height {
+            return Err(ChainErr::BadHeight {
+                block: block_hash,
+                got: block.height(),
+                expected_min: next_height,
+                expected_max: next_height,
+            });
         }
-    }
 
-    impl Eq for DummyBlock {}
+        self.write_block(block)
+    }
 
-    impl HashTrait for DummyBlock {
-        fn hash<H: Hasher>(&self, state: &mut H) {
-            self.block_hash().unwrap().hash(state);
+    /// Attaches the orphans that follow the canonical tip
+    /// after blocks have been written with `write_block_raw()`.
+    ///
+    /// Orphans at or below the canonical height cannot follow
+    /// the tip so the scan starts right above it.
+    pub fn flush_orphan_processing(&mut self) {
+        if let Some(start_height) = self.height.checked_add(1) {
+            self.process_orphans(start_height);
         }
     }
 
-    impl Block for DummyBlock {
-        fn genesis() -> Arc<Self> {
-            let genesis = DummyBlock {
-                hash: Hash::NULL,
-                parent_hash: Hash::NULL,
-                height: 0,
+    /// Appends the given blocks with `append_blocks()`,
+    /// returning the first error that was encountered.
+    pub fn batch_append(&mut self, blocks: Vec<Arc<B>>) -> Result<(), ChainErr> {
+        self.append_blocks(blocks)
+            .into_iter()
+            .map(|(_, result)| result)
+            .find(|result| result.is_err())
+            .unwrap_or(Ok(()))
+    }
+
+    /// Appends the given blocks in order of height, writing the ones
+    /// that follow the canonical tip without processing orphans
+    /// in between unless some orphan follows the written block.
+    ///
+    /// Returns the result of appending each block.
+    pub fn append_blocks(&mut self, mut blocks: Vec<Arc<B>>) -> Vec<(Hash, Result<(), ChainErr>)> {
+        let mut results = Vec::with_capacity(blocks.len());
+        blocks.sort_by_key(|block| block.height());
+
+        for block in blocks {
+            let block_hash = block.block_hash().unwrap();
+            let result = if block.parent_hash() == self.canonical_tip.block_hash() {
+                let result = self.write_block_raw(block);
+
+                // Orphans following the written block would be stranded
+                // once another block is written on top of it so they
+                // are attached right away, as `append_block()` does.
+                if result.is_ok() && self.children_index.contains_key(&block_hash) {
+                    self.flush_orphan_processing();
+                }
+
+                result
+            } else {
+                self.append_block(block)
             };
 
-            Arc::new(genesis)
+            results.push((block_hash, result));
         }
 
-        fn parent_hash(&self) -> Option<Hash> {
-            Some(self.parent_hash.clone())
-        }
+        self.flush_orphan_processing();
+        results
+    }
 
-        fn block_hash(&self) -> Option<Hash> {
-            Some(self.hash.clone())
-        }
+    /// Returns the time elapsed between the
+    /// genesis block and the given block.
+    pub fn block_age(&self, block: &B) -> chrono::Duration {
+        block.timestamp() - self.genesis_timestamp
+    }
 
-        fn merkle_root(&self) -> Option<Hash> {
-            unimplemented!();
-        }
-
-        fn timestamp(&self) -> DateTime<Utc> {
-            unimplemented!();
-        }
+    /// Returns a rough estimate of the memory in bytes
+    /// used by the in-memory structures of the chain.
+    pub fn estimate_storage_bytes(&self) -> usize {
+        let orphans_bytes: usize = self
+            .orphan_pool
+            .values()
+            .map(|orphan| orphan.estimated_serialized_size())
+            .sum();
+
+        let heights_bytes: usize = self
+            .heights_mapping
+            .values()
+            .map(|orphans| map_bytes(orphans))
+            .sum();
+
+        let cross_chain_bytes: usize = self
+            .cross_chain_orphans
+            .values()
+            .map(|orphans| {
+                map_bytes(orphans)
+                    + orphans
+                        .values()
+                        .map(|orphan| orphan.estimated_serialized_size())
+                        .sum::<usize>()
+            })
+            .sum();
+
+        orphans_bytes
+            + heights_bytes
+            + cross_chain_bytes
+            + map_bytes(&self.cross_chain_orphans)
+            + map_bytes(&self.heights_mapping)
+            + map_bytes(&self.validations_mapping)
+            + self.valid_tips.capacity() * mem::size_of::<Hash>()
+            + map_bytes(&self.disconnected_heads_mapping)
+            + map_bytes(&self.disconnected_heads_heights)
+            + map_bytes(&self.disconnected_tips_mapping)
+    }
 
-        fn height(&self) -> u64 {
-            self.height
-        }
+    /// Returns the heights which have at least one known
+    /// block, either canonical or orphan.
+    pub fn known_heights(&self) -> BTreeSet<u64> {
+        let mut heights: BTreeSet<u64> = (1..=self.height).collect();
 
-        fn after_write() -> Option<Box<FnMut(Arc<Self>)>> {
-            None
+        for (height, orphans) in self.heights_mapping.iter() {
+            if !orphans.is_empty() {
+                heights.insert(*height);
+            }
         }
 
-        fn to_bytes(&self) -> Vec<u8> {
-            let mut buf = Vec::new();
-            let height = encode_be_u64!(self.height);
+        heights
+    }
 
-            buf.extend_from_slice(&height);
-            buf.extend_from_slice(&self.hash.0.to_vec());
-            buf.extend_from_slice(&self.parent_hash.0.to_vec());
+    pub fn height(&self) -> u64 {
+        self.height
+    }
 
-            buf
-        }
+    pub fn canonical_tip(&self) -> Arc<B> {
+        self.canonical_tip.clone()
+    }
 
-        fn from_bytes(bytes: &[u8]) -> Result<Arc<Self>, &'static str> {
-            let mut buf = bytes.to_vec();
-            let height_bytes: Vec<u8> = buf.drain(..8).collect();
-            let height = decode_be_u64!(&height_bytes).unwrap();
-            let hash_bytes: Vec<u8> = buf.drain(..32).collect();
-            let parent_hash_bytes = buf;
-            let mut hash = [0; 32];
-            let mut parent_hash = [0; 32];
+    pub fn total_work(&self) -> u128 {
+        self.total_work
+    }
 
-            hash.copy_from_slice(&hash_bytes);
-            parent_hash.copy_from_slice(&parent_hash_bytes);
+    /// Returns the number of tips of valid chains
+    /// that descend from the canonical chain.
+    pub fn valid_tips_count(&self) -> usize {
+        self.valid_tips.len()
+    }
 
-            let hash = Hash(hash);
-            let parent_hash = Hash(parent_hash);
+    /// Returns the number of tips of disconnected chains.
+    pub fn disconnected_tips_count(&self) -> usize {
+        self.disconnected_tips_mapping.len()
+    }
 
-            Ok(Arc::new(DummyBlock {
-                height,
-                hash,
-                parent_hash,
-            }))
-        }
+    /// Returns the number of heads of disconnected chains.
+    pub fn disconnected_heads_count(&self) -> usize {
+        self.disconnected_heads_mapping.len()
     }
 
-    #[test]
-    fn stages_append_test1() {
-        let db = test_helpers::init_tempdb();
-        let mut hard_chain = Chain::<DummyBlock>::new(db);
+    /// Returns the tips of both valid and disconnected chains,
+    /// sorted by descending height.
+    pub fn tips(&self) -> Vec<TipInfo> {
+        let mut tips: Vec<TipInfo> = self
+            .valid_tips
+            .iter()
+            .chain(self.disconnected_tips_mapping.keys())
+            .filter_map(|tip_hash| {
+                let tip = self.orphan_pool.get(tip_hash)?;
+                let status = self.validations_mapping.get(tip_hash)?;
+
+                Some(TipInfo {
+                    hash: tip_hash.clone(),
+                    height: tip.height(),
+                    status: *status,
+                    fork_length: self.fork_blocks(tip_hash).unwrap().len() as u64,
+                })
+            })
+            .collect();
+
+        tips.sort_by(|a, b| b.height.cmp(&a.height));
+        tips
+    }
 
-        let mut A = DummyBlock::new(Some(Hash::NULL), 1);
-        let A = Arc::new(A);
+    /// Returns the orphans from the one with the given hash down to the
+    /// one whose parent is either canonical or, in case of disconnected
+    /// chains, unknown.
+    ///
+    /// Returns `None` if there is no orphan with the given hash.
+    pub fn fork_blocks(&self, tip: &Hash) -> Option<Vec<Arc<B>>> {
+        let mut current = self.orphan_pool.get(tip)?;
+        let mut blocks = vec![current.clone()];
 
-        let mut B = DummyBlock::new(Some(A.block_hash().unwrap()), 2);
-        let B = Arc::new(B);
+        while let Some(parent) = self.orphan_pool.get(&current.parent_hash().unwrap()) {
+            blocks.push(parent.clone());
+            current = parent;
+        }
 
-        let mut C = DummyBlock::new(Some(B.block_hash().unwrap()), 3);
-        let C = Arc::new(C);
+        Some(blocks)
+    }
 
-        let mut D = DummyBlock::new(Some(C.block_hash().unwrap()), 4);
-        let D = Arc::new(D);
+    /// Returns the number of parent links that can be followed
+    /// through the stored blocks from the canonical tip to genesis.
+    pub fn tip_distance_to_genesis(&self) -> u64 {
+        let genesis_hash = self.genesis.block_hash().unwrap();
+        let mut current = self.canonical_tip.clone();
+        let mut distance = 0;
 
-        let mut E = DummyBlock::new(Some(D.block_hash().unwrap()), 5);
-        let E = Arc::new(E);
+        if current.block_hash().unwrap() == genesis_hash {
+            return 0;
+        }
 
-        let mut F = DummyBlock::new(Some(E.block_hash().unwrap()), 6);
-        let F = Arc::new(F);
+        loop {
+            let parent_hash = current.parent_hash().unwrap();
 
-        let mut G = DummyBlock::new(Some(F.block_hash().unwrap()), 7);
-        let G = Arc::new(G);
+            distance += 1;
 
-        let mut B_prime = DummyBlock::new(Some(A.block_hash().unwrap()), 2);
-        let B_prime = Arc::new(B_prime);
+            if parent_hash == genesis_hash {
+                break;
+            }
 
-        let mut C_prime = DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3);
-        let C_prime = Arc::new(C_prime);
+            current = match self.query(&parent_hash) {
+                Some(parent) => parent,
+                None => break,
+            };
+        }
 
-        let mut D_prime = DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4);
-        let D_prime = Arc::new(D_prime);
+        distance
+    }
 
-        let mut E_prime = DummyBlock::new(Some(D_prime.block_hash().unwrap()), 5);
-        let E_prime = Arc::new(E_prime);
+    /// Verifies that the stored height of the chain matches
+    /// the distance from the canonical tip to genesis.
+    pub fn verify_height(&self) -> Result<(), ChainErr> {
+        let actual = self.tip_distance_to_genesis();
 
-        let mut C_second = DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3);
-        let C_second = Arc::new(C_second);
+        if actual == self.height {
+            Ok(())
+        } else {
+            Err(ChainErr::HeightMismatch {
+                stored: self.height,
+                actual,
+            })
+        }
+    }
 
-        let mut D_second = DummyBlock::new(Some(C_second.block_hash().unwrap()), 4);
-        let D_second = Arc::new(D_second);
+    /// Follows the parent links of the canonical chain from
+    /// the tip to genesis, checking that each parent is stored
+    /// and that heights decrease by exactly one.
+    ///
+    /// Returns `Err((height, hash))` of the block whose parent is
+    /// missing or of the parent whose height does not match.
+    pub fn verify_chain_contiguity(&self) -> Result<(), (u64, Hash)> {
+        let genesis_hash = self.genesis.block_hash().unwrap();
+        let mut current = self.canonical_tip.clone();
+
+        while current.height() > 0 {
+            let parent_hash = current.parent_hash().unwrap();
+
+            if parent_hash == genesis_hash {
+                if current.height() == 1 {
+                    return Ok(());
+                } else {
+                    return Err((current.height(), current.block_hash().unwrap()));
+                }
+            }
 
-        let mut E_second = DummyBlock::new(Some(D_second.block_hash().unwrap()), 5);
-        let E_second = Arc::new(E_second);
+            let parent = match self.query(&parent_hash) {
+                Some(parent) => parent,
+                None => return Err((current.height(), current.block_hash().unwrap())),
+            };
 
-        let mut F_second = DummyBlock::new(Some(E_second.block_hash().unwrap()), 6);
-        let F_second = Arc::new(F_second);
+            if parent.height() + 1 != current.height() {
+                return Err((parent.height(), parent_hash));
+            }
 
-        let mut D_tertiary = DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4);
-        let D_tertiary = Arc::new(D_tertiary);
+            current = parent;
+        }
 
-        hard_chain.append_block(E_second.clone()).unwrap();
-        hard_chain.append_block(F_second.clone()).unwrap();
+        Ok(())
+    }
 
-        assert_eq!(hard_chain.height(), 0);
+    /// Returns the mean interval between the last `last_n + 1`
+    /// canonical blocks, genesis included.
+    ///
+    /// Returns `None` if there are fewer than 2 canonical blocks.
+    pub fn average_block_time(&self, last_n: usize) -> Option<Duration> {
+        let newest = self.canonical_tip.timestamp();
+        let mut current = self.canonical_tip.clone();
+        let mut intervals = 0;
+
+        // Walk the canonical chain backwards
+        while intervals < last_n && current.height() > 0 {
+            current = if current.height() == 1 {
+                self.genesis.clone()
+            } else {
+                self.query(&current.parent_hash().unwrap())?
+            };
 
-        // We should have a disconnected chain of `E''` and `F''`
-        // with the tip of `E''` pointing to `F''`.
-        assert_eq!(
-            *hard_chain
-                .disconnected_tips_mapping
-                .get(&F_second.block_hash().unwrap())
-                .unwrap(),
-            E_second.block_hash().unwrap()
-        );
-        let heads_mapping = hard_chain
-            .disconnected_heads_mapping
-            .get(&E_second.block_hash().unwrap())
-            .unwrap();
-        let (largest_height, largest_tip) = hard_chain
-            .disconnected_heads_heights
-            .get(&E_second.block_hash().unwrap())
-            .unwrap();
-        assert!(heads_mapping.contains(&F_second.block_hash().unwrap()));
-        assert_eq!(*largest_height, F_second.height());
-        assert_eq!(largest_tip, &F_second.block_hash().unwrap());
+            intervals += 1;
+        }
 
-        hard_chain.append_block(A.clone()).unwrap();
-        hard_chain.append_block(B.clone()).unwrap();
+        if intervals == 0 {
+            return None;
+        }
 
-        assert_eq!(hard_chain.height(), 2);
-        assert_eq!(hard_chain.canonical_tip(), B);
+        let total = newest
+            .signed_duration_since(current.timestamp())
+            .to_std()
+            .ok()?;
 
-        hard_chain.append_block(F.clone()).unwrap();
-        hard_chain.append_block(G.clone()).unwrap();
+        Some(total / intervals as u32)
+    }
 
-        assert_eq!(hard_chain.height(), 2);
-        assert_eq!(hard_chain.canonical_tip(), B);
+    /// Returns all canonical blocks appended after the block
+    /// with the given hash, in ascending order of height.
+    ///
+    /// Returns `Err(ChainErr::NoSuchBlock)` if there is no block with
+    /// the given hash in the canonical chain.
+    pub fn blocks_since(&self, since_hash: &Hash) -> Result<Vec<Arc<B>>, ChainErr> {
+        let since_height = self
+            .block_height(since_hash)
+            .ok_or_else(|| ChainErr::NoSuchBlock(since_hash.clone()))?;
+
+        (since_height + 1..=self.height)
+            .map(|height| self.query_by_height(height))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| ChainErr::NoSuchBlock(since_hash.clone()))
+    }
 
-        // We should have a disconnected chain of `F` and `G`
-        // with the tip of `G` pointing to `F`.
-        assert_eq!(
-            *hard_chain
-                .disconnected_tips_mapping
-                .get(&G.block_hash().unwrap())
-                .unwrap(),
-            F.block_hash().unwrap()
-        );
-        let heads_mapping = hard_chain
-            .disconnected_heads_mapping
-            .get(&F.block_hash().unwrap())
-            .unwrap();
-        let (largest_height, largest_tip) = hard_chain
-            .disconnected_heads_heights
-            .get(&F.block_hash().unwrap())
-            .unwrap();
-        assert!(heads_mapping.contains(&G.block_hash().unwrap()));
-        assert_eq!(*largest_height, G.height());
-        assert_eq!(largest_tip, &G.block_hash().unwrap());
-        assert_eq!(hard_chain.height(), 2);
-        assert_eq!(hard_chain.canonical_tip(), B);
+    /// Returns the canonical block at the given height along
+    /// with all the orphans that are competing with it.
+    pub fn at_height(&self, height: u64) -> (Option<Arc<B>>, Vec<Arc<B>>) {
+        let canonical = self.query_by_height(height);
+        let orphans = match self.heights_mapping.get(&height) {
+            Some(entries) => entries
+                .keys()
+                .filter_map(|hash| self.orphan_pool.get(hash))
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
 
-        // We now append `B'` and the canonical tip should still be `B`
-        hard_chain.append_block(B_prime.clone()).unwrap();
+        (canonical, orphans)
+    }
 
-        assert_eq!(hard_chain.height(), 2);
-        assert_eq!(hard_chain.canonical_tip(), B);
+    /// Returns the longest chain of orphans that is rooted at
+    /// the orphan with the given hash, ordered oldest-first.
+    ///
+    /// Returns an empty vector if the given hash is not an orphan.
+    pub fn get_orphan_ancestry(&self, head_hash: &Hash) -> Vec<Arc<B>> {
+        let head = match self.orphan_pool.get(head_hash) {
+            Some(head) => head.clone(),
+            None => return Vec::new(),
+        };
 
-        hard_chain.append_block(C_prime.clone()).unwrap();
+        let mut result = vec![head.clone()];
+        let mut current = head;
+
+        // Follow the child with the largest inverse height
+        while let Some(next) = self
+            .block_children(&current.block_hash().unwrap())
+            .into_iter()
+            .max_by_key(|o| self.inverse_height(o))
+        {
+            result.push(next.clone());
+            current = next;
+        }
 
-        assert_eq!(hard_chain.height(), 3);
-        assert_eq!(hard_chain.canonical_tip(), C_prime);
+        result
+    }
 
-        hard_chain.append_block(C.clone()).unwrap();
-        assert_eq!(hard_chain.height(), 3);
-        assert_eq!(hard_chain.canonical_tip(), C_prime);
+    /// Returns the inclusive range of heights that are likely
+    /// missing between the canonical tip and the lowest
+    /// disconnected orphan chain.
+    ///
+    /// If there are no disconnected chains, the range only
+    /// contains the height following the canonical tip.
+    pub fn estimate_sync_window(&self) -> (u64, u64) {
+        let start = self.height + 1;
+        let lowest_head = self
+            .disconnected_heads_mapping
+            .keys()
+            .filter_map(|head_hash| self.orphan_pool.get(head_hash))
+            .map(|head| head.height())
+            .filter(|height| *height > start)
+            .min();
+
+        match lowest_head {
+            Some(height) => (start, height - 1),
+            None => (start, start),
+        }
+    }
 
-        hard_chain.append_block(D.clone()).unwrap();
+    /// Returns a proof that the orphan with the given hash
+    /// descends from a block in the canonical chain.
+    ///
+    /// Returns `None` if there is no such orphan or if it
+    /// belongs to a disconnected chain.
+    pub fn merkle_path_to_canonical(&self, orphan_hash: &Hash) -> Option<MerkleProof> {
+        let mut current = self.orphan_pool.get(orphan_hash)?.clone();
+        let mut block_hashes = vec![orphan_hash.clone()];
+        let mut sibling_hashes = Vec::new();
+        let mut headers = Vec::new();
+        let mut running_hash = orphan_hash.clone();
+
+        loop {
+            let parent_hash = current.parent_hash().unwrap();
+
+            running_hash = merkle_node(&running_hash, &parent_hash);
+            block_hashes.push(parent_hash.clone());
+            sibling_hashes.push(running_hash.clone());
+            headers.push(current.to_bytes());
+
+            if self.db.get(&parent_hash).is_some()
+                || parent_hash == self.genesis.block_hash().unwrap()
+            {
+                break;
+            }
 
-        assert_eq!(hard_chain.height(), 4);
-        assert_eq!(hard_chain.canonical_tip(), D);
+            current = self.orphan_pool.get(&parent_hash)?.clone();
+        }
 
-        // After appending `E` the chain should connect the old tip
-        // which is `D` to our previous disconnected chain of `F` -> `G`.
-        hard_chain.append_block(E.clone()).unwrap();
+        Some(MerkleProof {
+            block_hashes,
+            sibling_hashes,
+            headers,
+        })
+    }
 
-        assert_eq!(hard_chain.height(), 7);
-        assert_eq!(hard_chain.canonical_tip(), G);
+    /// Returns `Some(true)` if the parent of the orphan with the given
+    /// hash is in the canonical chain and `Some(false)` if the parent
+    /// is itself an orphan or unknown.
+    ///
+    /// Returns `None` if there is no orphan with the given hash.
+    pub fn orphan_references_canonical_parent(&self, orphan_hash: &Hash) -> Option<bool> {
+        let orphan = self.orphan_pool.get(orphan_hash)?;
+        let parent_hash = orphan.parent_hash().unwrap();
+
+        Some(
+            parent_hash == self.genesis.block_hash().unwrap()
+                || self.db.get(&parent_hash).is_some(),
+        )
     }
 
-    #[test]
-    fn stages_append_test2() {
-        let db = test_helpers::init_tempdb();
-        let mut hard_chain = Chain::<DummyBlock>::new(db);
+    /// Returns `true` if genesis can be reached by following the
+    /// parent links of the block with the given hash through the
+    /// ledger and the orphan pool.
+    ///
+    /// This walks the whole ancestry of the block so it should
+    /// only be used for diagnostics.
+    pub fn reachable_from_genesis(&self, hash: &Hash) -> bool {
+        let genesis_hash = self.genesis.block_hash().unwrap();
+        let mut current_hash = hash.clone();
+
+        loop {
+            if current_hash == genesis_hash {
+                return true;
+            }
 
-        let mut A = DummyBlock::new(Some(Hash::NULL), 1);
-        let A = Arc::new(A);
+            let current = match self.orphan_pool.get(&current_hash) {
+                Some(orphan) => orphan.clone(),
+                None => match self.query(&current_hash) {
+                    Some(block) => block,
+                    None => return false,
+                },
+            };
 
-        let mut B = DummyBlock::new(Some(A.block_hash().unwrap()), 2);
-        let B = Arc::new(B);
+            current_hash = current.parent_hash().unwrap();
+        }
+    }
 
-        let mut C = DummyBlock::new(Some(B.block_hash().unwrap()), 3);
-        let C = Arc::new(C);
+    /// Returns the `n` highest valid chain tips along
+    /// with their heights, sorted by decreasing height.
+    pub fn top_n_valid_tips_by_height(&self, n: usize) -> Vec<(Hash, u64)> {
+        let mut tips: Vec<(Hash, u64)> = self
+            .valid_tips
+            .iter()
+            .filter_map(|tip_hash| {
+                let height = self.orphan_pool.get(tip_hash)?.height();
 
-        let mut D = DummyBlock::new(Some(C.block_hash().unwrap()), 4);
-        let D = Arc::new(D);
+                match self.heights_mapping.get(&height) {
+                    Some(entries) if entries.contains_key(tip_hash) => {
+                        Some((tip_hash.clone(), height))
+                    }
+                    _ => None,
+                }
+            })
+            .collect();
 
-        let mut E = DummyBlock::new(Some(D.block_hash().unwrap()), 5);
-        let E = Arc::new(E);
+        tips.sort_by(|(a_hash, a_height), (b_hash, b_height)| {
+            b_height.cmp(a_height).then_with(|| a_hash.cmp(b_hash))
+        });
+        tips.truncate(n);
+        tips
+    }
 
-        let mut F = DummyBlock::new(Some(E.block_hash().unwrap()), 6);
-        let F = Arc::new(F);
+    /// Returns metrics for each disconnected chain segment.
+    pub fn orphan_stats_per_segment(&self) -> Vec<SegmentStats> {
+        self.disconnected_heads_mapping
+            .iter()
+            .map(|(head_hash, tips)| {
+                let mut depth = 0;
+
+                for tip_hash in tips.iter() {
+                    let mut current = tip_hash.clone();
+                    let mut tip_depth = 1;
+
+                    // Walk back to the head of the segment
+                    while current != *head_hash {
+                        match self.orphan_pool.get(&current) {
+                            Some(orphan) => current = orphan.parent_hash().unwrap(),
+                            None => break,
+                        }
 
-        let mut G = DummyBlock::new(Some(F.block_hash().unwrap()), 7);
-        let G = Arc::new(G);
+                        tip_depth += 1;
+                    }
 
-        let mut B_prime = DummyBlock::new(Some(A.block_hash().unwrap()), 2);
-        let B_prime = Arc::new(B_prime);
+                    if tip_depth > depth {
+                        depth = tip_depth;
+                    }
+                }
 
-        let mut C_prime = DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3);
-        let C_prime = Arc::new(C_prime);
+                let max_height = match self.disconnected_heads_heights.get(head_hash) {
+                    Some((height, _)) => *height,
+                    None => 0,
+                };
 
-        let mut D_prime = DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4);
-        let D_prime = Arc::new(D_prime);
+                SegmentStats {
+                    head_hash: head_hash.clone(),
+                    tip_count: tips.len(),
+                    depth,
+                    max_height,
+                }
+            })
+            .collect()
+    }
 
-        let mut E_prime = DummyBlock::new(Some(D_prime.block_hash().unwrap()), 5);
-        let E_prime = Arc::new(E_prime);
+    /// Returns the orphans whose parent is the block with the given hash.
+    pub fn block_children(&self, hash: &Hash) -> Vec<Arc<B>> {
+        match self.children_index.get(hash) {
+            Some(children) => children
+                .iter()
+                .filter_map(|child| self.orphan_pool.get(child))
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        }
+    }
 
-        let mut C_second = DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3);
-        let C_second = Arc::new(C_second);
+    /// Returns the blocks of each disconnected orphan chain sorted
+    /// from the lowest height to the highest. The chains are sorted
+    /// by the height of their head blocks.
+    pub fn enumerate_orphan_chains(&self) -> Vec<Vec<Arc<B>>> {
+        let mut chains: Vec<Vec<Arc<B>>> = self
+            .disconnected_heads_mapping
+            .keys()
+            .filter_map(|head_hash| self.orphan_pool.get(head_hash))
+            .map(|head| {
+                let mut chain = Vec::new();
+                let mut to_visit = vec![head.clone()];
+
+                while let Some(block) = to_visit.pop() {
+                    to_visit.extend(self.block_children(&block.block_hash().unwrap()));
+                    chain.push(block);
+                }
 
-        let mut D_second = DummyBlock::new(Some(C_second.block_hash().unwrap()), 4);
-        let D_second = Arc::new(D_second);
+                chain.sort_by_key(|block| block.height());
+                chain
+            })
+            .collect();
 
-        let mut E_second = DummyBlock::new(Some(D_second.block_hash().unwrap()), 5);
-        let E_second = Arc::new(E_second);
+        chains.sort_by_key(|chain| chain[0].height());
+        chains
+    }
 
-        let mut F_second = DummyBlock::new(Some(E_second.block_hash().unwrap()), 6);
-        let F_second = Arc::new(F_second);
+    /// Checks that every entry of the heights mapping holds orphans
+    /// of the entry's height and that every orphan in the pool has
+    /// exactly one entry. Returns the found violations.
+    pub fn verify_heights_mapping_consistency(&self) -> Vec<String> {
+        let mut violations = Vec::new();
+        let mut entries: HashMap<&Hash, usize> = HashMap::new();
+
+        for (height, orphans) in self.heights_mapping.iter() {
+            for orphan_hash in orphans.keys() {
+                *entries.entry(orphan_hash).or_insert(0) += 1;
+
+                match self.orphan_pool.get(orphan_hash) {
+                    Some(orphan) if orphan.height() != *height => {
+                        violations.push(format!(
+                            "Orphan {:?} with height {} is mapped to height {}",
+                            orphan_hash,
+                            orphan.height(),
+                            height
+                        ));
+                    }
+                    None => {
+                        violations.push(format!(
+                            "Orphan {:?} mapped to height {} is not in the orphan pool",
+                            orphan_hash, height
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+        }
 
-        let mut D_tertiary = DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4);
-        let D_tertiary = Arc::new(D_tertiary);
+        for orphan_hash in self.orphan_pool.keys() {
+            let count = entries.get(orphan_hash).cloned().unwrap_or(0);
 
-        hard_chain.append_block(A.clone()).unwrap();
+            if count != 1 {
+                violations.push(format!(
+                    "Orphan {:?} has {} entries in the heights mapping",
+                    orphan_hash, count
+                ));
+            }
+        }
 
-        assert_eq!(hard_chain.height(), 1);
+        violations
+    }
 
-        hard_chain.append_block(E_second.clone()).unwrap();
-        assert_eq!(
-            *hard_chain
-                .validations_mapping
-                .get(&E_second.block_hash().unwrap())
-                .unwrap(),
-            OrphanType::DisconnectedTip
-        );
+    /// Returns the inverse height of the given orphan.
+    fn inverse_height(&self, orphan: &Arc<B>) -> u64 {
+        self.heights_mapping
+            .get(&orphan.height())
+            .and_then(|entries| entries.get(&orphan.block_hash().unwrap()))
+            .cloned()
+            .unwrap_or(0)
+    }
+}
 
-        hard_chain.append_block(D_second.clone()).unwrap();
+/// Verifies that the given proof links the orphan
+/// with the given hash to the given canonical block.
+///
+/// Every header must hash to its entry in `block_hashes`
+/// and reference the next entry as its parent, so a proof
+/// cannot skip or replace any block between the two.
+pub fn verify_merkle_proof<B: Block>(
+    proof: &MerkleProof,
+    orphan_hash: &Hash,
+    canonical_hash: &Hash,
+) -> bool {
+    if proof.block_hashes.len() < 2
+        || proof.sibling_hashes.len() != proof.block_hashes.len() - 1
+        || proof.headers.len() != proof.block_hashes.len() - 1
+    {
+        return false;
+    }
 
-        assert_eq!(
-            *hard_chain
-                .validations_mapping
-                .get(&E_second.block_hash().unwrap())
-                .unwrap(),
-            OrphanType::DisconnectedTip
-        );
-        assert_eq!(
-            *hard_chain
-                .validations_mapping
-                .get(&D_second.block_hash().unwrap())
-                .unwrap(),
-            OrphanType::BelongsToDisconnected
-        );
+    if proof.block_hashes.first() != Some(orphan_hash)
+        || proof.block_hashes.last() != Some(canonical_hash)
+    {
+        return false;
+    }
 
-        hard_chain.append_block(F_second.clone()).unwrap();
+    let mut running_hash = orphan_hash.clone();
 
-        assert_eq!(
-            *hard_chain
-                .validations_mapping
-                .get(&E_second.block_hash().unwrap())
-                .unwrap(),
-            OrphanType::BelongsToDisconnected
-        );
-        assert_eq!(
-            *hard_chain
-                .validations_mapping
-                .get(&D_second.block_hash().unwrap())
-                .unwrap(),
-            OrphanType::BelongsToDisconnected
-        );
-        assert_eq!(
-            *hard_chain
-                .validations_mapping
-                .get(&F_second.block_hash().unwrap())
-                .unwrap(),
-            OrphanType::DisconnectedTip
-        );
+    for (block_hash, sibling_hash) in proof.block_hashes[1..]
+        .iter()
+        .zip(proof.sibling_hashes.iter())
+    {
+        running_hash = merkle_node(&running_hash, block_hash);
 
-        // We should have a disconnected chain of `E''` and `F''`
-        // with the tip of `D''` pointing to `F''`.
-        assert_eq!(
-            *hard_chain
-                .disconnected_tips_mapping
-                .get(&F_second.block_hash().unwrap())
-                .unwrap(),
-            D_second.block_hash().unwrap()
-        );
-        let heads_mapping = hard_chain
-            .disconnected_heads_mapping
-            .get(&D_second.block_hash().unwrap())
-            .unwrap();
-        let (largest_height, largest_tip) = hard_chain
-            .disconnected_heads_heights
-            .get(&D_second.block_hash().unwrap())
-            .unwrap();
-        assert!(heads_mapping.contains(&F_second.block_hash().unwrap()));
-        assert_eq!(*largest_height, F_second.height());
-        assert_eq!(largest_tip, &F_second.block_hash().unwrap());
+        if running_hash != *sibling_hash {
+            return false;
+        }
+    }
 
-        assert_eq!(hard_chain.height(), 1);
-        assert_eq!(hard_chain.canonical_tip(), A);
+    for (i, header) in proof.headers.iter().enumerate() {
+        let block = match B::from_bytes(header) {
+            Ok(block) => block,
+            Err(_) => return false,
+        };
 
-        hard_chain.append_block(C.clone()).unwrap();
-        hard_chain.append_block(D.clone()).unwrap();
-        hard_chain.append_block(F.clone()).unwrap();
-        hard_chain.append_block(E.clone()).unwrap();
-        hard_chain.append_block(G.clone()).unwrap();
+        if !block.verify_hash()
+            || block.block_hash().as_ref() != Some(&proof.block_hashes[i])
+            || block.parent_hash().as_ref() != Some(&proof.block_hashes[i + 1])
+        {
+            return false;
+        }
+    }
 
-        assert_eq!(
-            *hard_chain
-                .validations_mapping
-                .get(&C.block_hash().unwrap())
-                .unwrap(),
-            OrphanType::BelongsToDisconnected
-        );
-        assert_eq!(
-            *hard_chain
-                .validations_mapping
-                .get(&D.block_hash().unwrap())
-                .unwrap(),
-            OrphanType::BelongsToDisconnected
-        );
-        assert_eq!(
-            *hard_chain
-                .validations_mapping
-                .get(&E.block_hash().unwrap())
-                .unwrap(),
-            OrphanType::BelongsToDisconnected
-        );
-        assert_eq!(
-            *hard_chain
-                .validations_mapping
-                .get(&F.block_hash().unwrap())
-                .unwrap(),
-            OrphanType::BelongsToDisconnected
-        );
-        assert_eq!(
-            *hard_chain
-                .validations_mapping
-                .get(&G.block_hash().unwrap())
-                .unwrap(),
-            OrphanType::DisconnectedTip
-        );
+    true
+}
 
-        assert_eq!(hard_chain.height(), 1);
-        assert_eq!(hard_chain.canonical_tip(), A);
+/// Returns the tree hash of the given pair of hashes.
+fn merkle_node(left: &Hash, right: &Hash) -> Hash {
+    let mut buf = Vec::with_capacity(64);
+    buf.extend_from_slice(&left.0);
+    buf.extend_from_slice(&right.0);
 
-        // We now append `B'` and the canonical tip should be `B'`
-        hard_chain.append_block(B_prime.clone()).unwrap();
+    crypto::hash_slice(&buf)
+}
 
-        assert_eq!(hard_chain.height(), 2);
-        assert_eq!(hard_chain.canonical_tip(), B_prime);
+/// Returns the key of the extra data of the block with the given hash.
+fn extra_data_key(hash: &Hash) -> Hash {
+    let key = format!("{}.extra", hex::encode(hash.to_vec()));
+    crypto::hash_slice(key.as_bytes())
+}
 
-        hard_chain.append_block(C_second.clone()).unwrap();
+/// Returns the key of the height of the block with the given hash.
+fn block_height_key(hash: &Hash) -> Hash {
+    let key = format!("{}.height", hex::encode(hash.to_vec()));
+    crypto::hash_slice(key.as_bytes())
+}
 
-        // The chain should now be pointing to `F''` as being the canonical tip
-        assert_eq!(hard_chain.height(), 6);
-        assert_eq!(hard_chain.canonical_tip(), F_second);
+/// Returns the key of the hash of the canonical block with the given height.
+fn canonical_hash_key(height: u64) -> Hash {
+    let key = format!("{}.canonical_hash", height);
+    crypto::hash_slice(key.as_bytes())
+}
 
-        // We now append `B` and the chain should switch to `G` as the canonical tip
-        hard_chain.append_block(B.clone()).unwrap();
+/// Returns the number of bytes allocated for the entries of the given map.
+fn map_bytes<K, V>(map: &HashMap<K, V>) -> usize {
+    map.capacity() * mem::size_of::<(K, V)>()
+}
 
-        assert_eq!(hard_chain.height(), 7);
-        assert_eq!(hard_chain.canonical_tip(), G);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::easy_chain::block::EasyBlock;
+    use crate::hard_chain::block::HardBlock;
+    use chrono::prelude::*;
+    use futures::Future;
+    use quickcheck::*;
+    use rand::*;
+    use std::thread;
+
+    macro_rules! count {
+        () => (0);
+        ($fst:expr) => (1);
+        ($fst:expr, $snd:expr) => (2);
+        ($fst:expr, $snd:expr $(, $v:expr)*) => (1 + count!($snd $(, $v)*));
     }
 
-    #[test]
-    /// Assertions in stages on random order
-    /// of appended blocks.
-    fn stages_append_test3() {
-        let db = test_helpers::init_tempdb();
-        let mut hard_chain = Chain::<DummyBlock>::new(db);
+    macro_rules! set {
+        ($fst:expr $(, $v:expr)*) => ({
+            let mut set = HashSet::with_capacity(count!($fst $(, $v)*));
 
-        let mut A = DummyBlock::new(Some(Hash::NULL), 1);
-        let A = Arc::new(A);
+            set.insert($fst);
+            $(set.insert($v);)*
 
-        let mut B = DummyBlock::new(Some(A.block_hash().unwrap()), 2);
-        let B = Arc::new(B);
+            set
+        });
+    }
 
-        let mut C = DummyBlock::new(Some(B.block_hash().unwrap()), 3);
-        let C = Arc::new(C);
+    use std::hash::Hasher;
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
-        let mut D = DummyBlock::new(Some(C.block_hash().unwrap()), 4);
-        let D = Arc::new(D);
+    /// Nonce used for creating unique `DummyBlock` hashes
+    static NONCE: AtomicUsize = AtomicUsize::new(0);
 
-        let mut E = DummyBlock::new(Some(D.block_hash().unwrap()), 5);
-        let E = Arc::new(E);
+    #[derive(Clone, Debug)]
+    /// Dummy block used for testing
+    struct DummyBlock {
+        hash: Hash,
+        parent_hash: Hash,
+        height: u64,
+        timestamp: DateTime<Utc>,
+        difficulty: u64,
+        padding: usize,
+        parent_chain_id: Option<u64>,
+    }
 
-        let mut F = DummyBlock::new(Some(E.block_hash().unwrap()), 6);
-        let F = Arc::new(F);
+    impl DummyBlock {
+        pub fn new(parent_hash: Option<Hash>, height: u64) -> DummyBlock {
+            DummyBlock::with_timestamp(parent_hash, height, Utc.timestamp(height as i64, 0))
+        }
 
-        let mut G = DummyBlock::new(Some(F.block_hash().unwrap()), 7);
-        let G = Arc::new(G);
+        pub fn with_timestamp(
+            parent_hash: Option<Hash>,
+            height: u64,
+            timestamp: DateTime<Utc>,
+        ) -> DummyBlock {
+            let hash =
+                crypto::hash_slice(&format!("block-{}", NONCE.load(Ordering::Relaxed)).as_bytes());
+            NONCE.fetch_add(1, Ordering::Relaxed);
+            let parent_hash = parent_hash.unwrap();
 
-        let mut B_prime = DummyBlock::new(Some(A.block_hash().unwrap()), 2);
-        let B_prime = Arc::new(B_prime);
+            DummyBlock {
+                hash,
+                parent_hash,
+                height,
+                timestamp,
+                difficulty: 1,
+                padding: 0,
+                parent_chain_id: None,
+            }
+        }
+    }
 
-        let mut C_prime = DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3);
-        let C_prime = Arc::new(C_prime);
+    impl PartialEq for DummyBlock {
+        fn eq(&self, other: &DummyBlock) -> bool {
+            self.block_hash().unwrap() == other.block_hash().unwrap()
+        }
+    }
 
-        let mut D_prime = DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4);
-        let D_prime = Arc::new(D_prime);
+    impl Eq for DummyBlock {}
 
-        let mut E_prime = DummyBlock::new(Some(D_prime.block_hash().unwrap()), 5);
-        let E_prime = Arc::new(E_prime);
+    impl HashTrait for DummyBlock {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.block_hash().unwrap().hash(state);
+        }
+    }
 
-        let mut C_second = DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3);
-        let C_second = Arc::new(C_second);
+    impl Block for DummyBlock {
+        fn genesis() -> Arc<Self> {
+            let genesis = DummyBlock {
+                hash: Hash::NULL,
+                parent_hash: Hash::NULL,
+                height: 0,
+                timestamp: Utc.timestamp(0, 0),
+                difficulty: 1,
+                padding: 0,
+                parent_chain_id: None,
+            };
 
-        let mut D_second = DummyBlock::new(Some(C_second.block_hash().unwrap()), 4);
-        let D_second = Arc::new(D_second);
+            Arc::new(genesis)
+        }
 
-        let mut E_second = DummyBlock::new(Some(D_second.block_hash().unwrap()), 5);
-        let E_second = Arc::new(E_second);
+        fn parent_hash(&self) -> Option<Hash> {
+            Some(self.parent_hash.clone())
+        }
+
+        // Hashes are random nonces so there
+        // is nothing to recompute them from.
+        fn verify_hash(&self) -> bool {
+            true
+        }
+
+        fn block_hash(&self) -> Option<Hash> {
+            Some(self.hash.clone())
+        }
+
+        fn merkle_root(&self) -> Option<Hash> {
+            unimplemented!();
+        }
+
+        fn timestamp(&self) -> DateTime<Utc> {
+            self.timestamp.clone()
+        }
+
+        fn height(&self) -> u64 {
+            self.height
+        }
+
+        fn total_difficulty(&self) -> u64 {
+            self.difficulty
+        }
+
+        fn parent_chain_id(&self) -> Option<u64> {
+            self.parent_chain_id
+        }
+
+        fn state_transition(&self) -> Option<Vec<u8>> {
+            Some(self.hash.0.to_vec())
+        }
+
+        fn after_write() -> Option<Box<FnMut(Arc<Self>)>> {
+            None
+        }
+
+        fn to_bytes(&self) -> Vec<u8> {
+            let mut buf = Vec::new();
+            let height = encode_be_u64!(self.height);
+            let timestamp = encode_be_u64!(self.timestamp.timestamp() as u64);
+
+            buf.extend_from_slice(&height);
+            buf.extend_from_slice(&self.hash.0.to_vec());
+            buf.extend_from_slice(&self.parent_hash.0.to_vec());
+            buf.extend_from_slice(&timestamp);
+            buf.extend_from_slice(&encode_be_u64!(self.difficulty));
+            buf.extend_from_slice(&vec![0; self.padding]);
+
+            buf
+        }
+
+        fn from_bytes(bytes: &[u8]) -> Result<Arc<Self>, &'static str> {
+            let mut buf = bytes.to_vec();
+            let height_bytes: Vec<u8> = buf.drain(..8).collect();
+            let height = decode_be_u64!(&height_bytes).unwrap();
+            let hash_bytes: Vec<u8> = buf.drain(..32).collect();
+            let parent_hash_bytes: Vec<u8> = buf.drain(..32).collect();
+            let timestamp_bytes: Vec<u8> = buf.drain(..8).collect();
+            let timestamp = decode_be_u64!(&timestamp_bytes).unwrap();
+            let difficulty_bytes: Vec<u8> = buf.drain(..8).collect();
+            let difficulty = decode_be_u64!(&difficulty_bytes).unwrap();
+            let padding = buf.len();
+            let timestamp = Utc.timestamp(timestamp as i64, 0);
+            let mut hash = [0; 32];
+            let mut parent_hash = [0; 32];
+
+            hash.copy_from_slice(&hash_bytes);
+            parent_hash.copy_from_slice(&parent_hash_bytes);
+
+            let hash = Hash(hash);
+            let parent_hash = Hash(parent_hash);
+
+            Ok(Arc::new(DummyBlock {
+                height,
+                hash,
+                parent_hash,
+                timestamp,
+                difficulty,
+                padding,
+                parent_chain_id: None,
+            }))
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    /// Dummy block carrying extra data used for testing
+    struct ExtraDataBlock {
+        block: DummyBlock,
+        extra_data: Vec<u8>,
+    }
+
+    impl Block for ExtraDataBlock {
+        fn genesis() -> Arc<Self> {
+            let genesis = ExtraDataBlock {
+                block: (*DummyBlock::genesis()).clone(),
+                extra_data: Vec::new(),
+            };
+
+            Arc::new(genesis)
+        }
+
+        fn parent_hash(&self) -> Option<Hash> {
+            self.block.parent_hash()
+        }
+
+        fn verify_hash(&self) -> bool {
+            self.block.verify_hash()
+        }
+
+        fn block_hash(&self) -> Option<Hash> {
+            self.block.block_hash()
+        }
+
+        fn merkle_root(&self) -> Option<Hash> {
+            unimplemented!();
+        }
+
+        fn timestamp(&self) -> DateTime<Utc> {
+            self.block.timestamp()
+        }
+
+        fn height(&self) -> u64 {
+            self.block.height()
+        }
+
+        fn extra_data(&self) -> Option<&[u8]> {
+            if self.extra_data.is_empty() {
+                None
+            } else {
+                Some(&self.extra_data)
+            }
+        }
+
+        fn after_write() -> Option<Box<FnMut(Arc<Self>)>> {
+            None
+        }
+
+        fn to_bytes(&self) -> Vec<u8> {
+            let mut buf = self.block.to_bytes();
+            buf.extend_from_slice(&self.extra_data);
+            buf
+        }
+
+        fn from_bytes(bytes: &[u8]) -> Result<Arc<Self>, &'static str> {
+            // A serialized unpadded `DummyBlock` is 88 bytes long
+            let block = DummyBlock::from_bytes(&bytes[..88])?;
+
+            Ok(Arc::new(ExtraDataBlock {
+                block: (*block).clone(),
+                extra_data: bytes[88..].to_vec(),
+            }))
+        }
+    }
+
+    #[test]
+    fn stages_append_test1() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let mut A = DummyBlock::new(Some(Hash::NULL), 1);
+        let A = Arc::new(A);
+
+        let mut B = DummyBlock::new(Some(A.block_hash().unwrap()), 2);
+        let B = Arc::new(B);
+
+        let mut C = DummyBlock::new(Some(B.block_hash().unwrap()), 3);
+        let C = Arc::new(C);
+
+        let mut D = DummyBlock::new(Some(C.block_hash().unwrap()), 4);
+        let D = Arc::new(D);
+
+        let mut E = DummyBlock::new(Some(D.block_hash().unwrap()), 5);
+        let E = Arc::new(E);
+
+        let mut F = DummyBlock::new(Some(E.block_hash().unwrap()), 6);
+        let F = Arc::new(F);
+
+        let mut G = DummyBlock::new(Some(F.block_hash().unwrap()), 7);
+        let G = Arc::new(G);
+
+        let mut B_prime = DummyBlock::new(Some(A.block_hash().unwrap()), 2);
+        let B_prime = Arc::new(B_prime);
+
+        let mut C_prime = DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3);
+        let C_prime = Arc::new(C_prime);
+
+        let mut D_prime = DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4);
+        let D_prime = Arc::new(D_prime);
+
+        let mut E_prime = DummyBlock::new(Some(D_prime.block_hash().unwrap()), 5);
+        let E_prime = Arc::new(E_prime);
+
+        let mut C_second = DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3);
+        let C_second = Arc::new(C_second);
+
+        let mut D_second = DummyBlock::new(Some(C_second.block_hash().unwrap()), 4);
+        let D_second = Arc::new(D_second);
+
+        let mut E_second = DummyBlock::new(Some(D_second.block_hash().unwrap()), 5);
+        let E_second = Arc::new(E_second);
 
         let mut F_second = DummyBlock::new(Some(E_second.block_hash().unwrap()), 6);
         let F_second = Arc::new(F_second);
@@ -1780,163 +3865,187 @@ mod tests {
         let mut D_tertiary = DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4);
         let D_tertiary = Arc::new(D_tertiary);
 
-        hard_chain.append_block(C_second.clone()).unwrap();
-        let C_second_ih = hard_chain
-            .heights_mapping
-            .get(&C_second.height())
-            .unwrap()
-            .get(&C_second.block_hash().unwrap())
-            .unwrap();
+        hard_chain.append_block(E_second.clone()).unwrap();
+        hard_chain.append_block(F_second.clone()).unwrap();
 
-        // Check validations mapping
+        assert_eq!(hard_chain.height(), 0);
+
+        // We should have a disconnected chain of `E''` and `F''`
+        // with the tip of `E''` pointing to `F''`.
         assert_eq!(
             *hard_chain
-                .validations_mapping
-                .get(&C_second.block_hash().unwrap())
+                .disconnected_tips_mapping
+                .get(&F_second.block_hash().unwrap())
                 .unwrap(),
-            OrphanType::DisconnectedTip
+            E_second.block_hash().unwrap()
         );
+        let heads_mapping = hard_chain
+            .disconnected_heads_mapping
+            .get(&E_second.block_hash().unwrap())
+            .unwrap();
+        let (largest_height, largest_tip) = hard_chain
+            .disconnected_heads_heights
+            .get(&E_second.block_hash().unwrap())
+            .unwrap();
+        assert!(heads_mapping.contains(&F_second.block_hash().unwrap()));
+        assert_eq!(*largest_height, F_second.height());
+        assert_eq!(largest_tip, &F_second.block_hash().unwrap());
 
-        // Check inverse height
-        assert_eq!(*C_second_ih, 0);
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(B.clone()).unwrap();
 
-        // Check max orphan height
-        assert_eq!(hard_chain.max_orphan_height, Some(3));
+        assert_eq!(hard_chain.height(), 2);
+        assert_eq!(hard_chain.canonical_tip(), B);
 
-        hard_chain.append_block(D_prime.clone()).unwrap();
-        let C_second_ih = hard_chain
-            .heights_mapping
-            .get(&C_second.height())
-            .unwrap()
-            .get(&C_second.block_hash().unwrap())
-            .unwrap();
-        let D_prime_ih = hard_chain
-            .heights_mapping
-            .get(&D_prime.height())
-            .unwrap()
-            .get(&D_prime.block_hash().unwrap())
-            .unwrap();
-        assert_eq!(
-            *hard_chain
-                .validations_mapping
-                .get(&C_second.block_hash().unwrap())
-                .unwrap(),
-            OrphanType::DisconnectedTip
-        );
+        hard_chain.append_block(F.clone()).unwrap();
+        hard_chain.append_block(G.clone()).unwrap();
+
+        assert_eq!(hard_chain.height(), 2);
+        assert_eq!(hard_chain.canonical_tip(), B);
+
+        // We should have a disconnected chain of `F` and `G`
+        // with the tip of `G` pointing to `F`.
         assert_eq!(
             *hard_chain
-                .validations_mapping
-                .get(&D_prime.block_hash().unwrap())
+                .disconnected_tips_mapping
+                .get(&G.block_hash().unwrap())
                 .unwrap(),
-            OrphanType::DisconnectedTip
+            F.block_hash().unwrap()
         );
-        assert_eq!(*C_second_ih, 0);
-        assert_eq!(*D_prime_ih, 0);
-        assert_eq!(hard_chain.max_orphan_height, Some(4));
-
-        hard_chain.append_block(F.clone()).unwrap();
-        assert_eq!(hard_chain.max_orphan_height, Some(6));
-        hard_chain.append_block(D_second.clone()).unwrap();
-        let C_second_ih = hard_chain
-            .heights_mapping
-            .get(&C_second.height())
-            .unwrap()
-            .get(&C_second.block_hash().unwrap())
+        let heads_mapping = hard_chain
+            .disconnected_heads_mapping
+            .get(&F.block_hash().unwrap())
             .unwrap();
-        let D_prime_ih = hard_chain
-            .heights_mapping
-            .get(&D_prime.height())
-            .unwrap()
-            .get(&D_prime.block_hash().unwrap())
-            .unwrap();
-        let D_second_ih = hard_chain
-            .heights_mapping
-            .get(&D_second.height())
-            .unwrap()
-            .get(&D_second.block_hash().unwrap())
-            .unwrap();
-        let F_ih = hard_chain
-            .heights_mapping
-            .get(&F.height())
-            .unwrap()
-            .get(&F.block_hash().unwrap())
+        let (largest_height, largest_tip) = hard_chain
+            .disconnected_heads_heights
+            .get(&F.block_hash().unwrap())
             .unwrap();
+        assert!(heads_mapping.contains(&G.block_hash().unwrap()));
+        assert_eq!(*largest_height, G.height());
+        assert_eq!(largest_tip, &G.block_hash().unwrap());
+        assert_eq!(hard_chain.height(), 2);
+        assert_eq!(hard_chain.canonical_tip(), B);
+
+        // We now append `B'` and the canonical tip should still be `B`
+        hard_chain.append_block(B_prime.clone()).unwrap();
+
+        assert_eq!(hard_chain.height(), 2);
+        assert_eq!(hard_chain.canonical_tip(), B);
+
+        hard_chain.append_block(C_prime.clone()).unwrap();
+
+        assert_eq!(hard_chain.height(), 3);
+        assert_eq!(hard_chain.canonical_tip(), C_prime);
+
+        hard_chain.append_block(C.clone()).unwrap();
+        assert_eq!(hard_chain.height(), 3);
+        assert_eq!(hard_chain.canonical_tip(), C_prime);
+
+        hard_chain.append_block(D.clone()).unwrap();
+
+        assert_eq!(hard_chain.height(), 4);
+        assert_eq!(hard_chain.canonical_tip(), D);
+
+        // After appending `E` the chain should connect the old tip
+        // which is `D` to our previous disconnected chain of `F` -> `G`.
+        hard_chain.append_block(E.clone()).unwrap();
+
+        assert_eq!(hard_chain.height(), 7);
+        assert_eq!(hard_chain.canonical_tip(), G);
+        assert_eq!(hard_chain.valid_tips_count(), 1);
+        assert_eq!(hard_chain.disconnected_tips_count(), 1);
+        assert_eq!(hard_chain.disconnected_heads_count(), 1);
+    }
+
+    #[test]
+    fn stages_append_test2() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let mut A = DummyBlock::new(Some(Hash::NULL), 1);
+        let A = Arc::new(A);
+
+        let mut B = DummyBlock::new(Some(A.block_hash().unwrap()), 2);
+        let B = Arc::new(B);
+
+        let mut C = DummyBlock::new(Some(B.block_hash().unwrap()), 3);
+        let C = Arc::new(C);
+
+        let mut D = DummyBlock::new(Some(C.block_hash().unwrap()), 4);
+        let D = Arc::new(D);
+
+        let mut E = DummyBlock::new(Some(D.block_hash().unwrap()), 5);
+        let E = Arc::new(E);
+
+        let mut F = DummyBlock::new(Some(E.block_hash().unwrap()), 6);
+        let F = Arc::new(F);
+
+        let mut G = DummyBlock::new(Some(F.block_hash().unwrap()), 7);
+        let G = Arc::new(G);
+
+        let mut B_prime = DummyBlock::new(Some(A.block_hash().unwrap()), 2);
+        let B_prime = Arc::new(B_prime);
+
+        let mut C_prime = DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3);
+        let C_prime = Arc::new(C_prime);
+
+        let mut D_prime = DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4);
+        let D_prime = Arc::new(D_prime);
+
+        let mut E_prime = DummyBlock::new(Some(D_prime.block_hash().unwrap()), 5);
+        let E_prime = Arc::new(E_prime);
+
+        let mut C_second = DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3);
+        let C_second = Arc::new(C_second);
+
+        let mut D_second = DummyBlock::new(Some(C_second.block_hash().unwrap()), 4);
+        let D_second = Arc::new(D_second);
+
+        let mut E_second = DummyBlock::new(Some(D_second.block_hash().unwrap()), 5);
+        let E_second = Arc::new(E_second);
+
+        let mut F_second = DummyBlock::new(Some(E_second.block_hash().unwrap()), 6);
+        let F_second = Arc::new(F_second);
+
+        let mut D_tertiary = DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4);
+        let D_tertiary = Arc::new(D_tertiary);
+
+        hard_chain.append_block(A.clone()).unwrap();
+
+        assert_eq!(hard_chain.height(), 1);
+
+        hard_chain.append_block(E_second.clone()).unwrap();
         assert_eq!(
             *hard_chain
                 .validations_mapping
-                .get(&F.block_hash().unwrap())
+                .get(&E_second.block_hash().unwrap())
                 .unwrap(),
             OrphanType::DisconnectedTip
         );
+
+        hard_chain.append_block(D_second.clone()).unwrap();
+
         assert_eq!(
             *hard_chain
                 .validations_mapping
-                .get(&C_second.block_hash().unwrap())
-                .unwrap(),
-            OrphanType::BelongsToDisconnected
-        );
-        assert_eq!(
-            *hard_chain
-                .validations_mapping
-                .get(&D_second.block_hash().unwrap())
+                .get(&E_second.block_hash().unwrap())
                 .unwrap(),
             OrphanType::DisconnectedTip
         );
         assert_eq!(
             *hard_chain
                 .validations_mapping
-                .get(&D_prime.block_hash().unwrap())
+                .get(&D_second.block_hash().unwrap())
                 .unwrap(),
-            OrphanType::DisconnectedTip
+            OrphanType::BelongsToDisconnected
         );
-        assert_eq!(*C_second_ih, 1);
-        assert_eq!(*D_prime_ih, 0);
-        assert_eq!(*D_second_ih, 0);
-        assert_eq!(*F_ih, 0);
-        assert_eq!(hard_chain.max_orphan_height, Some(6));
 
-        hard_chain.append_block(C_prime.clone()).unwrap();
-        let C_second_ih = hard_chain
-            .heights_mapping
-            .get(&C_second.height())
-            .unwrap()
-            .get(&C_second.block_hash().unwrap())
-            .unwrap();
-        let C_prime_ih = hard_chain
-            .heights_mapping
-            .get(&C_prime.height())
-            .unwrap()
-            .get(&C_prime.block_hash().unwrap())
-            .unwrap();
-        let D_prime_ih = hard_chain
-            .heights_mapping
-            .get(&D_prime.height())
-            .unwrap()
-            .get(&D_prime.block_hash().unwrap())
-            .unwrap();
-        let D_second_ih = hard_chain
-            .heights_mapping
-            .get(&D_second.height())
-            .unwrap()
-            .get(&D_second.block_hash().unwrap())
-            .unwrap();
-        let F_ih = hard_chain
-            .heights_mapping
-            .get(&F.height())
-            .unwrap()
-            .get(&F.block_hash().unwrap())
-            .unwrap();
-        assert_eq!(
-            *hard_chain
-                .validations_mapping
-                .get(&F.block_hash().unwrap())
-                .unwrap(),
-            OrphanType::DisconnectedTip
-        );
+        hard_chain.append_block(F_second.clone()).unwrap();
+
         assert_eq!(
             *hard_chain
                 .validations_mapping
-                .get(&C_second.block_hash().unwrap())
+                .get(&E_second.block_hash().unwrap())
                 .unwrap(),
             OrphanType::BelongsToDisconnected
         );
@@ -1945,130 +4054,221 @@ mod tests {
                 .validations_mapping
                 .get(&D_second.block_hash().unwrap())
                 .unwrap(),
-            OrphanType::DisconnectedTip
+            OrphanType::BelongsToDisconnected
         );
         assert_eq!(
             *hard_chain
                 .validations_mapping
-                .get(&C_prime.block_hash().unwrap())
+                .get(&F_second.block_hash().unwrap())
                 .unwrap(),
-            OrphanType::BelongsToDisconnected
+            OrphanType::DisconnectedTip
         );
+
+        // We should have a disconnected chain of `E''` and `F''`
+        // with the tip of `D''` pointing to `F''`.
         assert_eq!(
             *hard_chain
-                .validations_mapping
-                .get(&D_prime.block_hash().unwrap())
+                .disconnected_tips_mapping
+                .get(&F_second.block_hash().unwrap())
                 .unwrap(),
-            OrphanType::DisconnectedTip
+            D_second.block_hash().unwrap()
         );
-        assert_eq!(hard_chain.max_orphan_height, Some(6));
-
-        assert_eq!(*C_second_ih, 1);
-        assert_eq!(*C_prime_ih, 1);
-        assert_eq!(*D_prime_ih, 0);
-        assert_eq!(*D_second_ih, 0);
-        assert_eq!(*F_ih, 0);
-
-        hard_chain.append_block(D.clone()).unwrap();
-        let C_second_ih = hard_chain
-            .heights_mapping
-            .get(&C_second.height())
-            .unwrap()
-            .get(&C_second.block_hash().unwrap())
-            .unwrap();
-        let C_prime_ih = hard_chain
-            .heights_mapping
-            .get(&C_prime.height())
-            .unwrap()
-            .get(&C_prime.block_hash().unwrap())
-            .unwrap();
-        let D_prime_ih = hard_chain
-            .heights_mapping
-            .get(&D_prime.height())
-            .unwrap()
-            .get(&D_prime.block_hash().unwrap())
-            .unwrap();
-        let D_second_ih = hard_chain
-            .heights_mapping
-            .get(&D_second.height())
-            .unwrap()
+        let heads_mapping = hard_chain
+            .disconnected_heads_mapping
             .get(&D_second.block_hash().unwrap())
             .unwrap();
-        let F_ih = hard_chain
-            .heights_mapping
-            .get(&F.height())
-            .unwrap()
-            .get(&F.block_hash().unwrap())
-            .unwrap();
-        let D_ih = hard_chain
-            .heights_mapping
-            .get(&D.height())
-            .unwrap()
-            .get(&D.block_hash().unwrap())
+        let (largest_height, largest_tip) = hard_chain
+            .disconnected_heads_heights
+            .get(&D_second.block_hash().unwrap())
             .unwrap();
+        assert!(heads_mapping.contains(&F_second.block_hash().unwrap()));
+        assert_eq!(*largest_height, F_second.height());
+        assert_eq!(largest_tip, &F_second.block_hash().unwrap());
+
+        assert_eq!(hard_chain.height(), 1);
+        assert_eq!(hard_chain.canonical_tip(), A);
+
+        hard_chain.append_block(C.clone()).unwrap();
+        hard_chain.append_block(D.clone()).unwrap();
+        hard_chain.append_block(F.clone()).unwrap();
+        hard_chain.append_block(E.clone()).unwrap();
+        hard_chain.append_block(G.clone()).unwrap();
+
         assert_eq!(
             *hard_chain
                 .validations_mapping
-                .get(&C_second.block_hash().unwrap())
+                .get(&C.block_hash().unwrap())
                 .unwrap(),
             OrphanType::BelongsToDisconnected
         );
         assert_eq!(
             *hard_chain
                 .validations_mapping
-                .get(&D_second.block_hash().unwrap())
+                .get(&D.block_hash().unwrap())
                 .unwrap(),
-            OrphanType::DisconnectedTip
+            OrphanType::BelongsToDisconnected
         );
         assert_eq!(
             *hard_chain
                 .validations_mapping
-                .get(&C_prime.block_hash().unwrap())
+                .get(&E.block_hash().unwrap())
                 .unwrap(),
             OrphanType::BelongsToDisconnected
         );
         assert_eq!(
             *hard_chain
                 .validations_mapping
-                .get(&D_prime.block_hash().unwrap())
+                .get(&F.block_hash().unwrap())
                 .unwrap(),
-            OrphanType::DisconnectedTip
+            OrphanType::BelongsToDisconnected
         );
         assert_eq!(
             *hard_chain
                 .validations_mapping
-                .get(&D.block_hash().unwrap())
+                .get(&G.block_hash().unwrap())
                 .unwrap(),
             OrphanType::DisconnectedTip
         );
-        assert_eq!(
+
+        assert_eq!(hard_chain.height(), 1);
+        assert_eq!(hard_chain.canonical_tip(), A);
+
+        // We now append `B'` and the canonical tip should be `B'`
+        hard_chain.append_block(B_prime.clone()).unwrap();
+
+        assert_eq!(hard_chain.height(), 2);
+        assert_eq!(hard_chain.canonical_tip(), B_prime);
+
+        hard_chain.append_block(C_second.clone()).unwrap();
+
+        // The chain should now be pointing to `F''` as being the canonical tip
+        assert_eq!(hard_chain.height(), 6);
+        assert_eq!(hard_chain.canonical_tip(), F_second);
+
+        // We now append `B` and the chain should switch to `G` as the canonical tip
+        hard_chain.append_block(B.clone()).unwrap();
+
+        assert_eq!(hard_chain.height(), 7);
+        assert_eq!(hard_chain.canonical_tip(), G);
+    }
+
+    #[test]
+    /// Assertions in stages on random order
+    /// of appended blocks.
+    fn stages_append_test3() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let mut A = DummyBlock::new(Some(Hash::NULL), 1);
+        let A = Arc::new(A);
+
+        let mut B = DummyBlock::new(Some(A.block_hash().unwrap()), 2);
+        let B = Arc::new(B);
+
+        let mut C = DummyBlock::new(Some(B.block_hash().unwrap()), 3);
+        let C = Arc::new(C);
+
+        let mut D = DummyBlock::new(Some(C.block_hash().unwrap()), 4);
+        let D = Arc::new(D);
+
+        let mut E = DummyBlock::new(Some(D.block_hash().unwrap()), 5);
+        let E = Arc::new(E);
+
+        let mut F = DummyBlock::new(Some(E.block_hash().unwrap()), 6);
+        let F = Arc::new(F);
+
+        let mut G = DummyBlock::new(Some(F.block_hash().unwrap()), 7);
+        let G = Arc::new(G);
+
+        let mut B_prime = DummyBlock::new(Some(A.block_hash().unwrap()), 2);
+        let B_prime = Arc::new(B_prime);
+
+        let mut C_prime = DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3);
+        let C_prime = Arc::new(C_prime);
+
+        let mut D_prime = DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4);
+        let D_prime = Arc::new(D_prime);
+
+        let mut E_prime = DummyBlock::new(Some(D_prime.block_hash().unwrap()), 5);
+        let E_prime = Arc::new(E_prime);
+
+        let mut C_second = DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3);
+        let C_second = Arc::new(C_second);
+
+        let mut D_second = DummyBlock::new(Some(C_second.block_hash().unwrap()), 4);
+        let D_second = Arc::new(D_second);
+
+        let mut E_second = DummyBlock::new(Some(D_second.block_hash().unwrap()), 5);
+        let E_second = Arc::new(E_second);
+
+        let mut F_second = DummyBlock::new(Some(E_second.block_hash().unwrap()), 6);
+        let F_second = Arc::new(F_second);
+
+        let mut D_tertiary = DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4);
+        let D_tertiary = Arc::new(D_tertiary);
+
+        hard_chain.append_block(C_second.clone()).unwrap();
+        let C_second_ih = hard_chain
+            .heights_mapping
+            .get(&C_second.height())
+            .unwrap()
+            .get(&C_second.block_hash().unwrap())
+            .unwrap();
+
+        // Check validations mapping
+        assert_eq!(
             *hard_chain
                 .validations_mapping
-                .get(&F.block_hash().unwrap())
+                .get(&C_second.block_hash().unwrap())
                 .unwrap(),
             OrphanType::DisconnectedTip
         );
-        assert_eq!(hard_chain.max_orphan_height, Some(6));
 
-        assert_eq!(*C_second_ih, 1);
-        assert_eq!(*C_prime_ih, 1);
-        assert_eq!(*D_prime_ih, 0);
-        assert_eq!(*D_second_ih, 0);
-        assert_eq!(*F_ih, 0);
-        assert_eq!(*D_ih, 0);
+        // Check inverse height
+        assert_eq!(*C_second_ih, 0);
 
-        hard_chain.append_block(G.clone()).unwrap();
+        // Check max orphan height
+        assert_eq!(hard_chain.max_orphan_height, Some(3));
+
+        hard_chain.append_block(D_prime.clone()).unwrap();
         let C_second_ih = hard_chain
             .heights_mapping
             .get(&C_second.height())
             .unwrap()
             .get(&C_second.block_hash().unwrap())
             .unwrap();
-        let C_prime_ih = hard_chain
+        let D_prime_ih = hard_chain
             .heights_mapping
-            .get(&C_prime.height())
+            .get(&D_prime.height())
             .unwrap()
-            .get(&C_prime.block_hash().unwrap())
+            .get(&D_prime.block_hash().unwrap())
+            .unwrap();
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&C_second.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::DisconnectedTip
+        );
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&D_prime.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::DisconnectedTip
+        );
+        assert_eq!(*C_second_ih, 0);
+        assert_eq!(*D_prime_ih, 0);
+        assert_eq!(hard_chain.max_orphan_height, Some(4));
+
+        hard_chain.append_block(F.clone()).unwrap();
+        assert_eq!(hard_chain.max_orphan_height, Some(6));
+        hard_chain.append_block(D_second.clone()).unwrap();
+        let C_second_ih = hard_chain
+            .heights_mapping
+            .get(&C_second.height())
+            .unwrap()
+            .get(&C_second.block_hash().unwrap())
             .unwrap();
         let D_prime_ih = hard_chain
             .heights_mapping
@@ -2082,24 +4282,19 @@ mod tests {
             .unwrap()
             .get(&D_second.block_hash().unwrap())
             .unwrap();
-        let G_ih = hard_chain
-            .heights_mapping
-            .get(&G.height())
-            .unwrap()
-            .get(&G.block_hash().unwrap())
-            .unwrap();
         let F_ih = hard_chain
             .heights_mapping
             .get(&F.height())
             .unwrap()
             .get(&F.block_hash().unwrap())
             .unwrap();
-        let D_ih = hard_chain
-            .heights_mapping
-            .get(&D.height())
-            .unwrap()
-            .get(&D.block_hash().unwrap())
-            .unwrap();
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&F.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::DisconnectedTip
+        );
         assert_eq!(
             *hard_chain
                 .validations_mapping
@@ -2117,61 +4312,97 @@ mod tests {
         assert_eq!(
             *hard_chain
                 .validations_mapping
-                .get(&C_prime.block_hash().unwrap())
+                .get(&D_prime.block_hash().unwrap())
                 .unwrap(),
-            OrphanType::BelongsToDisconnected
+            OrphanType::DisconnectedTip
         );
+        assert_eq!(*C_second_ih, 1);
+        assert_eq!(*D_prime_ih, 0);
+        assert_eq!(*D_second_ih, 0);
+        assert_eq!(*F_ih, 0);
+        assert_eq!(hard_chain.max_orphan_height, Some(6));
+
+        hard_chain.append_block(C_prime.clone()).unwrap();
+        let C_second_ih = hard_chain
+            .heights_mapping
+            .get(&C_second.height())
+            .unwrap()
+            .get(&C_second.block_hash().unwrap())
+            .unwrap();
+        let C_prime_ih = hard_chain
+            .heights_mapping
+            .get(&C_prime.height())
+            .unwrap()
+            .get(&C_prime.block_hash().unwrap())
+            .unwrap();
+        let D_prime_ih = hard_chain
+            .heights_mapping
+            .get(&D_prime.height())
+            .unwrap()
+            .get(&D_prime.block_hash().unwrap())
+            .unwrap();
+        let D_second_ih = hard_chain
+            .heights_mapping
+            .get(&D_second.height())
+            .unwrap()
+            .get(&D_second.block_hash().unwrap())
+            .unwrap();
+        let F_ih = hard_chain
+            .heights_mapping
+            .get(&F.height())
+            .unwrap()
+            .get(&F.block_hash().unwrap())
+            .unwrap();
         assert_eq!(
             *hard_chain
                 .validations_mapping
-                .get(&D_prime.block_hash().unwrap())
+                .get(&F.block_hash().unwrap())
                 .unwrap(),
             OrphanType::DisconnectedTip
         );
         assert_eq!(
             *hard_chain
                 .validations_mapping
-                .get(&D.block_hash().unwrap())
+                .get(&C_second.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::BelongsToDisconnected
+        );
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&D_second.block_hash().unwrap())
                 .unwrap(),
             OrphanType::DisconnectedTip
         );
         assert_eq!(
             *hard_chain
                 .validations_mapping
-                .get(&F.block_hash().unwrap())
+                .get(&C_prime.block_hash().unwrap())
                 .unwrap(),
             OrphanType::BelongsToDisconnected
         );
         assert_eq!(
             *hard_chain
                 .validations_mapping
-                .get(&G.block_hash().unwrap())
+                .get(&D_prime.block_hash().unwrap())
                 .unwrap(),
             OrphanType::DisconnectedTip
         );
-        assert_eq!(hard_chain.max_orphan_height, Some(7));
+        assert_eq!(hard_chain.max_orphan_height, Some(6));
 
         assert_eq!(*C_second_ih, 1);
         assert_eq!(*C_prime_ih, 1);
         assert_eq!(*D_prime_ih, 0);
         assert_eq!(*D_second_ih, 0);
-        assert_eq!(*F_ih, 1);
-        assert_eq!(*G_ih, 0);
-        assert_eq!(*D_ih, 0);
+        assert_eq!(*F_ih, 0);
 
-        hard_chain.append_block(B_prime.clone()).unwrap();
+        hard_chain.append_block(D.clone()).unwrap();
         let C_second_ih = hard_chain
             .heights_mapping
             .get(&C_second.height())
             .unwrap()
             .get(&C_second.block_hash().unwrap())
             .unwrap();
-        let B_prime_ih = hard_chain
-            .heights_mapping
-            .get(&B_prime.height())
-            .unwrap()
-            .get(&B_prime.block_hash().unwrap())
-            .unwrap();
         let C_prime_ih = hard_chain
             .heights_mapping
             .get(&C_prime.height())
@@ -2190,12 +4421,6 @@ mod tests {
             .unwrap()
             .get(&D_second.block_hash().unwrap())
             .unwrap();
-        let G_ih = hard_chain
-            .heights_mapping
-            .get(&G.height())
-            .unwrap()
-            .get(&G.block_hash().unwrap())
-            .unwrap();
         let F_ih = hard_chain
             .heights_mapping
             .get(&F.height())
@@ -2222,13 +4447,6 @@ mod tests {
                 .unwrap(),
             OrphanType::DisconnectedTip
         );
-        assert_eq!(
-            *hard_chain
-                .validations_mapping
-                .get(&B_prime.block_hash().unwrap())
-                .unwrap(),
-            OrphanType::BelongsToDisconnected
-        );
         assert_eq!(
             *hard_chain
                 .validations_mapping
@@ -2255,34 +4473,243 @@ mod tests {
                 .validations_mapping
                 .get(&F.block_hash().unwrap())
                 .unwrap(),
-            OrphanType::BelongsToDisconnected
-        );
-        assert_eq!(
-            *hard_chain
-                .validations_mapping
-                .get(&G.block_hash().unwrap())
-                .unwrap(),
             OrphanType::DisconnectedTip
         );
-        assert_eq!(hard_chain.max_orphan_height, Some(7));
+        assert_eq!(hard_chain.max_orphan_height, Some(6));
 
         assert_eq!(*C_second_ih, 1);
-        assert_eq!(*B_prime_ih, 2);
         assert_eq!(*C_prime_ih, 1);
         assert_eq!(*D_prime_ih, 0);
         assert_eq!(*D_second_ih, 0);
-        assert_eq!(*F_ih, 1);
-        assert_eq!(*G_ih, 0);
+        assert_eq!(*F_ih, 0);
         assert_eq!(*D_ih, 0);
 
-        hard_chain.append_block(D_tertiary.clone()).unwrap();
+        hard_chain.append_block(G.clone()).unwrap();
         let C_second_ih = hard_chain
             .heights_mapping
             .get(&C_second.height())
             .unwrap()
             .get(&C_second.block_hash().unwrap())
             .unwrap();
-        let B_prime_ih = hard_chain
+        let C_prime_ih = hard_chain
+            .heights_mapping
+            .get(&C_prime.height())
+            .unwrap()
+            .get(&C_prime.block_hash().unwrap())
+            .unwrap();
+        let D_prime_ih = hard_chain
+            .heights_mapping
+            .get(&D_prime.height())
+            .unwrap()
+            .get(&D_prime.block_hash().unwrap())
+            .unwrap();
+        let D_second_ih = hard_chain
+            .heights_mapping
+            .get(&D_second.height())
+            .unwrap()
+            .get(&D_second.block_hash().unwrap())
+            .unwrap();
+        let G_ih = hard_chain
+            .heights_mapping
+            .get(&G.height())
+            .unwrap()
+            .get(&G.block_hash().unwrap())
+            .unwrap();
+        let F_ih = hard_chain
+            .heights_mapping
+            .get(&F.height())
+            .unwrap()
+            .get(&F.block_hash().unwrap())
+            .unwrap();
+        let D_ih = hard_chain
+            .heights_mapping
+            .get(&D.height())
+            .unwrap()
+            .get(&D.block_hash().unwrap())
+            .unwrap();
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&C_second.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::BelongsToDisconnected
+        );
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&D_second.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::DisconnectedTip
+        );
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&C_prime.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::BelongsToDisconnected
+        );
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&D_prime.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::DisconnectedTip
+        );
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&D.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::DisconnectedTip
+        );
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&F.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::BelongsToDisconnected
+        );
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&G.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::DisconnectedTip
+        );
+        assert_eq!(hard_chain.max_orphan_height, Some(7));
+
+        assert_eq!(*C_second_ih, 1);
+        assert_eq!(*C_prime_ih, 1);
+        assert_eq!(*D_prime_ih, 0);
+        assert_eq!(*D_second_ih, 0);
+        assert_eq!(*F_ih, 1);
+        assert_eq!(*G_ih, 0);
+        assert_eq!(*D_ih, 0);
+
+        hard_chain.append_block(B_prime.clone()).unwrap();
+        let C_second_ih = hard_chain
+            .heights_mapping
+            .get(&C_second.height())
+            .unwrap()
+            .get(&C_second.block_hash().unwrap())
+            .unwrap();
+        let B_prime_ih = hard_chain
+            .heights_mapping
+            .get(&B_prime.height())
+            .unwrap()
+            .get(&B_prime.block_hash().unwrap())
+            .unwrap();
+        let C_prime_ih = hard_chain
+            .heights_mapping
+            .get(&C_prime.height())
+            .unwrap()
+            .get(&C_prime.block_hash().unwrap())
+            .unwrap();
+        let D_prime_ih = hard_chain
+            .heights_mapping
+            .get(&D_prime.height())
+            .unwrap()
+            .get(&D_prime.block_hash().unwrap())
+            .unwrap();
+        let D_second_ih = hard_chain
+            .heights_mapping
+            .get(&D_second.height())
+            .unwrap()
+            .get(&D_second.block_hash().unwrap())
+            .unwrap();
+        let G_ih = hard_chain
+            .heights_mapping
+            .get(&G.height())
+            .unwrap()
+            .get(&G.block_hash().unwrap())
+            .unwrap();
+        let F_ih = hard_chain
+            .heights_mapping
+            .get(&F.height())
+            .unwrap()
+            .get(&F.block_hash().unwrap())
+            .unwrap();
+        let D_ih = hard_chain
+            .heights_mapping
+            .get(&D.height())
+            .unwrap()
+            .get(&D.block_hash().unwrap())
+            .unwrap();
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&C_second.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::BelongsToDisconnected
+        );
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&D_second.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::DisconnectedTip
+        );
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&B_prime.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::BelongsToDisconnected
+        );
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&C_prime.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::BelongsToDisconnected
+        );
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&D_prime.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::DisconnectedTip
+        );
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&D.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::DisconnectedTip
+        );
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&F.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::BelongsToDisconnected
+        );
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&G.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::DisconnectedTip
+        );
+        assert_eq!(hard_chain.max_orphan_height, Some(7));
+
+        assert_eq!(*C_second_ih, 1);
+        assert_eq!(*B_prime_ih, 2);
+        assert_eq!(*C_prime_ih, 1);
+        assert_eq!(*D_prime_ih, 0);
+        assert_eq!(*D_second_ih, 0);
+        assert_eq!(*F_ih, 1);
+        assert_eq!(*G_ih, 0);
+        assert_eq!(*D_ih, 0);
+
+        hard_chain.append_block(D_tertiary.clone()).unwrap();
+        let C_second_ih = hard_chain
+            .heights_mapping
+            .get(&C_second.height())
+            .unwrap()
+            .get(&C_second.block_hash().unwrap())
+            .unwrap();
+        let B_prime_ih = hard_chain
             .heights_mapping
             .get(&B_prime.height())
             .unwrap()
@@ -6079,66 +8506,2789 @@ mod tests {
             OrphanType::BelongsToDisconnected
         );
         assert_eq!(
-            *hard_chain
-                .validations_mapping
-                .get(&F.block_hash().unwrap())
-                .unwrap(),
-            OrphanType::BelongsToDisconnected
+            *hard_chain
+                .validations_mapping
+                .get(&F.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::BelongsToDisconnected
+        );
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&G.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::DisconnectedTip
+        );
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&C_prime.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::BelongsToDisconnected
+        );
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&D_prime.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::DisconnectedTip
+        );
+        assert_eq!(
+            *hard_chain
+                .validations_mapping
+                .get(&D_tertiary.block_hash().unwrap())
+                .unwrap(),
+            OrphanType::DisconnectedTip
+        );
+
+        // Check inverse height
+        assert_eq!(*B_prime_ih, 4);
+        assert_eq!(*C_prime_ih, 1);
+        assert_eq!(*C_second_ih, 3);
+        assert_eq!(*D_second_ih, 2);
+        assert_eq!(*E_second_ih, 1);
+        assert_eq!(*F_second_ih, 0);
+        assert_eq!(*E_ih, 2);
+        assert_eq!(*F_ih, 1);
+        assert_eq!(*G_ih, 0);
+        assert_eq!(*D_ih, 3);
+        assert_eq!(*C_ih, 4);
+        assert_eq!(*B_ih, 5);
+        assert_eq!(*D_prime_ih, 0);
+        assert_eq!(*D_tertiary_ih, 0);
+
+        // Check max orphan height
+        assert_eq!(hard_chain.max_orphan_height, Some(7));
+
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(E_prime.clone()).unwrap();
+
+        assert_eq!(hard_chain.height(), 7);
+        assert_eq!(hard_chain.canonical_tip(), G);
+        assert_eq!(hard_chain.max_orphan_height, Some(6));
+    }
+
+    #[test]
+    fn it_returns_blocks_since_hash() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let C_prime = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(B.clone()).unwrap();
+        hard_chain.append_block(C.clone()).unwrap();
+        hard_chain.append_block(C_prime.clone()).unwrap();
+
+        assert_eq!(
+            hard_chain.blocks_since(&A.block_hash().unwrap()),
+            Ok(vec![B.clone(), C.clone()])
+        );
+        assert_eq!(
+            hard_chain.blocks_since(&Hash::NULL),
+            Ok(vec![A.clone(), B, C.clone()])
+        );
+        assert_eq!(
+            hard_chain.blocks_since(&C.block_hash().unwrap()),
+            Ok(vec![])
+        );
+        assert_eq!(
+            hard_chain.blocks_since(&C_prime.block_hash().unwrap()),
+            Err(ChainErr::NoSuchBlock(C_prime.block_hash().unwrap()))
+        );
+
+        // Rewound blocks are no longer in the height index
+        hard_chain.rewind(&A.block_hash().unwrap()).unwrap();
+
+        assert_eq!(
+            hard_chain.blocks_since(&C.block_hash().unwrap()),
+            Err(ChainErr::NoSuchBlock(C.block_hash().unwrap()))
+        );
+        assert_eq!(
+            hard_chain.blocks_since(&A.block_hash().unwrap()),
+            Ok(vec![])
+        );
+    }
+
+    #[test]
+    fn it_returns_canonical_and_orphan_blocks_at_height() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let C_prime = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(B.clone()).unwrap();
+        hard_chain.append_block(C.clone()).unwrap();
+        hard_chain.append_block(C_prime.clone()).unwrap();
+
+        assert_eq!(hard_chain.at_height(2), (Some(B), vec![]));
+        assert_eq!(hard_chain.at_height(3), (Some(C), vec![C_prime]));
+        assert_eq!(hard_chain.at_height(4), (None, vec![]));
+    }
+
+    #[test]
+    fn it_removes_stale_valid_tips() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let mut blocks = Vec::new();
+        let mut parent_hash = Hash::NULL;
+
+        for height in 1..16 {
+            let block = Arc::new(DummyBlock::new(Some(parent_hash), height));
+
+            parent_hash = block.block_hash().unwrap();
+            blocks.push(block);
+        }
+
+        let B_prime = Arc::new(DummyBlock::new(Some(blocks[0].block_hash().unwrap()), 2));
+
+        hard_chain.append_block(blocks[0].clone()).unwrap();
+        hard_chain.append_block(blocks[1].clone()).unwrap();
+        hard_chain.append_block(B_prime.clone()).unwrap();
+
+        assert!(hard_chain
+            .valid_tips
+            .contains(&B_prime.block_hash().unwrap()));
+
+        for block in blocks[2..12].iter() {
+            hard_chain.append_block(block.clone()).unwrap();
+        }
+
+        // The tip is not yet stale at height 12
+        assert_eq!(hard_chain.height(), 12);
+        assert!(hard_chain
+            .valid_tips
+            .contains(&B_prime.block_hash().unwrap()));
+
+        for block in blocks[12..].iter() {
+            hard_chain.append_block(block.clone()).unwrap();
+        }
+
+        assert_eq!(hard_chain.height(), 15);
+        assert!(hard_chain.valid_tips.is_empty());
+        assert!(hard_chain.orphan_pool.is_empty());
+        assert!(hard_chain.validations_mapping.is_empty());
+        assert!(hard_chain.heights_mapping.get(&2).is_none());
+        assert_eq!(hard_chain.max_orphan_height, None);
+    }
+
+    #[test]
+    fn it_stores_block_extra_data() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<ExtraDataBlock>::new(db);
+
+        let A = Arc::new(ExtraDataBlock {
+            block: DummyBlock::new(Some(Hash::NULL), 1),
+            extra_data: b"miner".to_vec(),
+        });
+
+        let B = Arc::new(ExtraDataBlock {
+            block: DummyBlock::new(Some(A.block_hash().unwrap()), 2),
+            extra_data: Vec::new(),
+        });
+
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(B.clone()).unwrap();
+
+        assert_eq!(
+            hard_chain.query_extra_data(&A.block_hash().unwrap()),
+            Some(b"miner".to_vec())
+        );
+        assert_eq!(hard_chain.query_extra_data(&B.block_hash().unwrap()), None);
+        assert_eq!(
+            hard_chain
+                .query(&A.block_hash().unwrap())
+                .unwrap()
+                .extra_data(),
+            Some(&b"miner"[..])
+        );
+    }
+
+    #[test]
+    fn it_deletes_extra_data_of_rewound_blocks() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<ExtraDataBlock>::new(db);
+
+        let A = Arc::new(ExtraDataBlock {
+            block: DummyBlock::new(Some(Hash::NULL), 1),
+            extra_data: b"miner".to_vec(),
+        });
+
+        let B = Arc::new(ExtraDataBlock {
+            block: DummyBlock::new(Some(A.block_hash().unwrap()), 2),
+            extra_data: b"pool".to_vec(),
+        });
+
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(B.clone()).unwrap();
+        hard_chain.rewind(&A.block_hash().unwrap()).unwrap();
+
+        assert_eq!(
+            hard_chain.query_extra_data(&A.block_hash().unwrap()),
+            Some(b"miner".to_vec())
+        );
+        assert_eq!(hard_chain.query_extra_data(&B.block_hash().unwrap()), None);
+    }
+
+    #[test]
+    fn it_marks_tip_as_invalid() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let D = Arc::new(DummyBlock::new(Some(C.block_hash().unwrap()), 4));
+        let C_prime = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let D_prime = Arc::new(DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4));
+
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(B.clone()).unwrap();
+        hard_chain.append_block(C.clone()).unwrap();
+        hard_chain.append_block(D.clone()).unwrap();
+        hard_chain.append_block(C_prime.clone()).unwrap();
+        hard_chain.append_block(D_prime.clone()).unwrap();
+
+        assert_eq!(
+            hard_chain.mark_tip_as_invalid(&C_prime.block_hash().unwrap()),
+            Err(ChainErr::NoSuchBlock(C_prime.block_hash().unwrap()))
+        );
+
+        hard_chain
+            .mark_tip_as_invalid(&D_prime.block_hash().unwrap())
+            .unwrap();
+
+        assert!(hard_chain.valid_tips.is_empty());
+        assert!(hard_chain.orphan_pool.is_empty());
+        assert!(hard_chain.validations_mapping.is_empty());
+        assert_eq!(hard_chain.max_orphan_height, None);
+        assert_eq!(
+            hard_chain.append_block(C_prime),
+            Err(ChainErr::MarkedInvalid)
+        );
+        assert_eq!(
+            hard_chain.append_block(D_prime),
+            Err(ChainErr::MarkedInvalid)
+        );
+        assert_eq!(hard_chain.canonical_tip(), D);
+    }
+
+    #[test]
+    fn it_fails_on_height_overflow() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        hard_chain.height = u64::max_value() - 1;
+
+        let A = Arc::new(DummyBlock::with_timestamp(
+            Some(Hash::NULL),
+            u64::max_value(),
+            Utc.timestamp(1, 0),
+        ));
+        let B = Arc::new(DummyBlock::with_timestamp(
+            Some(A.block_hash().unwrap()),
+            u64::max_value(),
+            Utc.timestamp(2, 0),
+        ));
+
+        assert_eq!(hard_chain.append_block(A.clone()), Ok(()));
+        assert_eq!(hard_chain.height(), u64::max_value());
+        assert_eq!(hard_chain.append_block(B), Err(ChainErr::HeightOverflow));
+        assert_eq!(hard_chain.canonical_tip(), A);
+    }
+
+    #[test]
+    fn it_writes_blocks_asynchronously() {
+        let db = test_helpers::init_tempdb();
+        let chain = Arc::new(RwLock::new(Chain::<DummyBlock>::new(db)));
+        let chain_ref = ChainRef::new(chain.clone());
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 3));
+
+        assert_eq!(chain_ref.write_block_async(A.clone()).wait(), Ok(Ok(())));
+        assert_eq!(
+            chain_ref.write_block_async(A.clone()).wait(),
+            Ok(Err(ChainErr::AlreadyInChain))
+        );
+        assert_eq!(
+            chain_ref.write_block_async(B.clone()).wait(),
+            Ok(Err(ChainErr::BadHeight {
+                block: B.block_hash().unwrap(),
+                got: 3,
+                expected_min: 2,
+                expected_max: 2,
+            }))
+        );
+        assert_eq!(chain.read().canonical_tip(), A);
+    }
+
+    #[test]
+    fn it_limits_concurrent_block_processing() {
+        let db = test_helpers::init_tempdb();
+        let chain = Arc::new(RwLock::new(Chain::<DummyBlock>::new(db)));
+        let chain_ref = ChainRef::with_processing_permits(chain.clone(), 2);
+
+        // Heights never exceed `MAX_HEIGHT` so that blocks
+        // are accepted in whichever order they are processed.
+        let mut blocks = vec![Arc::new(DummyBlock::new(Some(Hash::NULL), 1))];
+
+        for height in 2..=MAX_HEIGHT {
+            let parent_hash = blocks.last().unwrap().block_hash().unwrap();
+            blocks.push(Arc::new(DummyBlock::new(Some(parent_hash), height)));
+        }
+
+        let receivers: Vec<_> = {
+            let _guard = chain.write();
+            let receivers: Vec<_> = blocks
+                .iter()
+                .map(|block| chain_ref.write_block_async(block.clone()))
+                .collect();
+
+            let deadline = Instant::now() + Duration::from_secs(5);
+
+            // Two blocks hold the permits while waiting
+            // for the chain and the others wait for them.
+            while chain_ref.processing_semaphore.available_permits() != 0 {
+                assert!(Instant::now() < deadline);
+                thread::sleep(Duration::from_millis(1));
+            }
+
+            thread::sleep(Duration::from_millis(20));
+            assert_eq!(chain_ref.processing_semaphore.available_permits(), 0);
+
+            receivers
+        };
+
+        // All blocks are eventually processed
+        for receiver in receivers {
+            assert_eq!(receiver.wait(), Ok(Ok(())));
+        }
+
+        assert_eq!(chain.read().height(), MAX_HEIGHT);
+        assert_eq!(chain.read().canonical_tip(), *blocks.last().unwrap());
+        assert_eq!(chain_ref.processing_semaphore.available_permits(), 2);
+    }
+
+    #[test]
+    fn it_takes_a_processing_permit_when_appending() {
+        let db = test_helpers::init_tempdb();
+        let chain = Arc::new(RwLock::new(Chain::<DummyBlock>::new(db)));
+        let chain_ref = ChainRef::with_processing_permits(chain.clone(), 1);
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+
+        let handle = {
+            let _guard = chain.write();
+            let handle = {
+                let chain_ref = chain_ref.clone();
+                let A = A.clone();
+
+                thread::spawn(move || chain_ref.append_block(A))
+            };
+            let deadline = Instant::now() + Duration::from_secs(5);
+
+            // The append holds the permit while waiting for the chain
+            while chain_ref.processing_semaphore.available_permits() != 0 {
+                assert!(Instant::now() < deadline);
+                thread::sleep(Duration::from_millis(1));
+            }
+
+            handle
+        };
+
+        assert_eq!(handle.join().unwrap(), Ok(()));
+        assert_eq!(chain_ref.processing_semaphore.available_permits(), 1);
+        assert_eq!(chain_ref.canonical_tip(), A);
+    }
+
+    #[test]
+    fn it_waits_for_height() {
+        let db = test_helpers::init_tempdb();
+        let chain = Arc::new(RwLock::new(Chain::<DummyBlock>::new(db)));
+        let chain_ref = ChainRef::new(chain.clone());
+
+        let mut blocks = vec![Arc::new(DummyBlock::new(Some(Hash::NULL), 1))];
+
+        for height in 2..6 {
+            let parent_hash = blocks.last().unwrap().block_hash().unwrap();
+            blocks.push(Arc::new(DummyBlock::new(Some(parent_hash), height)));
+        }
+
+        assert_eq!(
+            chain_ref.wait_for_height(1, Duration::from_millis(10)),
+            Err(WaitError::Timeout)
+        );
+
+        let appender = thread::spawn(move || {
+            for block in blocks {
+                thread::sleep(Duration::from_millis(20));
+                chain.write().append_block(block).unwrap();
+            }
+        });
+
+        assert_eq!(chain_ref.wait_for_height(3, Duration::from_secs(5)), Ok(()));
+
+        assert!(chain_ref.chain.read().height() >= 3);
+
+        appender.join().unwrap();
+
+        assert_eq!(
+            chain_ref.wait_for_height(5, Duration::from_millis(10)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn it_evicts_disconnected_blocks_without_touching_subscribers() {
+        let db = test_helpers::init_tempdb();
+        let chain_ref = ChainRef::new(Arc::new(RwLock::new(Chain::<DummyBlock>::new(db))));
+        let events = chain_ref.subscribe();
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let B_prime = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C_prime = Arc::new(DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3));
+
+        chain_ref.append_block(A.clone()).unwrap();
+        chain_ref.append_block(B.clone()).unwrap();
+        assert_eq!(chain_ref.query(&B.block_hash().unwrap()), Some(B.clone()));
+
+        chain_ref.append_block(B_prime.clone()).unwrap();
+        chain_ref.append_block(C_prime.clone()).unwrap();
+
+        assert_eq!(chain_ref.canonical_tip(), C_prime);
+        assert!(chain_ref.query(&B.block_hash().unwrap()).is_none());
+
+        let chain = chain_ref.chain.read();
+        assert_eq!(chain.subscribers.lock().len(), 1);
+        assert!(chain.disconnected_hashes.is_none());
+
+        let events: Vec<_> = events.try_iter().collect();
+        assert!(events.contains(&ChainEvent::Disconnected(B.clone())));
+        assert!(events.contains(&ChainEvent::Connected(C_prime.clone())));
+    }
+
+    #[test]
+    fn it_caches_block_hashes_by_height() {
+        let db = test_helpers::init_tempdb();
+        let chain = Arc::new(RwLock::new(Chain::<DummyBlock>::new(db)));
+        let chain_ref = ChainRef::new(chain.clone());
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let B_prime = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+
+        chain.write().append_block(A.clone()).unwrap();
+        chain.write().append_block(B.clone()).unwrap();
+        chain.write().append_block(C.clone()).unwrap();
+
+        assert!(!chain_ref.height_cache.0.lock().contains(&2));
+        assert_eq!(chain_ref.query_by_height(2), Some(B.clone()));
+        assert!(chain_ref.height_cache.0.lock().contains(&2));
+        assert_eq!(chain_ref.query_by_height(3), Some(C.clone()));
+
+        // Second call is served from the cache
+        assert_eq!(chain_ref.query_by_height(2), Some(B.clone()));
+
+        chain.write().rewind(&A.block_hash().unwrap()).unwrap();
+
+        assert!(!chain_ref.height_cache.0.lock().contains(&2));
+        assert!(!chain_ref.height_cache.0.lock().contains(&3));
+        assert_eq!(chain_ref.query_by_height(2), None);
+
+        chain.write().append_block(B_prime.clone()).unwrap();
+
+        assert_eq!(chain_ref.query_by_height(2), Some(B_prime));
+        assert_eq!(chain_ref.query_by_height(1), Some(A));
+    }
+
+    #[test]
+    fn it_does_not_serve_stale_blocks_by_height_after_switch() {
+        let db = test_helpers::init_tempdb();
+        let chain = Arc::new(RwLock::new(Chain::<DummyBlock>::new(db)));
+        let chain_ref = ChainRef::new(chain.clone());
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let B_prime = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C_prime = Arc::new(DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3));
+        let D_prime = Arc::new(DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4));
+
+        for block in [&A, &B, &C, &B_prime, &C_prime].iter() {
+            chain.write().append_block((*block).clone()).unwrap();
+        }
+
+        assert_eq!(chain_ref.query_by_height(2), Some(B.clone()));
+        assert_eq!(chain_ref.query_by_height(3), Some(C.clone()));
+        assert!(chain_ref.height_cache.0.lock().contains(&3));
+
+        // Switch to the `B'` chain
+        chain.write().append_block(D_prime.clone()).unwrap();
+
+        assert_eq!(chain.read().canonical_tip(), D_prime);
+        assert_eq!(chain_ref.query_by_height(1), Some(A));
+        assert_eq!(chain_ref.query_by_height(2), Some(B_prime));
+        assert_eq!(chain_ref.query_by_height(3), Some(C_prime));
+        assert_eq!(chain_ref.query_by_height(4), Some(D_prime));
+    }
+
+    #[test]
+    fn it_does_not_block_on_contended_try_query() {
+        let db = test_helpers::init_tempdb();
+        let chain = Arc::new(RwLock::new(Chain::<DummyBlock>::new(db)));
+        let chain_ref = ChainRef::new(chain.clone());
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let A_hash = A.block_hash().unwrap();
+        chain.write().append_block(A.clone()).unwrap();
+
+        let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+        let writer_chain = chain.clone();
+
+        let writer = thread::spawn(move || {
+            let _guard = writer_chain.write();
+            locked_tx.send(()).unwrap();
+            thread::sleep(Duration::from_millis(50));
+        });
+
+        locked_rx.recv().unwrap();
+
+        let reader_ref = chain_ref.clone();
+        let reader_hash = A_hash.clone();
+        let reader = thread::spawn(move || reader_ref.try_query(&reader_hash));
+
+        assert!(reader.join().unwrap().is_none());
+
+        writer.join().unwrap();
+
+        assert_eq!(chain_ref.try_query(&A_hash), Some(A));
+    }
+
+    #[derive(Debug, Default)]
+    /// State db recording the transitions it receives
+    struct LogStateDb {
+        log: Vec<(bool, Vec<u8>)>,
+    }
+
+    impl StateDb for LogStateDb {
+        fn apply_transition(&mut self, transition: &[u8]) {
+            self.log.push((true, transition.to_vec()));
+        }
+
+        fn revert_transition(&mut self, transition: &[u8]) {
+            self.log.push((false, transition.to_vec()));
+        }
+    }
+
+    #[test]
+    fn it_reverts_and_reapplies_state_transitions_on_reorg() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+        let state_db = Arc::new(Mutex::new(LogStateDb::default()));
+        hard_chain.register_state_db(state_db.clone());
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let B_prime = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C_prime = Arc::new(DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3));
+        let D_prime = Arc::new(DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4));
+
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(B.clone()).unwrap();
+        hard_chain.append_block(C.clone()).unwrap();
+        hard_chain.append_block(B_prime.clone()).unwrap();
+        hard_chain.append_block(C_prime.clone()).unwrap();
+
+        let transition = |block: &Arc<DummyBlock>| block.state_transition().unwrap();
+
+        assert_eq!(
+            state_db.lock().log,
+            vec![
+                (true, transition(&A)),
+                (true, transition(&B)),
+                (true, transition(&C)),
+            ]
+        );
+
+        // Switch to the fork of A
+        hard_chain.append_block(D_prime.clone()).unwrap();
+
+        assert_eq!(hard_chain.canonical_tip(), D_prime);
+        assert_eq!(
+            state_db.lock().log[3..].to_vec(),
+            vec![
+                (false, transition(&C)),
+                (false, transition(&B)),
+                (true, transition(&B_prime)),
+                (true, transition(&C_prime)),
+                (true, transition(&D_prime)),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_records_recent_reorgs() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+        hard_chain.set_max_reorg_history(2);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let D = Arc::new(DummyBlock::new(Some(C.block_hash().unwrap()), 4));
+        let B_prime = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C_prime = Arc::new(DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3));
+        let D_prime = Arc::new(DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4));
+        let E_prime = Arc::new(DummyBlock::new(Some(D_prime.block_hash().unwrap()), 5));
+        let hashes = |blocks: &[&Arc<DummyBlock>]| -> Vec<Hash> {
+            blocks.iter().map(|b| b.block_hash().unwrap()).collect()
+        };
+
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(B.clone()).unwrap();
+        hard_chain.append_block(B_prime.clone()).unwrap();
+
+        assert!(hard_chain.recent_reorgs().is_empty());
+
+        // First reorg
+        hard_chain.append_block(C_prime.clone()).unwrap();
+
+        // Second reorg
+        hard_chain.append_block(C.clone()).unwrap();
+        hard_chain.append_block(D.clone()).unwrap();
+
+        assert_eq!(hard_chain.canonical_tip(), D);
+        assert_eq!(hard_chain.recent_reorgs().len(), 2);
+
+        let first = &hard_chain.recent_reorgs()[0];
+        let second = &hard_chain.recent_reorgs()[1];
+
+        assert_eq!(first.at_height, 1);
+        assert_eq!(first.removed, hashes(&[&B]));
+        assert_eq!(first.added, hashes(&[&B_prime, &C_prime]));
+        assert_eq!(second.at_height, 1);
+        assert_eq!(second.removed, hashes(&[&B_prime, &C_prime]));
+        assert_eq!(second.added, hashes(&[&B, &C, &D]));
+        assert!(second.timestamp >= first.timestamp);
+
+        // Third reorg evicts the first one
+        hard_chain.append_block(D_prime.clone()).unwrap();
+        hard_chain.append_block(E_prime.clone()).unwrap();
+
+        assert_eq!(hard_chain.canonical_tip(), E_prime);
+        assert_eq!(hard_chain.recent_reorgs().len(), 2);
+        assert_eq!(hard_chain.recent_reorgs()[0].added, hashes(&[&B, &C, &D]));
+        assert_eq!(hard_chain.recent_reorgs()[1].removed, hashes(&[&B, &C, &D]));
+        assert_eq!(
+            hard_chain.recent_reorgs()[1].added,
+            hashes(&[&B_prime, &C_prime, &D_prime, &E_prime])
+        );
+    }
+
+    #[test]
+    fn it_switches_to_the_fork_with_most_work() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let D = Arc::new(DummyBlock::new(Some(C.block_hash().unwrap()), 4));
+
+        let mut B_prime = DummyBlock::new(Some(A.block_hash().unwrap()), 2);
+        B_prime.difficulty = 2;
+        let B_prime = Arc::new(B_prime);
+
+        let mut C_prime = DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3);
+        C_prime.difficulty = 2;
+        let C_prime = Arc::new(C_prime);
+
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(B.clone()).unwrap();
+        hard_chain.append_block(C.clone()).unwrap();
+        hard_chain.append_block(D.clone()).unwrap();
+
+        assert_eq!(hard_chain.total_work(), 4);
+
+        // Same work as the canonical chain
+        hard_chain.append_block(B_prime.clone()).unwrap();
+
+        assert_eq!(hard_chain.canonical_tip(), D);
+        assert_eq!(hard_chain.total_work(), 4);
+
+        // More work than the canonical chain
+        hard_chain.append_block(C_prime.clone()).unwrap();
+
+        assert_eq!(hard_chain.height(), 3);
+        assert_eq!(hard_chain.canonical_tip(), C_prime);
+        assert_eq!(hard_chain.total_work(), 5);
+        assert!(hard_chain.valid_tips.contains(&D.block_hash().unwrap()));
+
+        // Rewinding subtracts the work of the removed blocks
+        hard_chain.rewind(&A.block_hash().unwrap()).unwrap();
+
+        assert_eq!(hard_chain.total_work(), 1);
+    }
+
+    #[test]
+    fn it_verifies_chain_contiguity() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let D = Arc::new(DummyBlock::new(Some(C.block_hash().unwrap()), 4));
+
+        assert_eq!(hard_chain.verify_chain_contiguity(), Ok(()));
+
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(B.clone()).unwrap();
+        hard_chain.append_block(C.clone()).unwrap();
+        hard_chain.append_block(D.clone()).unwrap();
+
+        assert_eq!(hard_chain.verify_chain_contiguity(), Ok(()));
+
+        // Corrupt the parent hash of C
+        let mut corrupted = (*C).clone();
+        corrupted.parent_hash = crypto::hash_slice(b"corrupted");
+
+        hard_chain.db.emplace(
+            C.block_hash().unwrap(),
+            ElasticArray128::<u8>::from_slice(&corrupted.to_bytes()),
+        );
+
+        assert_eq!(
+            hard_chain.verify_chain_contiguity(),
+            Err((3, C.block_hash().unwrap()))
+        );
+
+        // Corrupt the height of B
+        let mut corrupted = (*B).clone();
+        corrupted.height = 5;
+
+        hard_chain.db.emplace(
+            C.block_hash().unwrap(),
+            ElasticArray128::<u8>::from_slice(&C.to_bytes()),
+        );
+        hard_chain.db.emplace(
+            B.block_hash().unwrap(),
+            ElasticArray128::<u8>::from_slice(&corrupted.to_bytes()),
+        );
+
+        assert_eq!(
+            hard_chain.verify_chain_contiguity(),
+            Err((5, B.block_hash().unwrap()))
+        );
+    }
+
+    #[test]
+    fn it_verifies_height() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+
+        assert_eq!(hard_chain.tip_distance_to_genesis(), 0);
+        assert_eq!(hard_chain.verify_height(), Ok(()));
+
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(B.clone()).unwrap();
+        hard_chain.append_block(C.clone()).unwrap();
+
+        assert_eq!(hard_chain.tip_distance_to_genesis(), 3);
+        assert_eq!(hard_chain.verify_height(), Ok(()));
+
+        // Simulate an incomplete restoration
+        hard_chain.db.remove(&A.block_hash().unwrap());
+
+        assert_eq!(hard_chain.tip_distance_to_genesis(), 2);
+        assert_eq!(
+            hard_chain.verify_height(),
+            Err(ChainErr::HeightMismatch {
+                stored: 3,
+                actual: 2
+            })
+        );
+    }
+
+    #[test]
+    fn it_rejects_blocks_that_are_too_large() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let mut A = DummyBlock::new(Some(Hash::NULL), 1);
+        A.padding = DEFAULT_MAX_BLOCK_SIZE - A.size_bytes();
+        let A = Arc::new(A);
+
+        let mut B = DummyBlock::new(Some(A.block_hash().unwrap()), 2);
+        B.padding = DEFAULT_MAX_BLOCK_SIZE - B.size_bytes() + 1;
+        let B = Arc::new(B);
+
+        assert_eq!(A.size_bytes(), DEFAULT_MAX_BLOCK_SIZE);
+        assert_eq!(B.size_bytes(), DEFAULT_MAX_BLOCK_SIZE + 1);
+        assert_eq!(hard_chain.append_block(A.clone()), Ok(()));
+        assert_eq!(hard_chain.append_block(B), Err(ChainErr::BlockTooLarge));
+        assert_eq!(hard_chain.canonical_tip(), A);
+    }
+
+    #[test]
+    fn it_rejects_blocks_larger_than_the_configured_size() {
+        let db = test_helpers::init_tempdb();
+        let config = ChainConfig {
+            max_block_size: 1024,
+            ..ChainConfig::default()
+        };
+        let mut hard_chain = Chain::<DummyBlock>::with_config(db, DummyBlock::genesis(), config);
+
+        let mut A = DummyBlock::new(Some(Hash::NULL), 1);
+        A.padding = 1024 - A.size_bytes();
+        let A = Arc::new(A);
+
+        let mut B = DummyBlock::new(Some(A.block_hash().unwrap()), 2);
+        B.padding = 1024 - B.size_bytes() + 1;
+        let B = Arc::new(B);
+
+        assert_eq!(hard_chain.append_block(A.clone()), Ok(()));
+        assert_eq!(
+            hard_chain.write_block_raw(B.clone()),
+            Err(ChainErr::BlockTooLarge)
+        );
+        assert_eq!(hard_chain.append_block(B), Err(ChainErr::BlockTooLarge));
+        assert_eq!(hard_chain.canonical_tip(), A);
+    }
+
+    #[test]
+    fn it_returns_orphan_ancestry() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let D = Arc::new(DummyBlock::new(Some(C.block_hash().unwrap()), 4));
+        let E = Arc::new(DummyBlock::new(Some(D.block_hash().unwrap()), 5));
+
+        // Shorter fork of C
+        let F = Arc::new(DummyBlock::new(Some(C.block_hash().unwrap()), 4));
+
+        hard_chain.append_block(B.clone()).unwrap();
+        hard_chain.append_block(C.clone()).unwrap();
+        hard_chain.append_block(D.clone()).unwrap();
+        hard_chain.append_block(E.clone()).unwrap();
+        hard_chain.append_block(F.clone()).unwrap();
+
+        assert_eq!(hard_chain.height(), 0);
+        assert_eq!(
+            hard_chain.get_orphan_ancestry(&B.block_hash().unwrap()),
+            vec![B.clone(), C.clone(), D.clone(), E.clone()]
+        );
+        assert_eq!(
+            hard_chain.get_orphan_ancestry(&D.block_hash().unwrap()),
+            vec![D, E]
+        );
+        assert!(hard_chain
+            .get_orphan_ancestry(&A.block_hash().unwrap())
+            .is_empty());
+    }
+
+    #[test]
+    fn it_keeps_children_index_consistent() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let D = Arc::new(DummyBlock::new(Some(C.block_hash().unwrap()), 4));
+        let E = Arc::new(DummyBlock::new(Some(D.block_hash().unwrap()), 5));
+        let F = Arc::new(DummyBlock::new(Some(E.block_hash().unwrap()), 6));
+        let G = Arc::new(DummyBlock::new(Some(F.block_hash().unwrap()), 7));
+        let H = Arc::new(DummyBlock::new(Some(G.block_hash().unwrap()), 8));
+
+        // Valid fork of A
+        let X2 = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let X3 = Arc::new(DummyBlock::new(Some(X2.block_hash().unwrap()), 3));
+        let X4 = Arc::new(DummyBlock::new(Some(X3.block_hash().unwrap()), 4));
+
+        // Disconnected chain
+        let mut Y = vec![Arc::new(DummyBlock::new(
+            Some(crypto::hash_slice(b"missing")),
+            10,
+        ))];
+
+        for height in 11..15 {
+            let parent_hash = Y.last().unwrap().block_hash().unwrap();
+            Y.push(Arc::new(DummyBlock::new(Some(parent_hash), height)));
+        }
+
+        for block in [&A, &B, &C, &D, &E].iter() {
+            hard_chain.append_block((*block).clone()).unwrap();
+        }
+
+        // Append 10 orphans
+        for block in [&G, &H, &X2, &X3, &X4].iter().cloned().chain(Y.iter()) {
+            hard_chain.append_block(block.clone()).unwrap();
+        }
+
+        let assert_consistent = |chain: &Chain<DummyBlock>| {
+            let mut expected: HashMap<Hash, Vec<Hash>> = HashMap::new();
+
+            for orphan in chain.orphan_pool.values() {
+                expected
+                    .entry(orphan.parent_hash().unwrap())
+                    .or_insert_with(Vec::new)
+                    .push(orphan.block_hash().unwrap());
+            }
+
+            let mut actual = chain.children_index.clone();
+
+            for children in expected.values_mut().chain(actual.values_mut()) {
+                children.sort();
+            }
+
+            assert_eq!(actual, expected);
+        };
+
+        assert_eq!(hard_chain.orphan_pool.len(), 10);
+        assert_eq!(
+            hard_chain.block_children(&F.block_hash().unwrap()),
+            vec![G.clone()]
+        );
+        assert_eq!(
+            hard_chain.block_children(&X2.block_hash().unwrap()),
+            vec![X3.clone()]
+        );
+        assert_consistent(&hard_chain);
+
+        // Promote G and H to the canonical chain
+        hard_chain.append_block(F.clone()).unwrap();
+
+        assert_eq!(hard_chain.height(), 8);
+        assert_eq!(hard_chain.orphan_pool.len(), 8);
+        assert!(hard_chain
+            .block_children(&F.block_hash().unwrap())
+            .is_empty());
+        assert!(hard_chain
+            .block_children(&G.block_hash().unwrap())
+            .is_empty());
+        assert_consistent(&hard_chain);
+
+        // Prune the fork of A
+        hard_chain
+            .mark_tip_as_invalid(&X4.block_hash().unwrap())
+            .unwrap();
+
+        assert_eq!(hard_chain.orphan_pool.len(), 5);
+        assert!(hard_chain
+            .block_children(&A.block_hash().unwrap())
+            .is_empty());
+        assert!(hard_chain
+            .block_children(&X2.block_hash().unwrap())
+            .is_empty());
+        assert_eq!(
+            hard_chain.block_children(&Y[0].block_hash().unwrap()),
+            vec![Y[1].clone()]
+        );
+        assert_consistent(&hard_chain);
+    }
+
+    #[test]
+    fn it_returns_orphan_stats_per_segment() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let D = Arc::new(DummyBlock::new(Some(C.block_hash().unwrap()), 4));
+
+        let P = Arc::new(DummyBlock::new(Some(crypto::hash_slice(b"missing")), 5));
+        let Q = Arc::new(DummyBlock::new(Some(P.block_hash().unwrap()), 6));
+        let R = Arc::new(DummyBlock::new(Some(P.block_hash().unwrap()), 6));
+
+        // Two disjoint disconnected segments
+        hard_chain.append_block(B.clone()).unwrap();
+        hard_chain.append_block(C.clone()).unwrap();
+        hard_chain.append_block(D.clone()).unwrap();
+        hard_chain.append_block(P.clone()).unwrap();
+        hard_chain.append_block(Q.clone()).unwrap();
+        hard_chain.append_block(R.clone()).unwrap();
+
+        let stats = hard_chain.orphan_stats_per_segment();
+        assert_eq!(stats.len(), 2);
+
+        let first = stats
+            .iter()
+            .find(|s| s.head_hash == B.block_hash().unwrap())
+            .unwrap();
+        let second = stats
+            .iter()
+            .find(|s| s.head_hash == P.block_hash().unwrap())
+            .unwrap();
+
+        assert_eq!(first.tip_count, 1);
+        assert_eq!(first.depth, 3);
+        assert_eq!(first.max_height, 4);
+        assert_eq!(second.tip_count, 2);
+        assert_eq!(second.depth, 2);
+        assert_eq!(second.max_height, 6);
+    }
+
+    #[test]
+    fn it_stores_cross_chain_blocks_separately() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+        hard_chain.set_chain_id(1);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let mut B = DummyBlock::new(Some(crypto::hash_slice(b"foreign")), 1);
+        B.parent_chain_id = Some(2);
+        let B = Arc::new(B);
+        let mut C = DummyBlock::new(Some(A.block_hash().unwrap()), 2);
+        C.parent_chain_id = Some(1);
+        let C = Arc::new(C);
+
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(B.clone()).unwrap();
+
+        assert_eq!(hard_chain.cross_chain_orphan_count(2), 1);
+        assert_eq!(hard_chain.cross_chain_orphan_count(1), 0);
+        assert!(hard_chain.orphan_pool.is_empty());
+        assert_eq!(hard_chain.height(), 1);
+        assert_eq!(
+            hard_chain.append_block(B.clone()),
+            Err(ChainErr::AlreadyInChain)
+        );
+
+        // Blocks referencing the local chain are appended normally
+        hard_chain.append_block(C.clone()).unwrap();
+
+        assert_eq!(hard_chain.height(), 2);
+        assert_eq!(hard_chain.cross_chain_orphan_count(1), 0);
+    }
+
+    #[test]
+    fn it_limits_cross_chain_orphans_across_chain_ids() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+        hard_chain.set_chain_id(1);
+
+        let cross_chain_block = |parent_chain_id, height| {
+            let mut block = DummyBlock::new(Some(crypto::hash_slice(b"foreign")), height);
+            block.parent_chain_id = Some(parent_chain_id);
+            Arc::new(block)
+        };
+
+        for i in 0..MAX_CROSS_CHAIN_ORPHANS {
+            let parent_chain_id = 2 + (i as u64 % 10);
+            let height = 1 + (i as u64 / 10);
+
+            hard_chain
+                .append_block(cross_chain_block(parent_chain_id, height))
+                .unwrap();
+        }
+
+        for parent_chain_id in 2..12 {
+            assert_eq!(hard_chain.cross_chain_orphan_count(parent_chain_id), 10);
+        }
+
+        // The limit holds for known and for new chain ids
+        assert_eq!(
+            hard_chain.append_block(cross_chain_block(2, 1)),
+            Err(ChainErr::TooManyOrphans)
+        );
+        assert_eq!(
+            hard_chain.append_block(cross_chain_block(12, 1)),
+            Err(ChainErr::TooManyOrphans)
+        );
+        assert_eq!(hard_chain.cross_chain_orphans.len(), 10);
+        assert_eq!(hard_chain.cross_chain_orphan_count(12), 0);
+
+        let storage_with_orphans = hard_chain.estimate_storage_bytes();
+        let mut parent = Hash::NULL;
+
+        for height in 1..=(MIN_HEIGHT + 11) {
+            let block = Arc::new(DummyBlock::new(Some(parent), height));
+            parent = block.block_hash().unwrap();
+            hard_chain.append_block(block).unwrap();
+        }
+
+        // Cross-chain orphans below the minimum height are pruned
+        assert!(hard_chain.cross_chain_orphans.is_empty());
+        assert!(hard_chain.estimate_storage_bytes() < storage_with_orphans);
+
+        hard_chain
+            .append_block(cross_chain_block(12, hard_chain.height()))
+            .unwrap();
+        assert_eq!(hard_chain.cross_chain_orphan_count(12), 1);
+    }
+
+    #[test]
+    fn it_does_not_check_cross_chain_blocks_against_the_median_time_past() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+        hard_chain.set_chain_id(1);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(B.clone()).unwrap();
+        hard_chain.append_block(C.clone()).unwrap();
+
+        // Older than the median time past of C
+        let D = Arc::new(DummyBlock::with_timestamp(
+            Some(C.block_hash().unwrap()),
+            4,
+            Utc.timestamp(0, 0),
+        ));
+        let mut D_foreign =
+            DummyBlock::with_timestamp(Some(C.block_hash().unwrap()), 4, Utc.timestamp(0, 0));
+        D_foreign.parent_chain_id = Some(2);
+        let D_foreign = Arc::new(D_foreign);
+
+        assert_eq!(
+            hard_chain.append_block(D.clone()),
+            Err(ChainErr::InvalidTimestamp)
+        );
+        hard_chain.append_block(D_foreign.clone()).unwrap();
+        assert_eq!(hard_chain.cross_chain_orphan_count(2), 1);
+    }
+
+    #[test]
+    fn it_compacts_db() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let D = Arc::new(DummyBlock::new(Some(C.block_hash().unwrap()), 4));
+
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(B.clone()).unwrap();
+        hard_chain.append_block(C.clone()).unwrap();
+        hard_chain.append_block(D.clone()).unwrap();
+        hard_chain.rewind(&B.block_hash().unwrap()).unwrap();
+
+        // Simulate entries left behind by a rewind
+        for block in [&C, &D].iter() {
+            hard_chain.db.emplace(
+                block.block_hash().unwrap(),
+                ElasticArray128::<u8>::from_slice(&block.to_bytes()),
+            );
+        }
+
+        // Simulate an orphan left behind by a prune
+        hard_chain
+            .validations_mapping
+            .remove(&D.block_hash().unwrap());
+
+        let stored_orphans = |chain: &Chain<DummyBlock>| {
+            [&C, &D]
+                .iter()
+                .filter(|b| chain.db.get(&b.block_hash().unwrap()).is_some())
+                .count()
+        };
+
+        assert_eq!(stored_orphans(&hard_chain), 2);
+        hard_chain.compact_db();
+        assert_eq!(stored_orphans(&hard_chain), 0);
+
+        assert_eq!(hard_chain.height(), 2);
+        assert!(hard_chain.query(&B.block_hash().unwrap()).is_some());
+        assert!(hard_chain
+            .orphan_pool
+            .get(&C.block_hash().unwrap())
+            .is_some());
+        assert!(hard_chain
+            .orphan_pool
+            .get(&D.block_hash().unwrap())
+            .is_none());
+        assert!(hard_chain
+            .block_children(&C.block_hash().unwrap())
+            .is_empty());
+        assert_eq!(hard_chain.max_orphan_height, Some(3));
+    }
+
+    #[test]
+    fn it_estimates_sync_window() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let D = Arc::new(DummyBlock::new(Some(C.block_hash().unwrap()), 4));
+        let E = Arc::new(DummyBlock::new(Some(D.block_hash().unwrap()), 5));
+
+        assert_eq!(hard_chain.estimate_sync_window(), (1, 1));
+
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(D.clone()).unwrap();
+        hard_chain.append_block(E.clone()).unwrap();
+
+        assert_eq!(hard_chain.estimate_sync_window(), (2, 3));
+
+        hard_chain.append_block(B.clone()).unwrap();
+
+        assert_eq!(hard_chain.estimate_sync_window(), (3, 3));
+
+        hard_chain.append_block(C.clone()).unwrap();
+
+        assert_eq!(hard_chain.height(), 5);
+        assert_eq!(hard_chain.estimate_sync_window(), (6, 6));
+    }
+
+    #[test]
+    fn it_returns_merkle_path_to_canonical() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let D = Arc::new(DummyBlock::new(Some(C.block_hash().unwrap()), 4));
+
+        // Valid fork of A
+        let X2 = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let X3 = Arc::new(DummyBlock::new(Some(X2.block_hash().unwrap()), 3));
+
+        // Disconnected block
+        let Y = Arc::new(DummyBlock::new(Some(crypto::hash_slice(b"missing")), 5));
+
+        for block in [&A, &B, &C, &D, &X2, &X3, &Y].iter() {
+            hard_chain.append_block((*block).clone()).unwrap();
+        }
+
+        let X3_hash = X3.block_hash().unwrap();
+        let A_hash = A.block_hash().unwrap();
+        let proof = hard_chain.merkle_path_to_canonical(&X3_hash).unwrap();
+
+        assert_eq!(
+            proof.block_hashes,
+            vec![X3_hash.clone(), X2.block_hash().unwrap(), A_hash.clone()]
+        );
+        assert!(verify_merkle_proof::<DummyBlock>(&proof, &X3_hash, &A_hash));
+        assert!(!verify_merkle_proof::<DummyBlock>(
+            &proof,
+            &X3_hash,
+            &B.block_hash().unwrap()
+        ));
+
+        // Tampered proof
+        let mut tampered = proof.clone();
+        tampered.block_hashes[1] = B.block_hash().unwrap();
+        assert!(!verify_merkle_proof::<DummyBlock>(
+            &tampered, &X3_hash, &A_hash
+        ));
+
+        // Forged proof with recomputed sibling hashes
+        let B_hash = B.block_hash().unwrap();
+        let running_hash = merkle_node(&X3_hash, &B_hash);
+        let forged = MerkleProof {
+            block_hashes: vec![X3_hash.clone(), B_hash.clone(), A_hash.clone()],
+            sibling_hashes: vec![running_hash.clone(), merkle_node(&running_hash, &A_hash)],
+            headers: vec![X3.to_bytes(), B.to_bytes()],
+        };
+        assert!(!verify_merkle_proof::<DummyBlock>(
+            &forged, &X3_hash, &A_hash
+        ));
+
+        assert!(hard_chain
+            .merkle_path_to_canonical(&Y.block_hash().unwrap())
+            .is_none());
+        assert!(hard_chain
+            .merkle_path_to_canonical(&D.block_hash().unwrap())
+            .is_none());
+    }
+
+    #[test]
+    fn it_rejects_merkle_proofs_with_forged_headers() {
+        let easy_block_hash = EasyBlock::genesis().block_hash().unwrap();
+        let genesis_hash = HardBlock::genesis().block_hash().unwrap();
+        let new_block = |parent_hash: &Hash, height| {
+            let mut block =
+                HardBlock::new(Some(parent_hash.clone()), height, easy_block_hash.clone());
+            block.calculate_merkle_root();
+            block.compute_hash();
+            block
+        };
+        let forge_proof = |block_hashes: Vec<Hash>, headers: Vec<Vec<u8>>| {
+            let mut running_hash = block_hashes[0].clone();
+            let sibling_hashes = block_hashes[1..]
+                .iter()
+                .map(|block_hash| {
+                    running_hash = merkle_node(&running_hash, block_hash);
+                    running_hash.clone()
+                })
+                .collect();
+
+            MerkleProof {
+                block_hashes,
+                sibling_hashes,
+                headers,
+            }
+        };
+
+        let A = new_block(&genesis_hash, 1);
+        let X = new_block(&genesis_hash, 1);
+        let B = new_block(&A.block_hash().unwrap(), 2);
+        let A_hash = A.block_hash().unwrap();
+        let X_hash = X.block_hash().unwrap();
+        let B_hash = B.block_hash().unwrap();
+
+        let proof = forge_proof(
+            vec![B_hash.clone(), A_hash.clone(), genesis_hash.clone()],
+            vec![B.to_bytes(), A.to_bytes()],
+        );
+        assert!(verify_merkle_proof::<HardBlock>(
+            &proof,
+            &B_hash,
+            &genesis_hash
+        ));
+
+        // The header of B does not reference X
+        let proof = forge_proof(
+            vec![B_hash.clone(), X_hash.clone(), genesis_hash.clone()],
+            vec![B.to_bytes(), X.to_bytes()],
+        );
+        assert!(!verify_merkle_proof::<HardBlock>(
+            &proof,
+            &B_hash,
+            &genesis_hash
+        ));
+
+        // Rewriting the parent of B no longer matches its hash
+        let mut forged_header = B.to_bytes();
+        forged_header[73..105].copy_from_slice(&X_hash.0);
+        assert_eq!(
+            HardBlock::from_bytes(&forged_header).unwrap().parent_hash(),
+            Some(X_hash.clone())
+        );
+
+        let proof = forge_proof(
+            vec![B_hash.clone(), X_hash.clone(), genesis_hash.clone()],
+            vec![forged_header, X.to_bytes()],
+        );
+        assert!(!verify_merkle_proof::<HardBlock>(
+            &proof,
+            &B_hash,
+            &genesis_hash
+        ));
+    }
+
+    #[test]
+    fn it_returns_top_n_valid_tips_by_height() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let D = Arc::new(DummyBlock::new(Some(C.block_hash().unwrap()), 4));
+        let E = Arc::new(DummyBlock::new(Some(D.block_hash().unwrap()), 5));
+
+        // Valid tips at different heights
+        let X = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let Y = Arc::new(DummyBlock::new(Some(C.block_hash().unwrap()), 4));
+        let Z = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+
+        for block in [&A, &B, &C, &D, &E, &X, &Y, &Z].iter() {
+            hard_chain.append_block((*block).clone()).unwrap();
+        }
+
+        assert_eq!(hard_chain.canonical_tip(), E);
+        assert_eq!(
+            hard_chain.top_n_valid_tips_by_height(10),
+            vec![
+                (Y.block_hash().unwrap(), 4),
+                (Z.block_hash().unwrap(), 3),
+                (X.block_hash().unwrap(), 2),
+            ]
+        );
+        assert_eq!(
+            hard_chain.top_n_valid_tips_by_height(2),
+            vec![(Y.block_hash().unwrap(), 4), (Z.block_hash().unwrap(), 3)]
+        );
+        assert!(hard_chain.top_n_valid_tips_by_height(0).is_empty());
+    }
+
+    #[test]
+    fn it_checks_if_orphan_references_canonical_parent() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let X = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let Y = Arc::new(DummyBlock::new(Some(X.block_hash().unwrap()), 3));
+        let Z = Arc::new(DummyBlock::new(Some(crypto::hash_slice(b"missing")), 4));
+
+        for block in [&A, &B, &C, &X, &Y, &Z].iter() {
+            hard_chain.append_block((*block).clone()).unwrap();
+        }
+
+        assert_eq!(
+            hard_chain.orphan_references_canonical_parent(&X.block_hash().unwrap()),
+            Some(true)
+        );
+        assert_eq!(
+            hard_chain.orphan_references_canonical_parent(&Y.block_hash().unwrap()),
+            Some(false)
+        );
+        assert_eq!(
+            hard_chain.orphan_references_canonical_parent(&Z.block_hash().unwrap()),
+            Some(false)
+        );
+        assert_eq!(
+            hard_chain.orphan_references_canonical_parent(&B.block_hash().unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn it_flushes_orphan_processing_after_raw_writes() {
+        let db1 = test_helpers::init_tempdb();
+        let db2 = test_helpers::init_tempdb();
+        let mut raw_chain = Chain::<DummyBlock>::new(db1);
+        let mut hard_chain = Chain::<DummyBlock>::new(db2);
+
+        let mut blocks = vec![Arc::new(DummyBlock::new(Some(Hash::NULL), 1))];
+
+        for height in 2..12 {
+            let parent_hash = blocks.last().unwrap().block_hash().unwrap();
+            blocks.push(Arc::new(DummyBlock::new(Some(parent_hash), height)));
+        }
+
+        let orphan = blocks.pop().unwrap();
+
+        raw_chain.write_block_raw(blocks[0].clone()).unwrap();
+        raw_chain.append_block(orphan.clone()).unwrap();
+        hard_chain.append_block(blocks[0].clone()).unwrap();
+        hard_chain.append_block(orphan.clone()).unwrap();
+
+        for block in blocks[1..].iter() {
+            raw_chain.write_block_raw(block.clone()).unwrap();
+            hard_chain.append_block(block.clone()).unwrap();
+        }
+
+        // The orphan is not processed by raw writes
+        assert_eq!(raw_chain.height(), 10);
+        assert!(raw_chain
+            .orphan_pool
+            .contains_key(&orphan.block_hash().unwrap()));
+
+        raw_chain.flush_orphan_processing();
+
+        assert_eq!(raw_chain.height(), 11);
+        assert_eq!(raw_chain.height(), hard_chain.height());
+        assert_eq!(raw_chain.canonical_tip(), hard_chain.canonical_tip());
+        assert_eq!(raw_chain.total_work(), hard_chain.total_work());
+        assert!(raw_chain.orphan_pool.is_empty());
+        assert!(hard_chain.orphan_pool.is_empty());
+        assert_eq!(raw_chain.max_orphan_height, hard_chain.max_orphan_height);
+        assert_eq!(
+            raw_chain.write_block_raw(orphan),
+            Err(ChainErr::AlreadyInChain)
+        );
+    }
+
+    #[test]
+    fn it_batch_appends_blocks() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let mut blocks = vec![Arc::new(DummyBlock::new(Some(Hash::NULL), 1))];
+
+        for height in 2..11 {
+            let parent_hash = blocks.last().unwrap().block_hash().unwrap();
+            blocks.push(Arc::new(DummyBlock::new(Some(parent_hash), height)));
+        }
+
+        // Out of order blocks are added to the orphan pool
+        blocks.swap(3, 4);
+
+        assert_eq!(hard_chain.batch_append(blocks.clone()), Ok(()));
+        assert_eq!(hard_chain.height(), 10);
+        assert_eq!(hard_chain.canonical_tip(), blocks[9]);
+        assert!(hard_chain.orphan_pool.is_empty());
+    }
+
+    #[test]
+    fn it_compacts_heights_mapping() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let mut blocks = vec![Arc::new(DummyBlock::new(Some(Hash::NULL), 1))];
+
+        for height in 2..8 {
+            let parent_hash = blocks.last().unwrap().block_hash().unwrap();
+            blocks.push(Arc::new(DummyBlock::new(Some(parent_hash), height)));
+        }
+
+        // Append in reverse order so that all blocks but the first are orphans
+        for block in blocks.iter().rev() {
+            hard_chain.append_block(block.clone()).unwrap();
+        }
+
+        assert_eq!(hard_chain.height(), 7);
+        assert!(hard_chain.orphan_pool.is_empty());
+        assert!(hard_chain.heights_mapping.values().all(|o| o.is_empty()));
+        assert!(!hard_chain.heights_mapping.is_empty());
+
+        hard_chain.compact_heights_mapping();
+
+        assert_eq!(hard_chain.heights_mapping.len(), 0);
+    }
+
+    #[test]
+    fn it_computes_block_age() {
+        let db = test_helpers::init_tempdb();
+        let hard_chain = Chain::<DummyBlock>::new(db);
+        let now = Utc::now();
+
+        let A = DummyBlock::with_timestamp(Some(Hash::NULL), 1, now);
+        let B = DummyBlock::with_timestamp(
+            Some(A.block_hash().unwrap()),
+            2,
+            now + chrono::Duration::seconds(30),
+        );
+
+        assert_eq!(hard_chain.genesis_timestamp, Utc.timestamp(0, 0));
+        assert_eq!(hard_chain.block_age(&A), now - Utc.timestamp(0, 0));
+        assert_eq!(
+            hard_chain.block_age(&B) - hard_chain.block_age(&A),
+            chrono::Duration::seconds(30)
+        );
+        assert_eq!(
+            hard_chain.block_age(&DummyBlock::genesis()),
+            chrono::Duration::zero()
+        );
+    }
+
+    #[test]
+    fn it_writes_blocks_unchecked() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        // Replay a fork whose ancestors are not in the ledger
+        let A = Arc::new(DummyBlock::new(Some(crypto::hash_slice(b"snapshot")), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+
+        hard_chain.write_block_unchecked(A.clone()).unwrap();
+        hard_chain.write_block_unchecked(B.clone()).unwrap();
+
+        assert_eq!(hard_chain.height(), 2);
+        assert_eq!(hard_chain.canonical_tip(), B);
+        assert_eq!(hard_chain.query(&A.block_hash().unwrap()), Some(A));
+
+        // Blocks following the replayed tip are appended normally
+        hard_chain.append_block(C.clone()).unwrap();
+
+        assert_eq!(hard_chain.height(), 3);
+        assert_eq!(hard_chain.canonical_tip(), C);
+    }
+
+    #[test]
+    fn it_returns_known_heights() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let X = Arc::new(DummyBlock::new(Some(crypto::hash_slice(b"missing_1")), 5));
+        let Y = Arc::new(DummyBlock::new(Some(crypto::hash_slice(b"missing_2")), 7));
+
+        for block in [&A, &B, &C, &X, &Y].iter() {
+            hard_chain.append_block((*block).clone()).unwrap();
+        }
+
+        assert_eq!(hard_chain.height(), 3);
+        assert_eq!(
+            hard_chain.known_heights().into_iter().collect::<Vec<u64>>(),
+            vec![1, 2, 3, 5, 7]
+        );
+    }
+
+    #[test]
+    fn it_checks_if_blocks_are_reachable_from_genesis() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let X = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let Y = Arc::new(DummyBlock::new(Some(X.block_hash().unwrap()), 3));
+        let Z = Arc::new(DummyBlock::new(Some(crypto::hash_slice(b"missing")), 4));
+
+        for block in [&A, &B, &C, &X, &Y, &Z].iter() {
+            hard_chain.append_block((*block).clone()).unwrap();
+        }
+
+        assert_eq!(hard_chain.canonical_tip(), C);
+
+        for block in [&A, &B, &C, &X, &Y].iter() {
+            assert!(hard_chain.reachable_from_genesis(&block.block_hash().unwrap()));
+        }
+
+        assert!(!hard_chain.reachable_from_genesis(&Z.block_hash().unwrap()));
+        assert!(!hard_chain.reachable_from_genesis(&crypto::hash_slice(b"missing")));
+    }
+
+    #[test]
+    fn it_enumerates_orphan_chains() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(crypto::hash_slice(b"missing_1")), 4));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 5));
+        let D = Arc::new(DummyBlock::new(Some(C.block_hash().unwrap()), 6));
+        let X = Arc::new(DummyBlock::new(Some(crypto::hash_slice(b"missing_2")), 7));
+        let Y = Arc::new(DummyBlock::new(Some(X.block_hash().unwrap()), 8));
+        let Z = Arc::new(DummyBlock::new(Some(Y.block_hash().unwrap()), 9));
+
+        for block in [&A, &D, &C, &B, &Y, &Z, &X].iter() {
+            hard_chain.append_block((*block).clone()).unwrap();
+        }
+
+        assert_eq!(
+            hard_chain.enumerate_orphan_chains(),
+            vec![vec![B, C, D], vec![X, Y, Z]]
+        );
+    }
+
+    #[test]
+    fn it_estimates_storage_bytes() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+
+        hard_chain.append_block(A).unwrap();
+
+        let initial = hard_chain.estimate_storage_bytes();
+
+        hard_chain.append_block(C).unwrap();
+
+        let with_orphan = hard_chain.estimate_storage_bytes();
+        assert!(with_orphan > initial);
+
+        // Promotes C
+        hard_chain.append_block(B).unwrap();
+
+        assert_eq!(hard_chain.height(), 3);
+        assert!(hard_chain.estimate_storage_bytes() < with_orphan);
+    }
+
+    #[test]
+    fn it_verifies_heights_mapping_consistency() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let D = Arc::new(DummyBlock::new(Some(C.block_hash().unwrap()), 4));
+
+        for block in [&A, &C, &D].iter() {
+            hard_chain.append_block((*block).clone()).unwrap();
+        }
+
+        assert!(hard_chain.verify_heights_mapping_consistency().is_empty());
+
+        // Remove the entry of an orphan
+        hard_chain
+            .heights_mapping
+            .get_mut(&3)
+            .unwrap()
+            .remove(&C.block_hash().unwrap());
+
+        assert_eq!(hard_chain.verify_heights_mapping_consistency().len(), 1);
+
+        // Map an orphan to the wrong height
+        hard_chain
+            .heights_mapping
+            .get_mut(&4)
+            .unwrap()
+            .insert(C.block_hash().unwrap(), 0);
+
+        assert_eq!(hard_chain.verify_heights_mapping_consistency().len(), 1);
+
+        // Map a block which is not an orphan
+        hard_chain
+            .heights_mapping
+            .get_mut(&4)
+            .unwrap()
+            .insert(B.block_hash().unwrap(), 0);
+
+        assert_eq!(hard_chain.verify_heights_mapping_consistency().len(), 2);
+    }
+
+    #[test]
+    fn it_does_not_merge_chains_with_different_genesis() {
+        let db1 = test_helpers::init_tempdb();
+        let db2 = test_helpers::init_tempdb();
+        let genesis = Arc::new(DummyBlock::new(Some(Hash::NULL), 0));
+        let mut default_chain = Chain::<DummyBlock>::new(db1);
+        let mut custom_chain = Chain::<DummyBlock>::with_genesis(db2, genesis.clone());
+
+        let A = Arc::new(DummyBlock::new(Some(genesis.block_hash().unwrap()), 1));
+
+        assert_eq!(custom_chain.canonical_tip(), genesis);
+        assert_eq!(custom_chain.query_by_height(0), Some(genesis));
+
+        custom_chain.append_block(A.clone()).unwrap();
+
+        assert_eq!(custom_chain.height(), 1);
+        assert_eq!(custom_chain.canonical_tip(), A);
+        assert!(custom_chain.reachable_from_genesis(&A.block_hash().unwrap()));
+
+        // The parent of `A` is not the genesis of the default chain
+        default_chain.append_block(A.clone()).unwrap();
+
+        assert_eq!(default_chain.height(), 0);
+        assert_eq!(default_chain.canonical_tip(), DummyBlock::genesis());
+        assert!(!default_chain.reachable_from_genesis(&A.block_hash().unwrap()));
+    }
+
+    #[test]
+    fn it_queries_by_height_after_switch() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let B_prime = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C_prime = Arc::new(DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3));
+        let D_prime = Arc::new(DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4));
+
+        for block in [&A, &B, &C].iter() {
+            hard_chain.append_block((*block).clone()).unwrap();
+        }
+
+        assert_eq!(hard_chain.query_by_height(2), Some(B.clone()));
+        assert_eq!(hard_chain.query_by_height(3), Some(C.clone()));
+
+        for block in [&B_prime, &C_prime, &D_prime].iter() {
+            hard_chain.append_block((*block).clone()).unwrap();
+        }
+
+        assert_eq!(hard_chain.canonical_tip(), D_prime);
+        assert_eq!(hard_chain.query_by_height(0), Some(DummyBlock::genesis()));
+        assert_eq!(hard_chain.query_by_height(1), Some(A.clone()));
+        assert_eq!(hard_chain.query_by_height(2), Some(B_prime));
+        assert_eq!(hard_chain.query_by_height(3), Some(C_prime));
+        assert_eq!(hard_chain.query_by_height(4), Some(D_prime));
+        assert_eq!(hard_chain.query_by_height(5), None);
+
+        hard_chain.rewind(&A.block_hash().unwrap()).unwrap();
+
+        assert_eq!(hard_chain.query_by_height(1), Some(A));
+        assert_eq!(hard_chain.query_by_height(2), None);
+        assert!(hard_chain.db.get(&canonical_hash_key(2)).is_none());
+        assert!(hard_chain.db.get(&canonical_hash_key(4)).is_none());
+    }
+
+    #[test]
+    fn it_returns_block_heights_after_switch() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let B_prime = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C_prime = Arc::new(DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3));
+        let D_prime = Arc::new(DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4));
+
+        for block in [&A, &B, &C, &B_prime, &C_prime].iter() {
+            hard_chain.append_block((*block).clone()).unwrap();
+        }
+
+        assert_eq!(hard_chain.block_height(&Hash::NULL), Some(0));
+        assert_eq!(hard_chain.block_height(&B.block_hash().unwrap()), Some(2));
+        assert_eq!(
+            hard_chain.block_height(&B_prime.block_hash().unwrap()),
+            None
+        );
+
+        // Switch to the `B'` chain
+        hard_chain.append_block(D_prime.clone()).unwrap();
+
+        assert_eq!(hard_chain.canonical_tip(), D_prime);
+
+        for block in [&A, &B, &C, &B_prime, &C_prime, &D_prime].iter() {
+            let block_hash = block.block_hash().unwrap();
+            let expected = hard_chain.query(&block_hash).map(|b| b.height());
+
+            assert_eq!(hard_chain.block_height(&block_hash), expected);
+        }
+
+        assert_eq!(hard_chain.block_height(&B.block_hash().unwrap()), None);
+        assert_eq!(
+            hard_chain.block_height(&D_prime.block_hash().unwrap()),
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn it_reloads_consistent_state_after_batched_writes() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let B_prime = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C_prime = Arc::new(DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3));
+        let D_prime = Arc::new(DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4));
+
+        // A chain loaded from a snapshot of the ledger taken at
+        // a batch boundary must match the chain that wrote it.
+        let assert_reloads = |chain: &Chain<DummyBlock>| {
+            let reloaded = Chain::<DummyBlock>::new(chain.db.clone());
+
+            assert_eq!(reloaded.height(), chain.height());
+            assert_eq!(reloaded.canonical_tip(), chain.canonical_tip());
+            assert_eq!(reloaded.total_work(), chain.total_work());
+
+            for height in 0..=chain.height() + 1 {
+                assert_eq!(
+                    reloaded.query_by_height(height),
+                    chain.query_by_height(height)
+                );
+            }
+        };
+
+        assert_reloads(&hard_chain);
+
+        for block in [&A, &B, &C, &B_prime, &C_prime, &D_prime].iter() {
+            hard_chain.append_block((*block).clone()).unwrap();
+            assert_reloads(&hard_chain);
+        }
+
+        assert_eq!(hard_chain.canonical_tip(), D_prime);
+
+        hard_chain.rewind(&A.block_hash().unwrap()).unwrap();
+        assert_reloads(&hard_chain);
+
+        hard_chain.rewind(&Hash::NULL).unwrap();
+        assert_reloads(&hard_chain);
+        assert_eq!(hard_chain.height(), 0);
+    }
+
+    #[test]
+    fn it_evicts_orphans_when_the_pool_is_full() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        hard_chain.append_block(A.clone()).unwrap();
+
+        // The disconnected chain with the lowest tip
+        let X = Arc::new(DummyBlock::new(Some(crypto::hash_slice(b"missing")), 2));
+        let Y = Arc::new(DummyBlock::new(Some(X.block_hash().unwrap()), 3));
+
+        hard_chain.append_block(X.clone()).unwrap();
+        hard_chain.append_block(Y.clone()).unwrap();
+
+        for i in 0..MAX_ORPHANS - 2 {
+            let parent_hash = crypto::hash_slice(format!("missing-{}", i).as_bytes());
+            let height = 4 + (i as u64 % 8);
+
+            hard_chain
+                .append_block(Arc::new(DummyBlock::new(Some(parent_hash), height)))
+                .unwrap();
+        }
+
+        assert_eq!(hard_chain.orphan_pool.len(), MAX_ORPHANS);
+
+        let Z = Arc::new(DummyBlock::new(Some(crypto::hash_slice(b"missing_z")), 5));
+        hard_chain.append_block(Z.clone()).unwrap();
+
+        let X_hash = X.block_hash().unwrap();
+        let Y_hash = Y.block_hash().unwrap();
+
+        assert_eq!(hard_chain.orphan_pool.len(), MAX_ORPHANS - 1);
+        assert!(hard_chain
+            .orphan_pool
+            .contains_key(&Z.block_hash().unwrap()));
+
+        for hash in [&X_hash, &Y_hash].iter() {
+            assert!(!hard_chain.orphan_pool.contains_key(*hash));
+            assert!(!hard_chain.validations_mapping.contains_key(*hash));
+            assert!(!hard_chain.disconnected_tips_mapping.contains_key(*hash));
+            assert!(!hard_chain.children_index.contains_key(*hash));
+        }
+
+        assert!(!hard_chain.disconnected_heads_mapping.contains_key(&X_hash));
+        assert!(!hard_chain.disconnected_heads_heights.contains_key(&X_hash));
+        assert!(hard_chain.heights_mapping.get(&2).is_none());
+        assert!(hard_chain.heights_mapping.get(&3).is_none());
+        assert_eq!(hard_chain.max_orphan_height, Some(11));
+        assert!(hard_chain.verify_heights_mapping_consistency().is_empty());
+    }
+
+    #[test]
+    fn it_emits_chain_events() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+        let events = hard_chain.subscribe();
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let B_prime = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C_prime = Arc::new(DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3));
+        let D_prime = Arc::new(DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4));
+
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(B.clone()).unwrap();
+        hard_chain.append_block(C.clone()).unwrap();
+
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            vec![
+                ChainEvent::Connected(A.clone()),
+                ChainEvent::Connected(B.clone()),
+                ChainEvent::Connected(C.clone()),
+            ]
+        );
+
+        hard_chain.append_block(B_prime.clone()).unwrap();
+        hard_chain.append_block(C_prime.clone()).unwrap();
+        hard_chain.append_block(D_prime.clone()).unwrap();
+
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            vec![
+                ChainEvent::Disconnected(C.clone()),
+                ChainEvent::Disconnected(B.clone()),
+                ChainEvent::Connected(B_prime.clone()),
+                ChainEvent::Connected(C_prime.clone()),
+                ChainEvent::Connected(D_prime.clone()),
+                ChainEvent::Reorg {
+                    old_tip: C.block_hash().unwrap(),
+                    new_tip: D_prime.block_hash().unwrap(),
+                    depth: 2,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn it_drops_hung_up_subscribers() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+        let events = hard_chain.subscribe();
+
+        drop(hard_chain.subscribe());
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        hard_chain.append_block(A.clone()).unwrap();
+
+        assert_eq!(hard_chain.subscribers.lock().len(), 1);
+        assert_eq!(events.try_recv(), Ok(ChainEvent::Connected(A)));
+    }
+
+    #[test]
+    fn it_finds_common_ancestor_from_locator() {
+        let mut our_chain = Chain::<DummyBlock>::new(test_helpers::init_tempdb());
+        let mut their_chain = Chain::<DummyBlock>::new(test_helpers::init_tempdb());
+        let mut parent = Hash::NULL;
+
+        // Both chains share the first 30 blocks
+        for height in 1..=30 {
+            let block = Arc::new(DummyBlock::new(Some(parent), height));
+            parent = block.block_hash().unwrap();
+
+            our_chain.append_block(block.clone()).unwrap();
+            their_chain.append_block(block).unwrap();
+        }
+
+        let fork_point = parent.clone();
+        let mut our_parent = parent.clone();
+        let mut their_parent = parent;
+
+        for height in 31..=35 {
+            let block = Arc::new(DummyBlock::new(Some(our_parent), height));
+            our_parent = block.block_hash().unwrap();
+            our_chain.append_block(block).unwrap();
+        }
+
+        for height in 31..=70 {
+            let block = Arc::new(DummyBlock::new(Some(their_parent), height));
+            their_parent = block.block_hash().unwrap();
+            their_chain.append_block(block).unwrap();
+        }
+
+        let their_locator = their_chain.block_locator();
+        let our_locator = our_chain.block_locator();
+
+        assert_eq!(
+            their_locator[0],
+            their_chain.canonical_tip().block_hash().unwrap()
+        );
+        assert_eq!(
+            *their_locator.last().unwrap(),
+            their_chain.genesis().block_hash().unwrap()
+        );
+        assert!(their_locator.len() < 20);
+
+        // The fork point is not in their locator so the
+        // closest lower block must be found.
+        let ancestor = our_chain.find_common_ancestor(&their_locator).unwrap();
+        let ancestor_height = our_chain.block_height(&ancestor).unwrap();
+
+        assert!(ancestor_height <= 30);
+        assert_eq!(their_chain.block_height(&ancestor), Some(ancestor_height));
+        assert_eq!(
+            their_chain.find_common_ancestor(&our_locator),
+            Some(fork_point)
+        );
+        assert_eq!(
+            our_chain.find_common_ancestor(&[crypto::hash_slice(b"missing")]),
+            None
+        );
+    }
+
+    #[test]
+    fn it_rejects_blocks_from_the_future() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+        let timestamp = Utc::now() + chrono::Duration::hours(3);
+
+        let A = Arc::new(DummyBlock::with_timestamp(Some(Hash::NULL), 1, timestamp));
+
+        assert_eq!(
+            hard_chain.append_block(A.clone()),
+            Err(ChainErr::InvalidTimestamp)
+        );
+
+        hard_chain.set_max_timestamp_drift(chrono::Duration::hours(4));
+
+        assert_eq!(hard_chain.append_block(A.clone()), Ok(()));
+        assert_eq!(hard_chain.canonical_tip(), A);
+    }
+
+    #[test]
+    fn it_rejects_blocks_not_after_median_time_past() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+        let mut parent_hash = Hash::NULL;
+
+        // Timestamps are equal to heights
+        for height in 1..=5 {
+            let block = Arc::new(DummyBlock::new(Some(parent_hash), height));
+            parent_hash = block.block_hash().unwrap();
+            hard_chain.append_block(block).unwrap();
+        }
+
+        // The median of 0, 1, 2, 3, 4 and 5
+        let F = Arc::new(DummyBlock::with_timestamp(
+            Some(parent_hash),
+            6,
+            Utc.timestamp(3, 0),
+        ));
+
+        assert_eq!(hard_chain.append_block(F), Err(ChainErr::InvalidTimestamp));
+
+        // The median of 3, 4 and 5
+        hard_chain.set_median_time_span(3);
+
+        let F = Arc::new(DummyBlock::with_timestamp(
+            Some(parent_hash),
+            6,
+            Utc.timestamp(4, 0),
+        ));
+
+        assert_eq!(
+            hard_chain.append_block(F.clone()),
+            Err(ChainErr::InvalidTimestamp)
+        );
+
+        let F = Arc::new(DummyBlock::with_timestamp(
+            Some(parent_hash),
+            6,
+            Utc.timestamp(5, 0),
+        ));
+
+        assert_eq!(hard_chain.append_block(F.clone()), Ok(()));
+        assert_eq!(hard_chain.canonical_tip(), F);
+    }
+
+    #[test]
+    fn it_does_not_write_orphans_with_invalid_timestamps() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::with_timestamp(
+            Some(B.block_hash().unwrap()),
+            3,
+            Utc.timestamp(0, 0),
+        ));
+
+        hard_chain.append_block(A.clone()).unwrap();
+
+        // The ancestors of C are unknown at this point
+        hard_chain.append_block(C.clone()).unwrap();
+        hard_chain.append_block(B.clone()).unwrap();
+
+        assert_eq!(hard_chain.canonical_tip(), B);
+        assert!(hard_chain.query(&C.block_hash().unwrap()).is_none());
+    }
+
+    #[test]
+    fn it_does_not_switch_to_forks_with_invalid_timestamps() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let B_prime = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C_prime = Arc::new(DummyBlock::with_timestamp(
+            Some(B_prime.block_hash().unwrap()),
+            3,
+            Utc.timestamp(0, 0),
+        ));
+        let D_prime = Arc::new(DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4));
+
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(B.clone()).unwrap();
+        hard_chain.append_block(C.clone()).unwrap();
+
+        // The ancestors of C' and D' are unknown at this point
+        hard_chain.append_block(D_prime.clone()).unwrap();
+        hard_chain.append_block(C_prime.clone()).unwrap();
+        hard_chain.append_block(B_prime.clone()).unwrap();
+
+        assert_eq!(hard_chain.canonical_tip(), C);
+        assert_eq!(hard_chain.height(), 3);
+    }
+
+    #[test]
+    fn it_reports_error_context() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 12));
+        let C = Arc::new(DummyBlock::new(Some(crypto::hash_slice(b"missing")), 2));
+
+        hard_chain.append_block(A.clone()).unwrap();
+
+        let err = hard_chain.append_block(B.clone()).unwrap_err();
+
+        assert_eq!(
+            err,
+            ChainErr::BadHeight {
+                block: B.block_hash().unwrap(),
+                got: 12,
+                expected_min: 1,
+                expected_max: 11,
+            }
+        );
+        assert_eq!(
+            format!("{}", err),
+            format!(
+                "Block {:?} has height 12, expected between 1 and 11",
+                B.block_hash().unwrap()
+            )
+        );
+        assert_eq!(
+            hard_chain.write_block_raw(C.clone()),
+            Err(ChainErr::InvalidParent {
+                block: C.block_hash().unwrap(),
+                parent: crypto::hash_slice(b"missing"),
+            })
+        );
+
+        // Composes with boxed errors
+        let boxed: Box<dyn std::error::Error> = Box::new(err);
+        assert!(boxed.to_string().contains("has height 12"));
+    }
+
+    #[test]
+    fn it_does_not_reorg_deeper_than_max_reorg_depth() {
+        for (max_reorg_depth, switches) in vec![(3, false), (4, true)] {
+            let db = test_helpers::init_tempdb();
+            let config = ChainConfig {
+                max_reorg_depth,
+                ..ChainConfig::default()
+            };
+            let mut hard_chain =
+                Chain::<DummyBlock>::with_config(db, DummyBlock::genesis(), config);
+            let events = hard_chain.subscribe();
+
+            let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+            let mut parent_hash = A.block_hash().unwrap();
+
+            hard_chain.append_block(A.clone()).unwrap();
+
+            for height in 2..=5 {
+                let block = Arc::new(DummyBlock::new(Some(parent_hash), height));
+                parent_hash = block.block_hash().unwrap();
+                hard_chain.append_block(block).unwrap();
+            }
+
+            let E = hard_chain.canonical_tip();
+            let mut parent_hash = A.block_hash().unwrap();
+            let mut fork_tip = A.clone();
+
+            // Fork which removes B, C, D and E when switched to
+            for height in 2..=6 {
+                fork_tip = Arc::new(DummyBlock::new(Some(parent_hash), height));
+                parent_hash = fork_tip.block_hash().unwrap();
+                hard_chain.append_block(fork_tip.clone()).unwrap();
+            }
+
+            let fork_tip_hash = fork_tip.block_hash().unwrap();
+            let events: Vec<_> = events.try_iter().collect();
+
+            if switches {
+                assert_eq!(hard_chain.canonical_tip(), fork_tip);
+                assert_eq!(hard_chain.height(), 6);
+                assert!(events.contains(&ChainEvent::Reorg {
+                    old_tip: E.block_hash().unwrap(),
+                    new_tip: fork_tip_hash,
+                    depth: 4,
+                }));
+            } else {
+                assert_eq!(hard_chain.canonical_tip(), E);
+                assert_eq!(hard_chain.height(), 5);
+                assert!(hard_chain.valid_tips.contains(&fork_tip_hash));
+                assert_eq!(
+                    events.last(),
+                    Some(&ChainEvent::ReorgRejected {
+                        candidate_tip: fork_tip_hash,
+                        depth: 4,
+                    })
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn it_switches_to_deep_forks() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let mut canonical_parent = A.block_hash().unwrap();
+        let mut fork_parent = A.block_hash().unwrap();
+        let mut fork = vec![A.clone()];
+
+        hard_chain.append_block(A).unwrap();
+
+        // Grow both chains in lockstep up to the deepest
+        // fork which is not pruned from the orphan pool.
+        for height in 2..=11 {
+            let block = Arc::new(DummyBlock::new(Some(canonical_parent), height));
+            canonical_parent = block.block_hash().unwrap();
+            hard_chain.append_block(block).unwrap();
+
+            let block = Arc::new(DummyBlock::new(Some(fork_parent), height));
+            fork_parent = block.block_hash().unwrap();
+            hard_chain.append_block(block.clone()).unwrap();
+            fork.push(block);
+        }
+
+        assert_eq!(
+            hard_chain.canonical_tip().block_hash().unwrap(),
+            canonical_parent
+        );
+
+        let tip = Arc::new(DummyBlock::new(Some(fork_parent), 12));
+        hard_chain.append_block(tip.clone()).unwrap();
+        fork.push(tip.clone());
+
+        assert_eq!(hard_chain.canonical_tip(), tip);
+        assert_eq!(hard_chain.height(), 12);
+        assert_eq!(hard_chain.recent_reorgs().back().unwrap().removed.len(), 10);
+        assert!(hard_chain.orphan_pool.contains_key(&canonical_parent));
+        assert!(hard_chain.valid_tips.contains(&canonical_parent));
+
+        for block in fork {
+            assert_eq!(
+                hard_chain.query_by_height(block.height()),
+                Some(block.clone())
+            );
+        }
+    }
+
+    #[test]
+    fn it_returns_tips_and_fork_blocks() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let D = Arc::new(DummyBlock::new(Some(C.block_hash().unwrap()), 4));
+        let B_prime = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C_prime = Arc::new(DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3));
+        let C_second = Arc::new(DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3));
+        let F = Arc::new(DummyBlock::new(Some(crypto::hash_slice(b"missing")), 6));
+        let G = Arc::new(DummyBlock::new(Some(F.block_hash().unwrap()), 7));
+
+        for block in [&A, &B, &C, &D, &B_prime, &C_prime, &C_second, &F, &G].iter() {
+            hard_chain.append_block((*block).clone()).unwrap();
+        }
+
+        assert_eq!(hard_chain.canonical_tip(), D);
+
+        let tips = hard_chain.tips();
+
+        assert_eq!(tips.len(), 3);
+        assert_eq!(
+            tips[0],
+            TipInfo {
+                hash: G.block_hash().unwrap(),
+                height: 7,
+                status: OrphanType::DisconnectedTip,
+                fork_length: 2,
+            }
+        );
+
+        for tip in [&C_prime, &C_second].iter() {
+            assert!(tips.contains(&TipInfo {
+                hash: tip.block_hash().unwrap(),
+                height: 3,
+                status: OrphanType::ValidChainTip,
+                fork_length: 2,
+            }));
+        }
+
+        assert_eq!(
+            hard_chain.fork_blocks(&C_second.block_hash().unwrap()),
+            Some(vec![C_second.clone(), B_prime.clone()])
+        );
+        assert_eq!(
+            hard_chain.fork_blocks(&G.block_hash().unwrap()),
+            Some(vec![G.clone(), F.clone()])
+        );
+        assert_eq!(hard_chain.fork_blocks(&D.block_hash().unwrap()), None);
+    }
+
+    #[test]
+    fn it_prunes_stale_orphans() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+        let mut blocks = Vec::new();
+        let mut parent_hash = Hash::NULL;
+
+        for height in 1..=15 {
+            let block = Arc::new(DummyBlock::new(Some(parent_hash), height));
+            parent_hash = block.block_hash().unwrap();
+            blocks.push(block);
+        }
+
+        // Valid chain forking from B
+        let C_prime = Arc::new(DummyBlock::new(Some(blocks[1].block_hash().unwrap()), 3));
+        let D_prime = Arc::new(DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4));
+
+        // Disconnected chain of which only the head is stale
+        let X = Arc::new(DummyBlock::new(Some(crypto::hash_slice(b"missing_1")), 3));
+        let Y = Arc::new(DummyBlock::new(Some(X.block_hash().unwrap()), 4));
+        let Z = Arc::new(DummyBlock::new(Some(Y.block_hash().unwrap()), 5));
+
+        // Disconnected chain which is not stale
+        let W = Arc::new(DummyBlock::new(Some(crypto::hash_slice(b"missing_2")), 12));
+
+        for block in blocks[..3].iter() {
+            hard_chain.append_block(block.clone()).unwrap();
+        }
+
+        for block in [&C_prime, &D_prime, &X, &Y, &Z].iter() {
+            hard_chain.append_block((*block).clone()).unwrap();
+        }
+
+        for block in blocks[3..10].iter() {
+            hard_chain.append_block(block.clone()).unwrap();
+        }
+
+        hard_chain.append_block(W.clone()).unwrap();
+
+        assert_eq!(hard_chain.height(), 10);
+        assert_eq!(hard_chain.orphan_pool.len(), 6);
+
+        // The canonical height minus `MIN_HEIGHT` is now 4
+        for block in blocks[10..14].iter() {
+            hard_chain.append_block(block.clone()).unwrap();
+        }
+
+        assert_eq!(hard_chain.height(), 14);
+
+        let stale = [&C_prime, &D_prime, &X, &Y, &Z];
+
+        for block in stale.iter() {
+            let hash = block.block_hash().unwrap();
+
+            assert!(!hard_chain.orphan_pool.contains_key(&hash));
+            assert!(!hard_chain.validations_mapping.contains_key(&hash));
+            assert!(!hard_chain.valid_tips.contains(&hash));
+            assert!(!hard_chain.disconnected_tips_mapping.contains_key(&hash));
+            assert!(!hard_chain.disconnected_heads_mapping.contains_key(&hash));
+            assert!(!hard_chain.disconnected_heads_heights.contains_key(&hash));
+            assert!(!hard_chain.children_index.contains_key(&hash));
+        }
+
+        let W_hash = W.block_hash().unwrap();
+
+        assert_eq!(hard_chain.orphan_pool.len(), 1);
+        assert!(hard_chain.orphan_pool.contains_key(&W_hash));
+        assert!(hard_chain.disconnected_heads_mapping.contains_key(&W_hash));
+        assert_eq!(hard_chain.heights_mapping.len(), 1);
+        assert_eq!(hard_chain.max_orphan_height, Some(12));
+        assert!(hard_chain.verify_heights_mapping_consistency().is_empty());
+    }
+
+    #[test]
+    fn it_persists_the_genesis_block() {
+        let db = test_helpers::init_tempdb();
+        let genesis = Arc::new(DummyBlock::new(Some(Hash::NULL), 0));
+        let other_genesis = Arc::new(DummyBlock::new(Some(Hash::NULL), 0));
+        let mut hard_chain = Chain::<DummyBlock>::new_with_genesis(db, genesis.clone()).unwrap();
+
+        let A = Arc::new(DummyBlock::new(Some(genesis.block_hash().unwrap()), 1));
+        hard_chain.append_block(A.clone()).unwrap();
+
+        let db = hard_chain.db.clone();
+        let reopened = Chain::<DummyBlock>::new_with_genesis(db.clone(), genesis.clone()).unwrap();
+
+        assert_eq!(reopened.canonical_tip(), A);
+        assert_eq!(reopened.query_by_height(0), Some(genesis.clone()));
+        assert_eq!(
+            Chain::<DummyBlock>::new_with_genesis(db, other_genesis.clone()).err(),
+            Some(ChainErr::GenesisMismatch {
+                stored: genesis.block_hash().unwrap(),
+                given: other_genesis.block_hash().unwrap(),
+            })
+        );
+    }
+
+    #[test]
+    fn it_enforces_checkpoints() {
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let D = Arc::new(DummyBlock::new(Some(C.block_hash().unwrap()), 4));
+        let C_bad = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let B_prime = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let D_prime = Arc::new(DummyBlock::new(Some(C.block_hash().unwrap()), 4));
+        let E_prime = Arc::new(DummyBlock::new(Some(D_prime.block_hash().unwrap()), 5));
+
+        let db = test_helpers::init_tempdb();
+        let config = ChainConfig {
+            checkpoints: vec![(3, C.block_hash().unwrap())],
+            ..ChainConfig::default()
+        };
+        let mut hard_chain = Chain::<DummyBlock>::with_config(db, DummyBlock::genesis(), config);
+
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(B.clone()).unwrap();
+
+        // Only the checkpointed block is accepted at height 3
+        assert_eq!(
+            hard_chain.append_block(C_bad.clone()),
+            Err(ChainErr::CheckpointViolation {
+                block: C_bad.block_hash().unwrap(),
+                checkpoint_height: 3,
+            })
+        );
+
+        hard_chain.append_block(C.clone()).unwrap();
+        hard_chain.append_block(D.clone()).unwrap();
+
+        // Forks below the checkpoint are rejected
+        assert_eq!(
+            hard_chain.append_block(B_prime.clone()),
+            Err(ChainErr::CheckpointViolation {
+                block: B_prime.block_hash().unwrap(),
+                checkpoint_height: 3,
+            })
+        );
+        assert_eq!(
+            hard_chain.rewind(&B.block_hash().unwrap()),
+            Err(ChainErr::CheckpointViolation {
+                block: B.block_hash().unwrap(),
+                checkpoint_height: 3,
+            })
+        );
+        assert_eq!(hard_chain.canonical_tip(), D);
+
+        // Forks above the checkpoint reorg normally
+        hard_chain.append_block(D_prime.clone()).unwrap();
+        hard_chain.append_block(E_prime.clone()).unwrap();
+
+        assert_eq!(hard_chain.canonical_tip(), E_prime);
+        assert_eq!(hard_chain.query_by_height(3), Some(C));
+    }
+
+    #[test]
+    fn it_enforces_checkpoints_with_a_custom_genesis() {
+        let genesis = Arc::new(DummyBlock::new(Some(Hash::NULL), 0));
+        let A = Arc::new(DummyBlock::new(Some(genesis.block_hash().unwrap()), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let B_bad = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+
+        let db = test_helpers::init_tempdb();
+        let config = ChainConfig {
+            checkpoints: vec![(2, B.block_hash().unwrap())],
+            ..ChainConfig::default()
+        };
+        let mut hard_chain = Chain::<DummyBlock>::with_config(db, genesis.clone(), config);
+
+        assert_eq!(hard_chain.genesis(), genesis);
+        assert_eq!(hard_chain.canonical_tip(), genesis);
+
+        hard_chain.append_block(A.clone()).unwrap();
+
+        assert_eq!(
+            hard_chain.append_block(B_bad.clone()),
+            Err(ChainErr::CheckpointViolation {
+                block: B_bad.block_hash().unwrap(),
+                checkpoint_height: 2,
+            })
+        );
+
+        hard_chain.append_block(B.clone()).unwrap();
+
+        assert_eq!(hard_chain.canonical_tip(), B);
+        assert_eq!(hard_chain.query_by_height(0), Some(genesis));
+    }
+
+    #[test]
+    fn it_discards_inconsistent_forks_attached_to_valid_chains() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let B_prime = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+
+        // Skips heights 3 and 4
+        let E_prime = Arc::new(DummyBlock::new(Some(B_prime.block_hash().unwrap()), 5));
+        let F_prime = Arc::new(DummyBlock::new(Some(E_prime.block_hash().unwrap()), 6));
+
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(B.clone()).unwrap();
+        hard_chain.append_block(E_prime.clone()).unwrap();
+        hard_chain.append_block(F_prime.clone()).unwrap();
+        hard_chain.append_block(B_prime.clone()).unwrap();
+
+        assert_eq!(hard_chain.height(), 2);
+        assert_eq!(hard_chain.canonical_tip(), B);
+        assert_eq!(hard_chain.orphan_pool.len(), 1);
+        assert!(hard_chain
+            .orphan_pool
+            .get(&B_prime.block_hash().unwrap())
+            .is_some());
+        assert!(hard_chain.disconnected_heads_mapping.is_empty());
+        assert!(hard_chain.disconnected_tips_mapping.is_empty());
+        assert!(hard_chain.query(&F_prime.block_hash().unwrap()).is_none());
+    }
+
+    #[test]
+    fn it_discards_inconsistent_forks_attached_to_disconnected_chains() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+
+        // Skips height 4
+        let E = Arc::new(DummyBlock::new(Some(C.block_hash().unwrap()), 5));
+        let F = Arc::new(DummyBlock::new(Some(E.block_hash().unwrap()), 6));
+
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(E.clone()).unwrap();
+        hard_chain.append_block(F.clone()).unwrap();
+        hard_chain.append_block(C.clone()).unwrap();
+
+        assert!(hard_chain
+            .orphan_pool
+            .get(&E.block_hash().unwrap())
+            .is_none());
+        assert!(hard_chain
+            .orphan_pool
+            .get(&F.block_hash().unwrap())
+            .is_none());
+
+        hard_chain.append_block(B.clone()).unwrap();
+
+        assert_eq!(hard_chain.height(), 3);
+        assert_eq!(hard_chain.canonical_tip(), C);
+        assert!(hard_chain.orphan_pool.is_empty());
+        assert_eq!(hard_chain.max_orphan_height, None);
+        assert!(hard_chain.query(&E.block_hash().unwrap()).is_none());
+        assert!(hard_chain.query(&F.block_hash().unwrap()).is_none());
+    }
+
+    #[test]
+    fn it_discards_inconsistent_forks_following_the_canonical_tip() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+
+        // Skips heights 4 to 8
+        let I = Arc::new(DummyBlock::new(Some(C.block_hash().unwrap()), 9));
+
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(I.clone()).unwrap();
+        hard_chain.append_block(B.clone()).unwrap();
+        hard_chain.append_block(C.clone()).unwrap();
+
+        assert_eq!(hard_chain.height(), 3);
+        assert_eq!(hard_chain.canonical_tip(), C);
+        assert!(hard_chain.orphan_pool.is_empty());
+        assert!(hard_chain.disconnected_heads_mapping.is_empty());
+        assert!(hard_chain.query(&I.block_hash().unwrap()).is_none());
+        assert!(hard_chain.query_by_height(4).is_none());
+        assert_eq!(hard_chain.verify_chain_contiguity(), Ok(()));
+    }
+
+    #[test]
+    fn it_validates_disconnected_chains() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+        let D = Arc::new(DummyBlock::new(Some(C.block_hash().unwrap()), 4));
+        let C_hash = C.block_hash().unwrap();
+
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(C.clone()).unwrap();
+        hard_chain.append_block(D.clone()).unwrap();
+
+        assert_eq!(hard_chain.validate_disconnected_chain(&C_hash, 2), Ok(()));
+        assert_eq!(
+            hard_chain.validate_disconnected_chain(&C_hash, 1),
+            Err(ChainErr::InconsistentFork(C_hash.clone()))
+        );
+        assert_eq!(
+            hard_chain.validate_disconnected_chain(&D.block_hash().unwrap(), 3),
+            Err(ChainErr::InconsistentFork(D.block_hash().unwrap()))
+        );
+    }
+
+    #[test]
+    fn it_does_not_evict_orphans_for_invalid_blocks() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+        let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+        hard_chain.append_block(A.clone()).unwrap();
+        hard_chain.append_block(B.clone()).unwrap();
+
+        let X = Arc::new(DummyBlock::new(Some(crypto::hash_slice(b"missing")), 4));
+        hard_chain.append_block(X.clone()).unwrap();
+
+        for i in 0..MAX_ORPHANS - 1 {
+            let parent_hash = crypto::hash_slice(format!("missing-{}", i).as_bytes());
+            let height = 4 + (i as u64 % 8);
+
+            hard_chain
+                .append_block(Arc::new(DummyBlock::new(Some(parent_hash), height)))
+                .unwrap();
+        }
+
+        assert_eq!(hard_chain.orphan_pool.len(), MAX_ORPHANS);
+
+        // Forks the canonical chain with a bad height
+        let B_prime = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 3));
+
+        // Follows an orphan with a bad height
+        let Y = Arc::new(DummyBlock::new(Some(X.block_hash().unwrap()), 6));
+
+        assert!(match hard_chain.append_block(B_prime) {
+            Err(ChainErr::BadHeight { .. }) => true,
+            _ => false,
+        });
+        assert!(match hard_chain.append_block(Y) {
+            Err(ChainErr::BadHeight { .. }) => true,
+            _ => false,
+        });
+        assert_eq!(hard_chain.orphan_pool.len(), MAX_ORPHANS);
+        assert!(hard_chain
+            .orphan_pool
+            .contains_key(&X.block_hash().unwrap()));
+    }
+
+    #[test]
+    fn it_computes_average_block_time() {
+        let db = test_helpers::init_tempdb();
+        let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+        assert_eq!(hard_chain.average_block_time(5), None);
+
+        let mut parent_hash = Hash::NULL;
+
+        // Blocks are 10 seconds apart except for
+        // the last one which is 40 seconds apart.
+        for (height, timestamp) in vec![(1, 10), (2, 20), (3, 30), (4, 40), (5, 80)] {
+            let block =
+                DummyBlock::with_timestamp(Some(parent_hash), height, Utc.timestamp(timestamp, 0));
+            let block = Arc::new(block);
+
+            parent_hash = block.block_hash().unwrap();
+            hard_chain.append_block(block).unwrap();
+        }
+
+        assert_eq!(hard_chain.average_block_time(0), None);
+        assert_eq!(
+            hard_chain.average_block_time(1),
+            Some(Duration::from_secs(40))
         );
         assert_eq!(
-            *hard_chain
-                .validations_mapping
-                .get(&G.block_hash().unwrap())
-                .unwrap(),
-            OrphanType::DisconnectedTip
+            hard_chain.average_block_time(2),
+            Some(Duration::from_secs(25))
         );
         assert_eq!(
-            *hard_chain
-                .validations_mapping
-                .get(&C_prime.block_hash().unwrap())
-                .unwrap(),
-            OrphanType::BelongsToDisconnected
+            hard_chain.average_block_time(4),
+            Some(Duration::from_millis(17500))
         );
         assert_eq!(
-            *hard_chain
-                .validations_mapping
-                .get(&D_prime.block_hash().unwrap())
-                .unwrap(),
-            OrphanType::DisconnectedTip
+            hard_chain.average_block_time(5),
+            Some(Duration::from_secs(16))
         );
         assert_eq!(
-            *hard_chain
-                .validations_mapping
-                .get(&D_tertiary.block_hash().unwrap())
-                .unwrap(),
-            OrphanType::DisconnectedTip
+            hard_chain.average_block_time(100),
+            Some(Duration::from_secs(16))
         );
-
-        // Check inverse height
-        assert_eq!(*B_prime_ih, 4);
-        assert_eq!(*C_prime_ih, 1);
-        assert_eq!(*C_second_ih, 3);
-        assert_eq!(*D_second_ih, 2);
-        assert_eq!(*E_second_ih, 1);
-        assert_eq!(*F_second_ih, 0);
-        assert_eq!(*E_ih, 2);
-        assert_eq!(*F_ih, 1);
-        assert_eq!(*G_ih, 0);
-        assert_eq!(*D_ih, 3);
-        assert_eq!(*C_ih, 4);
-        assert_eq!(*B_ih, 5);
-        assert_eq!(*D_prime_ih, 0);
-        assert_eq!(*D_tertiary_ih, 0);
-
-        // Check max orphan height
-        assert_eq!(hard_chain.max_orphan_height, Some(7));
-
-        hard_chain.append_block(A.clone()).unwrap();
-        hard_chain.append_block(E_prime.clone()).unwrap();
-
-        assert_eq!(hard_chain.height(), 7);
-        assert_eq!(hard_chain.canonical_tip(), G);
-        assert_eq!(hard_chain.max_orphan_height, Some(6));
     }
 
     quickcheck! {
@@ -6262,6 +11412,204 @@ mod tests {
             true
         }
 
+        fn append_blocks_stress_test() -> bool {
+            let db1 = test_helpers::init_tempdb();
+            let db2 = test_helpers::init_tempdb();
+            let mut batch_chain = Chain::<DummyBlock>::new(db1);
+            let mut hard_chain = Chain::<DummyBlock>::new(db2);
+
+            let mut A = DummyBlock::new(Some(Hash::NULL), 1);
+            let A = Arc::new(A);
+
+            let mut B = DummyBlock::new(Some(A.block_hash().unwrap()), 2);
+            let B = Arc::new(B);
+
+            let mut C = DummyBlock::new(Some(B.block_hash().unwrap()), 3);
+            let C = Arc::new(C);
+
+            let mut D = DummyBlock::new(Some(C.block_hash().unwrap()), 4);
+            let D = Arc::new(D);
+
+            let mut E = DummyBlock::new(Some(D.block_hash().unwrap()), 5);
+            let E = Arc::new(E);
+
+            let mut F = DummyBlock::new(Some(E.block_hash().unwrap()), 6);
+            let F = Arc::new(F);
+
+            let mut G = DummyBlock::new(Some(F.block_hash().unwrap()), 7);
+            let G = Arc::new(G);
+
+            let mut B_prime = DummyBlock::new(Some(A.block_hash().unwrap()), 2);
+            let B_prime = Arc::new(B_prime);
+
+            let mut C_prime = DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3);
+            let C_prime = Arc::new(C_prime);
+
+            let mut D_prime = DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4);
+            let D_prime = Arc::new(D_prime);
+
+            let mut E_prime = DummyBlock::new(Some(D_prime.block_hash().unwrap()), 5);
+            let E_prime = Arc::new(E_prime);
+
+            let mut C_second = DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3);
+            let C_second = Arc::new(C_second);
+
+            let mut D_second = DummyBlock::new(Some(C_second.block_hash().unwrap()), 4);
+            let D_second = Arc::new(D_second);
+
+            let mut E_second = DummyBlock::new(Some(D_second.block_hash().unwrap()), 5);
+            let E_second = Arc::new(E_second);
+
+            let mut F_second = DummyBlock::new(Some(E_second.block_hash().unwrap()), 6);
+            let F_second = Arc::new(F_second);
+
+            let mut D_tertiary = DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4);
+            let D_tertiary = Arc::new(D_tertiary);
+
+            let mut blocks = vec![
+                A.clone(),
+                B.clone(),
+                C.clone(),
+                D.clone(),
+                E.clone(),
+                F.clone(),
+                G.clone(),
+                B_prime.clone(),
+                C_prime.clone(),
+                D_prime.clone(),
+                E_prime.clone(),
+                C_second.clone(),
+                D_second.clone(),
+                E_second.clone(),
+                F_second.clone(),
+                D_tertiary.clone()
+            ];
+
+            // Shuffle blocks
+            thread_rng().shuffle(&mut blocks);
+
+            for b in blocks.iter() {
+                hard_chain.append_block(b.clone()).unwrap();
+            }
+
+            let results = batch_chain.append_blocks(blocks.clone());
+
+            assert_eq!(results.len(), blocks.len());
+            assert!(results.iter().all(|(_, result)| result.is_ok()));
+            assert_eq!(batch_chain.height(), hard_chain.height());
+            assert_eq!(batch_chain.canonical_tip(), hard_chain.canonical_tip());
+            assert_eq!(batch_chain.canonical_tip(), G);
+
+            // Disconnected chains which follow a written block
+            // must be attached just as with sequential appends.
+            let db3 = test_helpers::init_tempdb();
+            let db4 = test_helpers::init_tempdb();
+            let mut batch_chain = Chain::<DummyBlock>::new(db3);
+            let mut hard_chain = Chain::<DummyBlock>::new(db4);
+            let mut forks = vec![B_prime.clone(), C_prime.clone(), D_prime.clone()];
+
+            thread_rng().shuffle(&mut forks);
+
+            for b in forks.iter() {
+                hard_chain.append_block(b.clone()).unwrap();
+                batch_chain.append_block(b.clone()).unwrap();
+            }
+
+            hard_chain.append_block(A.clone()).unwrap();
+            hard_chain.append_block(B.clone()).unwrap();
+
+            let results = batch_chain.append_blocks(vec![B.clone(), A.clone()]);
+
+            assert!(results.iter().all(|(_, result)| result.is_ok()));
+            assert_eq!(batch_chain.height(), hard_chain.height());
+            assert_eq!(batch_chain.canonical_tip(), hard_chain.canonical_tip());
+            assert_eq!(batch_chain.canonical_tip(), D_prime);
+
+            true
+        }
+
+        fn concurrent_append_stress_test() -> bool {
+            let db = test_helpers::init_tempdb();
+            let chain = Arc::new(RwLock::new(Chain::<DummyBlock>::new(db)));
+            let chain_ref = ChainRef::new(chain);
+
+            let A = Arc::new(DummyBlock::new(Some(Hash::NULL), 1));
+            let B = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+            let C = Arc::new(DummyBlock::new(Some(B.block_hash().unwrap()), 3));
+            let D = Arc::new(DummyBlock::new(Some(C.block_hash().unwrap()), 4));
+            let E = Arc::new(DummyBlock::new(Some(D.block_hash().unwrap()), 5));
+            let F = Arc::new(DummyBlock::new(Some(E.block_hash().unwrap()), 6));
+            let G = Arc::new(DummyBlock::new(Some(F.block_hash().unwrap()), 7));
+            let B_prime = Arc::new(DummyBlock::new(Some(A.block_hash().unwrap()), 2));
+            let C_prime = Arc::new(DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3));
+            let D_prime = Arc::new(DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4));
+            let E_prime = Arc::new(DummyBlock::new(Some(D_prime.block_hash().unwrap()), 5));
+            let C_second = Arc::new(DummyBlock::new(Some(B_prime.block_hash().unwrap()), 3));
+            let D_second = Arc::new(DummyBlock::new(Some(C_second.block_hash().unwrap()), 4));
+            let E_second = Arc::new(DummyBlock::new(Some(D_second.block_hash().unwrap()), 5));
+            let F_second = Arc::new(DummyBlock::new(Some(E_second.block_hash().unwrap()), 6));
+            let D_tertiary = Arc::new(DummyBlock::new(Some(C_prime.block_hash().unwrap()), 4));
+
+            let mut blocks = vec![
+                A.clone(),
+                B.clone(),
+                C.clone(),
+                D.clone(),
+                E.clone(),
+                F.clone(),
+                G.clone(),
+                B_prime.clone(),
+                C_prime.clone(),
+                D_prime.clone(),
+                E_prime.clone(),
+                C_second.clone(),
+                D_second.clone(),
+                E_second.clone(),
+                F_second.clone(),
+                D_tertiary.clone()
+            ];
+
+            // Shuffle blocks
+            thread_rng().shuffle(&mut blocks);
+
+            // Cache some blocks so that evictions are exercised
+            for block in blocks.iter() {
+                chain_ref.query(&block.block_hash().unwrap());
+            }
+
+            let handles: Vec<_> = blocks
+                .chunks(4)
+                .map(|chunk| {
+                    let chain_ref = chain_ref.clone();
+                    let chunk = chunk.to_vec();
+
+                    thread::spawn(move || {
+                        for block in chunk {
+                            chain_ref.append_block(block).unwrap();
+                        }
+                    })
+                })
+                .collect();
+
+            for handle in handles {
+                handle.join().unwrap();
+            }
+
+            assert_eq!(chain_ref.height(), 7);
+            assert_eq!(chain_ref.canonical_tip(), G);
+
+            // Only canonical blocks are served
+            for block in [&A, &B, &C, &D, &E, &F, &G].iter() {
+                assert_eq!(chain_ref.query(&block.block_hash().unwrap()), Some((*block).clone()));
+            }
+
+            for block in [&B_prime, &C_prime, &E_second, &D_tertiary].iter() {
+                assert!(chain_ref.query(&block.block_hash().unwrap()).is_none());
+            }
+
+            true
+        }
+
         fn it_rewinds_correctly1() -> bool {
             let db = test_helpers::init_tempdb();
             let mut hard_chain = Chain::<DummyBlock>::new(db);
@@ -6338,6 +11686,89 @@ mod tests {
             true
         }
 
+        fn it_rewinds_to_genesis() -> bool {
+            let db = test_helpers::init_tempdb();
+            let mut hard_chain = Chain::<DummyBlock>::new(db);
+
+            let mut A = DummyBlock::new(Some(Hash::NULL), 1);
+            let A = Arc::new(A);
+
+            let mut B = DummyBlock::new(Some(A.block_hash().unwrap()), 2);
+            let B = Arc::new(B);
+
+            let mut C = DummyBlock::new(Some(B.block_hash().unwrap()), 3);
+            let C = Arc::new(C);
+
+            let mut D = DummyBlock::new(Some(C.block_hash().unwrap()), 4);
+            let D = Arc::new(D);
+
+            let mut E = DummyBlock::new(Some(D.block_hash().unwrap()), 5);
+            let E = Arc::new(E);
+
+            let mut F = DummyBlock::new(Some(E.block_hash().unwrap()), 6);
+            let F = Arc::new(F);
+
+            let mut G = DummyBlock::new(Some(F.block_hash().unwrap()), 7);
+            let G = Arc::new(G);
+
+            let mut H = DummyBlock::new(Some(G.block_hash().unwrap()), 8);
+            let H = Arc::new(H);
+
+            let blocks = vec![
+                A.clone(),
+                B.clone(),
+                C.clone(),
+                D.clone(),
+                E.clone(),
+                F.clone(),
+                G.clone(),
+            ];
+
+            // Rewinding an empty chain is a no-op
+            assert_eq!(hard_chain.rewind(&Hash::NULL), Ok(()));
+            assert_eq!(hard_chain.height(), 0);
+
+            for b in blocks.iter() {
+                hard_chain.append_block(b.clone()).unwrap();
+            }
+
+            assert_eq!(hard_chain.height(), 7);
+            assert_eq!(hard_chain.canonical_tip(), G.clone());
+            assert_eq!(hard_chain.max_orphan_height, None);
+
+            hard_chain.rewind(&Hash::NULL).unwrap();
+
+            assert_eq!(hard_chain.height(), 0);
+            assert_eq!(hard_chain.canonical_tip(), DummyBlock::genesis());
+            assert_eq!(hard_chain.total_work(), 0);
+            assert_eq!(hard_chain.max_orphan_height, Some(7));
+            assert_eq!(hard_chain.query_by_height(1), None);
+
+            for b in blocks.iter() {
+                assert!(hard_chain.query(&b.block_hash().unwrap()).is_none());
+            }
+
+            for b in blocks[..6].iter() {
+                assert_eq!(*hard_chain.validations_mapping.get(&b.block_hash().unwrap()).unwrap(), OrphanType::BelongsToValidChain);
+            }
+
+            assert_eq!(*hard_chain.validations_mapping.get(&G.block_hash().unwrap()).unwrap(), OrphanType::ValidChainTip);
+            let mut tips = HashSet::new();
+            tips.insert(G.block_hash().unwrap());
+
+            assert_eq!(hard_chain.valid_tips, tips);
+
+            // Extending the rewound chain makes it canonical again
+            hard_chain.append_block(H.clone()).unwrap();
+
+            assert_eq!(hard_chain.height(), 8);
+            assert_eq!(hard_chain.canonical_tip(), H);
+            assert_eq!(hard_chain.query_by_height(1), Some(A));
+            assert!(hard_chain.orphan_pool.is_empty());
+
+            true
+        }
+
         fn it_rewinds_correctly2() -> bool {
             let db = test_helpers::init_tempdb();
             let mut hard_chain = Chain::<DummyBlock>::new(db);
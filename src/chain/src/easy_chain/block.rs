@@ -97,6 +97,13 @@ impl Block for EasyBlock {
         self.parent_hash.clone()
     }
 
+    fn verify_hash(&self) -> bool {
+        let message = self.compute_hash_message();
+        let oracle = crypto::hash_slice(&message);
+
+        self.hash.unwrap() == oracle
+    }
+
     fn merkle_root(&self) -> Option<Hash> {
         self.merkle_root.clone()
     }
@@ -136,13 +143,6 @@ impl EasyBlock {
         self.hash = Some(hash);
     }
 
-    pub fn verify_hash(&self) -> bool {
-        let message = self.compute_hash_message();
-        let oracle = crypto::hash_slice(&message);
-
-        self.hash.unwrap() == oracle
-    }
-
     fn compute_hash_message(&self) -> Vec<u8> {
         let mut buf: Vec<u8> = Vec::new();
         let encoded_height = encode_be_u64!(self.height);
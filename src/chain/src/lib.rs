@@ -23,6 +23,9 @@ mod chain;
 mod easy_chain;
 mod hard_chain;
 mod orphan_type;
+mod semaphore;
+mod state_db;
+mod worker_pool;
 
 pub use crate::chain::*;
 pub use block::*;
@@ -30,3 +33,5 @@ pub use easy_chain::block::*;
 pub use easy_chain::chain::*;
 pub use hard_chain::block::*;
 pub use hard_chain::chain::*;
+pub use orphan_type::*;
+pub use state_db::*;
@@ -0,0 +1,70 @@
+/*
+  Copyright 2018 The Purple Library Authors
+  This file is part of the Purple Library.
+
+  The Purple Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use parking_lot::{Condvar, Mutex};
+
+#[derive(Debug)]
+/// Counting semaphore limiting the number of threads
+/// that can run a section of code at the same time.
+pub struct Semaphore {
+    /// The number of available permits.
+    permits: Mutex<usize>,
+
+    /// Notifies waiting threads when a permit is released.
+    released: Condvar,
+}
+
+impl Semaphore {
+    pub fn new(permits: usize) -> Semaphore {
+        Semaphore {
+            permits: Mutex::new(permits),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Returns the number of permits which are not acquired.
+    #[cfg(test)]
+    pub fn available_permits(&self) -> usize {
+        *self.permits.lock()
+    }
+
+    /// Blocks until a permit is available and acquires it. The
+    /// permit is released when the returned guard is dropped.
+    pub fn acquire(&self) -> SemaphorePermit<'_> {
+        let mut permits = self.permits.lock();
+
+        while *permits == 0 {
+            self.released.wait(&mut permits);
+        }
+
+        *permits -= 1;
+        SemaphorePermit { semaphore: self }
+    }
+}
+
+/// Guard holding a permit of a `Semaphore`.
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+}
+
+impl<'a> Drop for SemaphorePermit<'a> {
+    fn drop(&mut self) {
+        *self.semaphore.permits.lock() += 1;
+        self.semaphore.released.notify_one();
+    }
+}
@@ -0,0 +1,68 @@
+/*
+  Copyright 2018 The Purple Library Authors
+  This file is part of the Purple Library.
+
+  The Purple Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use parking_lot::Mutex;
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+#[derive(Debug)]
+/// Fixed size pool of threads running jobs in the order
+/// in which they are submitted.
+///
+/// Jobs are allowed to block, which is why plain threads are used
+/// instead of the tokio executor whose workers must never block.
+pub struct WorkerPool {
+    /// Queues jobs for the workers.
+    sender: Mutex<Sender<Job>>,
+}
+
+impl WorkerPool {
+    pub fn new(size: usize) -> WorkerPool {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        for _ in 0..size {
+            let receiver = receiver.clone();
+
+            // Workers exit once the pool is dropped
+            // and all of the queued jobs are done.
+            thread::spawn(move || loop {
+                let job = receiver.lock().recv();
+
+                match job {
+                    Ok(job) => job(),
+                    Err(_) => break,
+                }
+            });
+        }
+
+        WorkerPool {
+            sender: Mutex::new(sender),
+        }
+    }
+
+    /// Queues the given job to be run by the first available worker.
+    pub fn execute<F: FnOnce() + Send + 'static>(&self, job: F) {
+        // Sending only fails if all of the workers
+        // have exited in which case the job is dropped.
+        let _ = self.sender.lock().send(Box::new(job));
+    }
+}
@@ -25,6 +25,16 @@ use rlp::NULL_RLP;
 use std::sync::Arc;
 use BlakeDbHasher;
 
+/// An operation which is part of a write batch.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DbOp {
+    /// Writes the value at the given key.
+    Put(Hash, ElasticArray128<u8>),
+
+    /// Removes the value at the given key.
+    Delete(Hash),
+}
+
 #[derive(Clone)]
 pub struct PersistentDb {
     db_ref: Option<Arc<Database>>,
@@ -49,6 +59,35 @@ impl PersistentDb {
             memory_db: Some(HashMap::new()),
         }
     }
+
+    /// Applies the given operations in order as a single atomic write.
+    pub fn write_batch(&mut self, ops: Vec<DbOp>) {
+        if let Some(db_ref) = &self.db_ref {
+            let mut tx = db_ref.transaction();
+
+            for op in ops {
+                match op {
+                    DbOp::Put(key, val) => tx.put(self.cf, &key.0.to_vec(), &val),
+                    DbOp::Delete(key) => tx.delete(self.cf, &key.0.to_vec()),
+                }
+            }
+
+            db_ref.write(tx).unwrap();
+        } else {
+            let memory_db = self.memory_db.as_mut().unwrap();
+
+            for op in ops {
+                match op {
+                    DbOp::Put(key, val) => {
+                        memory_db.insert(key.0.to_vec(), val.to_vec());
+                    }
+                    DbOp::Delete(key) => {
+                        memory_db.remove(&key.0.to_vec());
+                    }
+                }
+            }
+        }
+    }
 }
 
 impl std::fmt::Debug for PersistentDb {
@@ -233,4 +272,25 @@ mod tests {
 
         assert!(!persistent_db.contains(&key));
     }
+
+    #[test]
+    fn it_writes_batches() {
+        let config = DatabaseConfig::with_columns(None);
+        let dir = TempDir::new("purple_test").unwrap();
+        let db = Database::open(&config, dir.path().to_str().unwrap()).unwrap();
+        let db_ref = Arc::new(db);
+        let mut persistent_db = PersistentDb::new(db_ref, None);
+        let key1 = crypto::hash_slice(b"key1");
+        let key2 = crypto::hash_slice(b"key2");
+        let data = b"Hello world";
+
+        persistent_db.emplace(key1, ElasticArray128::from_slice(data));
+        persistent_db.write_batch(vec![
+            DbOp::Delete(key1),
+            DbOp::Put(key2, ElasticArray128::from_slice(data)),
+        ]);
+
+        assert!(!persistent_db.contains(&key1));
+        assert_eq!(persistent_db.get(&key2).unwrap().to_vec(), data.to_vec());
+    }
 }
@@ -0,0 +1,231 @@
+/*
+  Copyright 2018 The Purple Library Authors
+  This file is part of the Purple Library.
+
+  The Purple Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use code::disassembler::DisassemblerIterator;
+use code::validated_bytecode::ValidatedBytecode;
+use instruction_set::{Instruction, CT_FLOW_OPS};
+use stack::Stack;
+
+/// A node in the control flow graph of a block of bytecode.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CfgNode {
+    /// The decoded instruction
+    pub op: Instruction,
+
+    /// The byte offset of the instruction
+    pub offset: usize,
+
+    /// The offsets of the instructions that can be executed
+    /// after this one. An offset lower than `offset` is a
+    /// back-edge i.e. a loop.
+    pub successors: Vec<usize>,
+}
+
+/// Builds the control flow graph of the given bytecode, with
+/// one node for each instruction in the order they appear.
+pub fn build_cfg(bytecode: &ValidatedBytecode) -> Vec<CfgNode> {
+    let decoded: Vec<(usize, Instruction)> = DisassemblerIterator::new(bytecode)
+        .map(|(offset, op, _)| (offset, op))
+        .collect();
+
+    // Pair every block opener with its `End`
+    let mut ends: Vec<Option<usize>> = vec![None; decoded.len()];
+    let mut stack: Stack<usize> = Stack::new();
+
+    for (i, (_, op)) in decoded.iter().enumerate() {
+        if opens_frame(op) {
+            stack.push(i);
+        } else if let Instruction::End = op {
+            ends[stack.pop()] = Some(i);
+        }
+    }
+
+    // Returns the offset of the node after the one at `i`, if any
+    let after = |i: usize| decoded.get(i + 1).map(|(offset, _)| *offset);
+    let mut nodes = Vec::with_capacity(decoded.len());
+    let mut stack: Stack<usize> = Stack::new();
+
+    for (i, (offset, op)) in decoded.iter().enumerate() {
+        let mut successors = Vec::new();
+
+        match op {
+            Instruction::End => {
+                let opener = stack.pop();
+
+                match decoded[opener].1 {
+                    // Loops jump back to their beginning
+                    Instruction::Loop | Instruction::While => {
+                        successors.push(decoded[opener].0);
+                    }
+                    // A finished `If` skips its `Else` block
+                    Instruction::If => {
+                        let next = decoded.get(i + 1).map(|(_, op)| *op);
+
+                        if let Some(Instruction::Else) = next {
+                            successors.extend(ends[i + 1].and_then(&after));
+                        } else {
+                            successors.extend(after(i));
+                        }
+                    }
+                    _ => successors.extend(after(i)),
+                }
+            }
+            Instruction::If => {
+                stack.push(i);
+                successors.extend(after(i));
+                successors.extend(ends[i].and_then(&after));
+            }
+            Instruction::Break | Instruction::BreakIf => {
+                let depth = match bytecode.get(offset + 1) {
+                    Some(depth) => *depth as usize,
+                    None => 0,
+                };
+
+                if let Instruction::BreakIf = op {
+                    successors.extend(after(i));
+                }
+
+                // Jump after the end of the targeted loop
+                let target = stack
+                    .iter()
+                    .filter(|j| match decoded[**j].1 {
+                        Instruction::Loop | Instruction::While => true,
+                        _ => false,
+                    })
+                    .nth(depth);
+
+                if let Some(j) = target {
+                    successors.extend(ends[*j].and_then(&after));
+                }
+            }
+            Instruction::Return | Instruction::Halt => {
+                // Execution does not continue in this block
+            }
+            _ => {
+                if opens_frame(op) {
+                    stack.push(i);
+                }
+
+                successors.extend(after(i));
+            }
+        }
+
+        nodes.push(CfgNode {
+            op: *op,
+            offset: *offset,
+            successors: successors,
+        });
+    }
+
+    nodes
+}
+
+/// Returns `true` if the given op opens a frame which
+/// is closed by an `End` instruction.
+fn opens_frame(op: &Instruction) -> bool {
+    match op {
+        // A function body is a `Begin`-like frame
+        Instruction::DefineFunction => true,
+        _ => CT_FLOW_OPS.iter().any(|o| o == op),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_adds_back_edges_for_loops() {
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Loop.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Nop.repr(),
+            Instruction::Break.repr(),
+            0x00,                             // Loop depth
+            Instruction::End.repr(),
+            Instruction::End.repr()
+        ];
+
+        let bytecode = ValidatedBytecode::new(block).unwrap();
+        let cfg = build_cfg(&bytecode);
+
+        assert_eq!(cfg, vec![
+            CfgNode { op: Instruction::Begin, offset: 0, successors: vec![2] },
+            CfgNode { op: Instruction::Loop, offset: 2, successors: vec![4] },
+            CfgNode { op: Instruction::Nop, offset: 4, successors: vec![5] },
+            CfgNode { op: Instruction::Break, offset: 5, successors: vec![8] },
+            CfgNode { op: Instruction::End, offset: 7, successors: vec![2] },
+            CfgNode { op: Instruction::End, offset: 8, successors: vec![] }
+        ]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_adds_forward_edges_for_conditional_breaks() {
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::While.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Nop.repr(),
+            Instruction::BreakIf.repr(),
+            0x00,                             // Loop depth
+            Instruction::Eq.repr(),
+            Instruction::Nop.repr(),
+            Instruction::End.repr(),
+            Instruction::End.repr()
+        ];
+
+        let bytecode = ValidatedBytecode::new(block).unwrap();
+        let cfg = build_cfg(&bytecode);
+
+        assert_eq!(cfg[3].op, Instruction::BreakIf);
+        assert_eq!(cfg[3].successors, vec![8, 10]);
+        assert_eq!(cfg[5].successors, vec![2]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_pairs_function_definitions_with_their_end() {
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::DeclareFunction.repr(),
+            0x00,                             // 0 Arity
+            Instruction::DefineFunction.repr(),
+            Instruction::Nop.repr(),
+            Instruction::End.repr(),
+            Instruction::End.repr()
+        ];
+
+        let bytecode = ValidatedBytecode::new(block).unwrap();
+        let cfg = build_cfg(&bytecode);
+
+        assert_eq!(cfg, vec![
+            CfgNode { op: Instruction::Begin, offset: 0, successors: vec![2] },
+            CfgNode { op: Instruction::DeclareFunction, offset: 2, successors: vec![4] },
+            CfgNode { op: Instruction::DefineFunction, offset: 4, successors: vec![5] },
+            CfgNode { op: Instruction::Nop, offset: 5, successors: vec![6] },
+            CfgNode { op: Instruction::End, offset: 6, successors: vec![7] },
+            CfgNode { op: Instruction::End, offset: 7, successors: vec![] }
+        ]);
+    }
+}
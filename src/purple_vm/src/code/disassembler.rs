@@ -0,0 +1,229 @@
+/*
+  Copyright 2018 The Purple Library Authors
+  This file is part of the Purple Library.
+
+  The Purple Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use bitvec::Bits;
+use instruction_set::Instruction;
+use primitives::r#type::VmType;
+use std::cmp;
+
+/// Lazily decodes bytecode into `(offset, instruction, arguments)`
+/// triples, where `arguments` are the immediate bytes that follow
+/// the instruction.
+///
+/// An unknown byte is yielded as `(offset, Instruction::Nop, vec![byte])`.
+#[derive(Clone, Debug)]
+pub struct DisassemblerIterator<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> DisassemblerIterator<'a> {
+    pub fn new(bytes: &'a [u8]) -> DisassemblerIterator<'a> {
+        DisassemblerIterator {
+            bytes: bytes,
+            offset: 0,
+        }
+    }
+
+    /// Returns the byte at the given index or `0x00`
+    /// if the index is out of bounds.
+    fn byte_at(&self, idx: usize) -> u8 {
+        match self.bytes.get(idx) {
+            Some(byte) => *byte,
+            None => 0x00,
+        }
+    }
+
+    /// Returns the number of immediate argument
+    /// bytes of `op`, starting at `start`.
+    fn args_len(&self, op: Instruction, start: usize) -> usize {
+        match op {
+            Instruction::Begin
+            | Instruction::Loop
+            | Instruction::While
+            | Instruction::Else
+            | Instruction::Block
+            | Instruction::Break
+            | Instruction::Return
+            | Instruction::And
+            | Instruction::Or
+            | Instruction::Xor
+            | Instruction::Not
+            | Instruction::Shl
+            | Instruction::ShrSigned
+            | Instruction::ShrUnsigned
+            | Instruction::Rotl
+            | Instruction::Rotr
+            | Instruction::Clz
+            | Instruction::Ctz
+            | Instruction::Popcnt
+            | Instruction::Min
+            | Instruction::Max
+            | Instruction::CopySign
+            | Instruction::Sqrt
+            | Instruction::Abs
+            | Instruction::Neg
+            | Instruction::Ceil
+            | Instruction::Floor
+            | Instruction::Trunc
+            | Instruction::Nearest
+            | Instruction::Eqz => 1,
+            Instruction::If
+            | Instruction::BreakIf
+            | Instruction::Compare
+            | Instruction::Convert
+            | Instruction::PickLocal
+            | Instruction::Call
+            | Instruction::GlobalGet
+            | Instruction::GlobalSet
+            | Instruction::TableGet => 2,
+            Instruction::TableSet | Instruction::PushI32Const | Instruction::PushF32Const => 4,
+            Instruction::PushI64Const | Instruction::PushF64Const => 8,
+            // Type, alignment hint and 32bit offset
+            Instruction::MemLoad | Instruction::MemStore => 6,
+            // Targets count followed by 16bit targets
+            Instruction::BranchTable => 1 + 2 * self.byte_at(start) as usize,
+            // Arity followed by the argument types
+            Instruction::DeclareFunction => 1 + self.byte_at(start) as usize,
+            Instruction::PushLocal | Instruction::PushOperand => self.push_args_len(start),
+            _ => 0,
+        }
+    }
+
+    /// Returns the number of argument bytes of a push instruction.
+    /// These are the arity, the reference bits, the types of the
+    /// pushed values and then the values themselves.
+    fn push_args_len(&self, start: usize) -> usize {
+        let arity = self.byte_at(start);
+        let bitmask = self.byte_at(start + 1);
+        let mut len = 2 + arity as usize;
+
+        for j in 0..arity {
+            let arg_primitive_type = self.byte_at(start + 2 + j as usize);
+
+            if bitmask.get(j) {
+                // The value is popped from a stack
+                len += 1;
+            } else if let Some(t) = VmType::from_op(arg_primitive_type) {
+                len += t.byte_size();
+            }
+        }
+
+        len
+    }
+}
+
+impl<'a> Iterator for DisassemblerIterator<'a> {
+    type Item = (usize, Instruction, Vec<u8>);
+
+    fn next(&mut self) -> Option<(usize, Instruction, Vec<u8>)> {
+        if self.offset >= self.bytes.len() {
+            return None;
+        }
+
+        let offset = self.offset;
+        let byte = self.bytes[offset];
+
+        match Instruction::from_repr(byte) {
+            Some(op) => {
+                let start = offset + 1;
+                let end = cmp::min(start + self.args_len(op, start), self.bytes.len());
+
+                self.offset = end;
+                Some((offset, op, self.bytes[start..end].to_vec()))
+            }
+            None => {
+                self.offset += 1;
+                Some((offset, Instruction::Nop, vec![byte]))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_disassembles_bytecode() {
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),
+            0x02,                             // 2 Arity
+            0x00,                             // Reference bits
+            Instruction::i32Const.repr(),
+            Instruction::i64Const.repr(),
+            0x00,                             // i32 value
+            0x00,
+            0x00,
+            0x05,
+            0x00,                             // i64 value
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x1b,
+            Instruction::Nop.repr(),
+            Instruction::GlobalGet.repr(),
+            0x00,                             // Global 1
+            0x01,
+            Instruction::End.repr()
+        ];
+
+        let result: Vec<(usize, Instruction, Vec<u8>)> = DisassemblerIterator::new(&block).collect();
+
+        assert_eq!(result, vec![
+            (0, Instruction::Begin, vec![0x00]),
+            (2, Instruction::PushOperand, block[3..19].to_vec()),
+            (19, Instruction::Nop, vec![]),
+            (20, Instruction::GlobalGet, vec![0x00, 0x01]),
+            (23, Instruction::End, vec![])
+        ]);
+    }
+
+    #[test]
+    fn it_yields_unknown_bytes_as_nop() {
+        let unknown = (0..=255u8)
+            .find(|b| Instruction::from_repr(*b).is_none())
+            .unwrap();
+        let block = vec![unknown, Instruction::Nop.repr()];
+        let result: Vec<(usize, Instruction, Vec<u8>)> =
+            DisassemblerIterator::new(&block).collect();
+
+        assert_eq!(
+            result,
+            vec![
+                (0, Instruction::Nop, vec![unknown]),
+                (1, Instruction::Nop, vec![])
+            ]
+        );
+    }
+
+    #[test]
+    fn it_truncates_arguments_at_the_end_of_the_bytecode() {
+        let block = vec![Instruction::Call.repr(), 0x00];
+        let result: Vec<(usize, Instruction, Vec<u8>)> =
+            DisassemblerIterator::new(&block).collect();
+
+        assert_eq!(result, vec![(0, Instruction::Call, vec![0x00])]);
+    }
+}
@@ -54,8 +54,10 @@ impl Function {
         match Instruction::from_repr(op) {
             Some(Instruction::Begin) => self.find_block_len(idx),
             Some(Instruction::Loop) => self.find_block_len(idx),
+            Some(Instruction::While) => self.find_block_len(idx),
             Some(Instruction::If) => self.find_block_len(idx),
             Some(Instruction::Else) => self.find_block_len(idx),
+            Some(Instruction::Block) => self.find_block_len(idx),
             _ => {
                 panic!("The length of a block can only be queried for a control flow instruction!")
             }
@@ -107,6 +109,11 @@ impl Function {
                             offset += 2;
                             result_len += 2;
                         }
+                        Instruction::Break | Instruction::BreakIf => {
+                            // Account for loop depth
+                            offset += 1;
+                            result_len += 1;
+                        }
                         Instruction::Return => {
                             offset += 1;
                             result_len += 1;
@@ -293,6 +300,7 @@ mod tests {
             0x02,                            // Arity 0
             Instruction::Eq.repr(),
             Instruction::Break.repr(),       // Break loop
+            0x00,                            // Loop depth
             Instruction::End.repr(),
             Instruction::Else.repr(),
             0x02,
@@ -328,7 +336,7 @@ mod tests {
             arguments: vec![]
         }; 
         
-        assert_eq!(function.find_block_len(72), 5);
+        assert_eq!(function.find_block_len(72), 6);
     }
 
     #[test]
@@ -415,6 +423,7 @@ mod tests {
             0x02,                            // Arity 0
             Instruction::Eq.repr(),
             Instruction::Break.repr(),       // Break loop
+            0x00,                            // Loop depth
             Instruction::End.repr(),
             Instruction::Else.repr(),
             0x02,
@@ -537,6 +546,7 @@ mod tests {
             0x02,                            // Arity 0
             Instruction::Eq.repr(),
             Instruction::Break.repr(),       // Break loop
+            0x00,                            // Loop depth
             Instruction::End.repr(),
             Instruction::Else.repr(),
             0x02,
@@ -572,7 +582,7 @@ mod tests {
             arguments: vec![]
         }; 
 
-        assert_eq!(function.find_block_len(51), 48);
+        assert_eq!(function.find_block_len(51), 49);
     }
 
     #[test]
@@ -613,6 +623,7 @@ mod tests {
             0x00,
             0x04,
             Instruction::BreakIf.repr(),
+            0x00,
             Instruction::Eq.repr(),
             Instruction::End.repr(),
             Instruction::End.repr()
@@ -626,6 +637,6 @@ mod tests {
             arguments: vec![]
         };
 
-        assert_eq!(function.find_block_len(11), 21);
+        assert_eq!(function.find_block_len(11), 22);
     }
 }
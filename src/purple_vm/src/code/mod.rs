@@ -16,12 +16,17 @@
   along with the Purple Library. If not, see <http://www.gnu.org/licenses/>.
 */
 
+pub mod control_flow_graph;
+pub mod disassembler;
 pub mod function;
+pub mod function_signature;
 pub mod import;
+pub mod section;
 pub mod transition;
+pub mod validated_bytecode;
 mod validator;
 
-use self::validator::Validator;
+pub use self::validator::Validator;
 use byteorder::{BigEndian, ReadBytesExt};
 use function::Function;
 use hashbrown::HashSet;
@@ -0,0 +1,265 @@
+/*
+  Copyright 2018 The Purple Library Authors
+  This file is part of the Purple Library.
+
+  The Purple Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use byteorder::{BigEndian, ReadBytesExt};
+use error::ValidationError;
+use primitives::r#type::VmType;
+use std::io::Cursor;
+use std::str;
+
+/// The maximum arity of a function type.
+const MAX_ARITY: u8 = 8;
+
+/// The sections of a module.
+///
+/// Every section starts with an 8bit entry count followed
+/// by the entries themselves. Everything is encoded in Big Endian.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SectionType {
+    /// An entry in the type section has the following format:
+    /// 1) Arity                 - 8bits                - The number of arguments. At most 8.
+    /// 2) Argument types        - Variable length      - The types of the arguments.
+    /// 3) Return type           - 8bits                - The return type or 0x00 for none.
+    TypeSection,
+
+    /// An entry in the function section has the following format:
+    /// 1) Function name length  - 8bits                - The length of the function name field.
+    /// 2) Function name         - Variable length      - The name of the function. Must be valid utf8.
+    /// 3) Type index            - 8bits                - The index of the function type.
+    FunctionSection,
+
+    /// An entry in the code section has the following format:
+    /// 1) Block length          - 16bits               - The length of the block field.
+    /// 2) Block                 - Variable length      - The function's block of code.
+    CodeSection,
+
+    /// An entry in the data section has the following format:
+    /// 1) Memory offset         - 32bits               - The offset at which the data is written.
+    /// 2) Data length           - 16bits               - The length of the data field.
+    /// 3) Data                  - Variable length      - The data payload.
+    DataSection,
+}
+
+/// Reads the entries of a section, keeping track of
+/// the offset of each byte in the section.
+pub(crate) struct SectionReader<'a> {
+    cursor: Cursor<&'a [u8]>,
+}
+
+impl<'a> SectionReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> SectionReader<'a> {
+        SectionReader {
+            cursor: Cursor::new(bytes),
+        }
+    }
+
+    /// Returns the offset of the next byte.
+    pub fn offset(&self) -> usize {
+        self.cursor.position() as usize
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, ValidationError> {
+        self.cursor
+            .read_u8()
+            .map_err(|_| ValidationError::Incomplete)
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, ValidationError> {
+        self.cursor
+            .read_u16::<BigEndian>()
+            .map_err(|_| ValidationError::Incomplete)
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, ValidationError> {
+        self.cursor
+            .read_u32::<BigEndian>()
+            .map_err(|_| ValidationError::Incomplete)
+    }
+
+    /// Reads the given number of bytes.
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], ValidationError> {
+        let bytes = *self.cursor.get_ref();
+        let start = self.offset();
+
+        if bytes.len() - start < len {
+            return Err(ValidationError::Incomplete);
+        }
+
+        self.cursor.set_position((start + len) as u64);
+        Ok(&bytes[start..start + len])
+    }
+
+    /// Reads a type byte, rejecting it if it doesn't denote a type.
+    fn read_type(&mut self) -> Result<VmType, ValidationError> {
+        let offset = self.offset();
+
+        match VmType::from_op(self.read_u8()?) {
+            Some(t) => Ok(t),
+            None => Err(ValidationError::InvalidByte(offset)),
+        }
+    }
+
+    /// Succeeds if all the bytes of the section have been read.
+    pub fn finish(&self) -> Result<(), ValidationError> {
+        if self.offset() == self.cursor.get_ref().len() {
+            Ok(())
+        } else {
+            Err(ValidationError::InvalidByte(self.offset()))
+        }
+    }
+}
+
+/// Validates a type section.
+pub(crate) fn validate_type_section(bytes: &[u8]) -> Result<(), ValidationError> {
+    let mut reader = SectionReader::new(bytes);
+    let count = reader.read_u8()?;
+
+    for _ in 0..count {
+        let offset = reader.offset();
+        let arity = reader.read_u8()?;
+
+        if arity > MAX_ARITY {
+            return Err(ValidationError::InvalidByte(offset));
+        }
+
+        for _ in 0..arity {
+            reader.read_type()?;
+        }
+
+        // 0x00 denotes no return type
+        let offset = reader.offset();
+        let return_type = reader.read_u8()?;
+
+        if return_type != 0x00 && VmType::from_op(return_type).is_none() {
+            return Err(ValidationError::InvalidByte(offset));
+        }
+    }
+
+    reader.finish()
+}
+
+/// Validates a function section.
+pub(crate) fn validate_function_section(bytes: &[u8]) -> Result<(), ValidationError> {
+    let mut reader = SectionReader::new(bytes);
+    let count = reader.read_u8()?;
+
+    for _ in 0..count {
+        let offset = reader.offset();
+        let name_len = reader.read_u8()?;
+
+        // Functions must be named
+        if name_len == 0 {
+            return Err(ValidationError::InvalidByte(offset));
+        }
+
+        let offset = reader.offset();
+        let name = reader.read_bytes(name_len as usize)?;
+
+        if str::from_utf8(name).is_err() {
+            return Err(ValidationError::InvalidByte(offset));
+        }
+
+        reader.read_u8()?;
+    }
+
+    reader.finish()
+}
+
+/// Validates a data section.
+pub(crate) fn validate_data_section(bytes: &[u8]) -> Result<(), ValidationError> {
+    let mut reader = SectionReader::new(bytes);
+    let count = reader.read_u8()?;
+
+    for _ in 0..count {
+        reader.read_u32()?;
+
+        let data_len = reader.read_u16()?;
+        reader.read_bytes(data_len as usize)?;
+    }
+
+    reader.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instruction_set::Instruction;
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_type_section() {
+        let section: Vec<u8> = vec![
+            0x02,                               // 2 Entries
+            0x02,                               // 2 Arity
+            Instruction::i32Const.repr(),
+            Instruction::i64Const.repr(),
+            Instruction::i32Const.repr(),       // Returns i32
+            0x00,                               // 0 Arity
+            0x00                                // No return type
+        ];
+
+        assert_eq!(validate_type_section(&section), Ok(()));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_on_invalid_argument_type() {
+        let section: Vec<u8> = vec![
+            0x01,                               // 1 Entry
+            0x01,                               // 1 Arity
+            Instruction::Nop.repr(),
+            0x00                                // No return type
+        ];
+
+        assert_eq!(validate_type_section(&section), Err(ValidationError::InvalidByte(2)));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_function_section() {
+        let section: Vec<u8> = vec![
+            0x01,                               // 1 Entry
+            0x03,                               // Name length
+            0x66,                               // "foo"
+            0x6f,
+            0x6f,
+            0x00                                // Type index
+        ];
+
+        assert_eq!(validate_function_section(&section), Ok(()));
+        assert_eq!(validate_function_section(&section[..4]), Err(ValidationError::Incomplete));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_on_trailing_bytes() {
+        let section: Vec<u8> = vec![
+            0x01,                               // 1 Entry
+            0x00,                               // Memory offset
+            0x00,
+            0x00,
+            0x10,
+            0x00,                               // Data length
+            0x01,
+            0xff,                               // Data
+            0xff                                // Trailing byte
+        ];
+
+        assert_eq!(validate_data_section(&section), Err(ValidationError::InvalidByte(8)));
+    }
+}
@@ -22,6 +22,10 @@ use instruction_set::Instruction;
 pub enum Transition {
     Op(Instruction),
     Byte(u8),
+    /// Accepts any byte in the given inclusive range.
+    Range(u8, u8),
+    /// Accepts any byte except `0x00`. Same as `Range(1, 255)`.
+    NonZeroByte,
     AnyByte,
 }
 
@@ -33,7 +37,32 @@ impl Transition {
                 None => false,
             },
             Transition::Byte(b) => b == byte,
+            Transition::Range(start, end) => start <= byte && byte <= end,
+            Transition::NonZeroByte => Transition::Range(1, 255).accepts_byte(byte),
             Transition::AnyByte => true,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_accepts_bytes_within_bounds() {
+        let transition = Transition::Range(2, 4);
+
+        assert!(!transition.accepts_byte(1));
+        assert!(transition.accepts_byte(2));
+        assert!(transition.accepts_byte(3));
+        assert!(transition.accepts_byte(4));
+        assert!(!transition.accepts_byte(5));
+    }
+
+    #[test]
+    fn non_zero_byte_rejects_zero() {
+        assert!(!Transition::NonZeroByte.accepts_byte(0x00));
+        assert!(Transition::NonZeroByte.accepts_byte(0x01));
+        assert!(Transition::NonZeroByte.accepts_byte(0xff));
+    }
+}
@@ -0,0 +1,119 @@
+/*
+  Copyright 2018 The Purple Library Authors
+  This file is part of the Purple Library.
+
+  The Purple Library is free software: you can redistribute it and/or modify
+  it under the terms of the GNU General Public License as published by
+  the Free Software Foundation, either version 3 of the License, or
+  (at your option) any later version.
+
+  The Purple Library is distributed in the hope that it will be useful,
+  but WITHOUT ANY WARRANTY; without even the implied warranty of
+  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+  GNU General Public License for more details.
+
+  You should have received a copy of the GNU General Public License
+  along with the Purple Library. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use code::validator::Validator;
+use error::ValidationError;
+use std::ops::Deref;
+
+/// A block of bytecode that has passed validation.
+///
+/// The inner bytes are private so the only way of
+/// obtaining a `ValidatedBytecode` is through `new()`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidatedBytecode(Vec<u8>);
+
+impl ValidatedBytecode {
+    /// Validates the given bytes, wrapping them on success.
+    pub fn new(bytes: Vec<u8>) -> Result<ValidatedBytecode, ValidationError> {
+        validate_bytecode(&bytes)?;
+        Ok(ValidatedBytecode(bytes))
+    }
+}
+
+impl AsRef<[u8]> for ValidatedBytecode {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Deref for ValidatedBytecode {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Runs the given bytes through a `Validator`, returning
+/// the offset of the first rejected byte on failure.
+pub fn validate_bytecode(bytes: &[u8]) -> Result<(), ValidationError> {
+    if bytes.is_empty() {
+        return Err(ValidationError::Empty);
+    }
+
+    let mut validator = Validator::new();
+    validator.push_bytes(bytes)?;
+
+    if validator.valid() {
+        Ok(())
+    } else {
+        Err(ValidationError::Incomplete)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instruction_set::Instruction;
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_wraps_valid_bytecode() {
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                           // 0 Arity
+            Instruction::Nop.repr(),
+            Instruction::End.repr()
+        ];
+
+        let bytecode = ValidatedBytecode::new(block.clone()).unwrap();
+
+        assert_eq!(bytecode.as_ref(), block.as_slice());
+        assert_eq!(&*bytecode, block.as_slice());
+        assert_eq!(bytecode.len(), 4);
+    }
+
+    #[test]
+    fn it_fails_on_empty_bytecode() {
+        assert_eq!(ValidatedBytecode::new(vec![]), Err(ValidationError::Empty));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_on_invalid_bytecode() {
+        let block: Vec<u8> = vec![
+            Instruction::Nop.repr(),
+            Instruction::Nop.repr(),
+            Instruction::End.repr()
+        ];
+
+        assert_eq!(ValidatedBytecode::new(block), Err(ValidationError::InvalidByte(0)));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_on_incomplete_bytecode() {
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                           // 0 Arity
+            Instruction::Nop.repr()
+        ];
+
+        assert_eq!(ValidatedBytecode::new(block), Err(ValidationError::Incomplete));
+    }
+}
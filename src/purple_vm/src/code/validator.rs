@@ -17,21 +17,24 @@
 */
 
 use bitvec::Bits;
+use code::function_signature::FunctionSignature;
+use code::section::{self, SectionReader, SectionType};
 use code::transition::Transition;
+use error::ValidationError;
 use frame::Frame;
 use instruction_set::{Instruction, CT_FLOW_OPS};
 use primitives::control_flow::CfOperator;
 use primitives::r#type::VmType;
 use stack::Stack;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 enum Validity {
     Valid,
     Invalid,
     IrrefutablyInvalid,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Validator {
     /// The state of the validator
     state: Validity,
@@ -53,6 +56,37 @@ pub struct Validator {
 
     /// The arity of the latest validated block
     last_arity: Option<u8>,
+
+    /// The types of the globals declared for the current module
+    global_types: Vec<VmType>,
+
+    /// Result types of the currently open `Block` frames
+    block_result_types: Stack<VmType>,
+
+    /// The signatures of the functions declared for the current module
+    function_table: Vec<FunctionSignature>,
+
+    /// The number of declared functions which have been defined
+    defined_functions: usize,
+
+    /// Whether the current module declares a linear memory
+    has_memory: bool,
+
+    /// The deepest call stack nesting reached so far
+    max_depth: usize,
+
+    /// The sizes of the function tables declared for the current module
+    table_sizes: Vec<u32>,
+
+    /// The number of successfully pushed ops
+    ops_validated: usize,
+
+    /// The maximum number of ops that can be pushed
+    max_ops: Option<usize>,
+
+    /// The reason for the validator becoming irrefutably
+    /// invalid, if it is known.
+    invalid_reason: Option<ValidationError>,
 }
 
 impl Validator {
@@ -60,12 +94,229 @@ impl Validator {
         Validator {
             state: Validity::Invalid,
             transitions: Vec::new(),
-            validation_stack: Stack::new(),
+            validation_stack: Default::default(),
             validation_buffer: Vec::new(),
-            call_stack: Stack::new(),
-            operand_stack: Stack::new(),
+            call_stack: Default::default(),
+            operand_stack: Default::default(),
             last_arity: None,
+            global_types: Vec::new(),
+            block_result_types: Default::default(),
+            function_table: Vec::new(),
+            defined_functions: 0,
+            has_memory: false,
+            max_depth: 0,
+            table_sizes: Vec::new(),
+            ops_validated: 0,
+            max_ops: None,
+            invalid_reason: None,
+        }
+    }
+
+    /// Discards the state of the block being validated so that the
+    /// validator can be reused. The module declarations and the
+    /// maximum number of ops are kept.
+    pub fn reset(&mut self) {
+        self.state = Validity::Invalid;
+        self.transitions = Vec::new();
+        self.validation_stack = Default::default();
+        self.validation_buffer = Vec::new();
+        self.call_stack = Default::default();
+        self.operand_stack = Default::default();
+        self.last_arity = None;
+        self.block_result_types = Default::default();
+        self.max_depth = 0;
+        self.ops_validated = 0;
+        self.invalid_reason = None;
+    }
+
+    /// Declares a new global of the given type. The index
+    /// of the global is its position in the declaration order.
+    pub fn declare_global(&mut self, t: VmType) {
+        self.global_types.push(t);
+    }
+
+    /// Declares a linear memory for the current module. Memory
+    /// size instructions are only valid once this is called.
+    pub fn declare_memory(&mut self) {
+        self.has_memory = true;
+    }
+
+    /// Declares a new function table of the given size. The index
+    /// of the table is its position in the declaration order.
+    pub fn declare_table(&mut self, size: u32) {
+        self.table_sizes.push(size);
+    }
+
+    /// Returns the depth of the pseudo operand stack
+    pub fn operand_stack_depth(&self) -> usize {
+        self.operand_stack.len()
+    }
+
+    /// Returns the current call stack nesting depth
+    pub fn depth(&self) -> usize {
+        self.call_stack.len()
+    }
+
+    /// Returns the deepest call stack nesting reached so far
+    pub fn max_nesting_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Limits the number of ops that can be pushed. Pushing
+    /// more than `max_ops` ops is irrefutably invalid.
+    pub fn set_max_ops(&mut self, max_ops: usize) {
+        self.max_ops = Some(max_ops);
+    }
+
+    /// Returns the number of successfully pushed ops
+    pub fn ops_validated(&self) -> usize {
+        self.ops_validated
+    }
+
+    /// Returns the reason for the validator being irrefutably
+    /// invalid, if it is known.
+    pub fn invalid_reason(&self) -> Option<&ValidationError> {
+        self.invalid_reason.as_ref()
+    }
+
+    /// Pushes the given bytes one by one, stopping at the first
+    /// one after which the validator is irrefutably invalid.
+    ///
+    /// Returns `Err(ValidationError::InvalidByte(offset))` with the
+    /// offset of that byte in `bytes`, unless a more specific reason
+    /// is known.
+    pub fn push_bytes(&mut self, bytes: &[u8]) -> Result<(), ValidationError> {
+        self.push_op_batch(bytes)
+            .map(|_| ())
+            .map_err(|(_, reason)| reason)
+    }
+
+    /// Validates the given ops in one call.
+    ///
+    /// Returns the number of consumed bytes or the offset of the first
+    /// rejected byte in `ops` along with the reason for rejecting it.
+    pub fn push_op_batch(&mut self, ops: &[u8]) -> Result<usize, (usize, ValidationError)> {
+        for (i, op) in ops.iter().enumerate() {
+            self.push_op(*op);
+
+            if self.done() {
+                let reason = match self.invalid_reason() {
+                    Some(reason) => reason.clone(),
+                    None => ValidationError::InvalidByte(i),
+                };
+
+                return Err((i, reason));
+            }
+        }
+
+        Ok(ops.len())
+    }
+
+    /// Validates a section of a module with the given type.
+    ///
+    /// Each function block of a code section is validated by a fresh
+    /// validator which shares the module declarations of this one.
+    pub fn validate_section(
+        &mut self,
+        section_type: SectionType,
+        bytes: &[u8],
+    ) -> Result<(), ValidationError> {
+        if bytes.is_empty() {
+            return Err(ValidationError::Empty);
+        }
+
+        match section_type {
+            SectionType::TypeSection => section::validate_type_section(bytes),
+            SectionType::FunctionSection => section::validate_function_section(bytes),
+            SectionType::DataSection => section::validate_data_section(bytes),
+            SectionType::CodeSection => self.validate_code_section(bytes),
+        }
+    }
+
+    fn validate_code_section(&mut self, bytes: &[u8]) -> Result<(), ValidationError> {
+        let mut reader = SectionReader::new(bytes);
+        let count = reader.read_u8()?;
+
+        for _ in 0..count {
+            let offset = reader.offset();
+            let block_len = reader.read_u16()?;
+
+            // Function blocks cannot be empty
+            if block_len == 0 {
+                return Err(ValidationError::InvalidByte(offset));
+            }
+
+            let offset = reader.offset();
+            let block = reader.read_bytes(block_len as usize)?;
+            let mut validator = Validator::new();
+
+            validator.global_types = self.global_types.clone();
+            validator.function_table = self.function_table.clone();
+            validator.has_memory = self.has_memory;
+            validator.table_sizes = self.table_sizes.clone();
+            validator.max_ops = self.max_ops;
+
+            // Report rejected bytes relative to the section
+            validator.push_bytes(block).map_err(|err| match err {
+                ValidationError::InvalidByte(i) => ValidationError::InvalidByte(offset + i),
+                err => err,
+            })?;
+
+            if !validator.valid() {
+                return Err(ValidationError::Incomplete);
+            }
+        }
+
+        reader.finish()
+    }
+
+    /// Validates each function of the given module with a fresh
+    /// validator and returns one result per function.
+    ///
+    /// A module has the following format:
+    /// 1) Function count        - 8bits                - The number of functions.
+    /// 2) Function length       - 32bits               - The length of the function field.
+    /// 3) Function              - Variable length      - The function's block of code.
+    ///
+    /// Fields 2 and 3 are repeated for each function. If the module ends
+    /// before a function is read, the result for that function is an
+    /// error and the remaining functions are not validated.
+    pub fn consume_module(bytes: &[u8]) -> Vec<Result<(), ValidationError>> {
+        let mut reader = SectionReader::new(bytes);
+        let mut results = Vec::new();
+
+        let count = match reader.read_u8() {
+            Ok(count) => count,
+            Err(_) => return results,
+        };
+
+        for _ in 0..count {
+            let block = match reader.read_u32() {
+                Ok(len) => reader.read_bytes(len as usize),
+                Err(err) => Err(err),
+            };
+
+            let block = match block {
+                Ok(block) => block,
+                Err(err) => {
+                    results.push(Err(err));
+                    break;
+                }
+            };
+
+            let mut validator = Validator::new();
+            let result = validator.push_bytes(block).and_then(|_| {
+                if validator.valid() {
+                    Ok(())
+                } else {
+                    Err(ValidationError::Incomplete)
+                }
+            });
+
+            results.push(result);
         }
+
+        results
     }
 
     pub fn push_op(&mut self, op: u8) {
@@ -73,6 +324,12 @@ impl Validator {
             panic!("Cannot switch state since the state machine is DONE.");
         }
 
+        if self.ops_validated >= self.max_ops.unwrap_or(usize::MAX) {
+            self.state = Validity::IrrefutablyInvalid;
+            self.invalid_reason = Some(ValidationError::TooManyInstructions);
+            return;
+        }
+
         // If the control flow stack is empty,
         // only accept a begin instruction.
         if self.call_stack.len() == 0 {
@@ -91,6 +348,10 @@ impl Validator {
                     // is always 0x00, representing 0 arity.
                     self.transitions = vec![Transition::Byte(0x00)];
                 }
+                None => {
+                    self.state = Validity::IrrefutablyInvalid;
+                    self.invalid_reason = Some(ValidationError::UnknownOpcode(op));
+                }
                 _ => {
                     // The first instruction can only be a begin instruction
                     // so there is nothing more to do at this point.
@@ -119,16 +380,33 @@ impl Validator {
 
                     // If op is `End`, pop frame from stack.
                     if let Instruction::End = op {
+                        let frame = self.call_stack.pop();
+                        let returned = frame.return_types.len();
+
+                        // The topmost operands must match the return types of the frame
+                        if self.operand_stack.len() < returned
+                            || &self.operand_stack.as_slice()[self.operand_stack.len() - returned..]
+                                != frame.return_types.as_slice()
                         {
-                            let frame = self.call_stack.peek();
+                            self.state = Validity::IrrefutablyInvalid;
+                            return;
+                        }
 
-                            if let Some(CfOperator::If) = frame.scope_type {
+                        match frame.scope_type {
+                            Some(CfOperator::If) => {
                                 // Allow else in case of if
                                 allow_else = true;
                             }
+                            Some(CfOperator::Block) => {
+                                // A block leaves exactly one operand
+                                // of its declared result type.
+                                let result_type = self.block_result_types.pop();
+                                self.operand_stack.push(result_type);
+                            }
+                            _ => {
+                                // Do nothing
+                            }
                         }
-
-                        self.call_stack.pop();
                     }
 
                     // Changes state to `Valid` if the stack is empty.
@@ -158,12 +436,148 @@ impl Validator {
 
                                 vec![Transition::AnyByte]
                             }
-                            Instruction::Loop => {
+                            Instruction::BranchTable => {
+                                // Mark op for argument validation
+                                self.validation_stack
+                                    .push((Instruction::BranchTable.repr(), true));
+
+                                // The next byte is the number of targets
+                                vec![Transition::AnyByte]
+                            }
+                            Instruction::TableGet | Instruction::TableSet => {
+                                // Mark op for argument validation
+                                self.validation_stack.push((op.repr(), true));
+
+                                vec![Transition::AnyByte]
+                            }
+                            Instruction::GlobalGet => {
+                                // Mark op for argument validation
+                                self.validation_stack
+                                    .push((Instruction::GlobalGet.repr(), true));
+
+                                vec![Transition::AnyByte]
+                            }
+                            Instruction::GlobalSet => {
+                                // Mark op for argument validation
+                                self.validation_stack
+                                    .push((Instruction::GlobalSet.repr(), true));
+
+                                vec![Transition::AnyByte]
+                            }
+                            Instruction::MemLoad => {
+                                // Mark op for argument validation
+                                self.validation_stack
+                                    .push((Instruction::MemLoad.repr(), true));
+
+                                vec![Transition::AnyByte]
+                            }
+                            Instruction::MemStore => {
+                                // Mark op for argument validation
+                                self.validation_stack
+                                    .push((Instruction::MemStore.repr(), true));
+
+                                vec![Transition::AnyByte]
+                            }
+                            Instruction::And
+                            | Instruction::Or
+                            | Instruction::Xor
+                            | Instruction::Not
+                            | Instruction::Shl
+                            | Instruction::ShrSigned
+                            | Instruction::ShrUnsigned
+                            | Instruction::Rotl
+                            | Instruction::Rotr
+                            | Instruction::Clz
+                            | Instruction::Ctz
+                            | Instruction::Popcnt
+                            | Instruction::Min
+                            | Instruction::Max
+                            | Instruction::CopySign
+                            | Instruction::Sqrt
+                            | Instruction::Abs
+                            | Instruction::Neg
+                            | Instruction::Ceil
+                            | Instruction::Floor
+                            | Instruction::Trunc
+                            | Instruction::Nearest
+                            | Instruction::Eqz => {
+                                // Mark op for argument validation
+                                self.validation_stack.push((op.repr(), true));
+
+                                // The next byte is the type of the operands
+                                ARG_DECLARATIONS.to_vec()
+                            }
+                            Instruction::Compare => {
+                                // Mark op for argument validation
+                                self.validation_stack
+                                    .push((Instruction::Compare.repr(), true));
+
+                                // The next byte is the type of the operands
+                                ARG_DECLARATIONS.to_vec()
+                            }
+                            Instruction::PushI32Const
+                            | Instruction::PushI64Const
+                            | Instruction::PushF32Const
+                            | Instruction::PushF64Const => {
+                                // Mark op for argument validation
+                                self.validation_stack.push((op.repr(), true));
+
+                                // The next bytes are the value of the constant
+                                vec![Transition::AnyByte]
+                            }
+                            Instruction::Convert => {
+                                // Mark op for argument validation
+                                self.validation_stack
+                                    .push((Instruction::Convert.repr(), true));
+
+                                // The next two bytes are the source and target types
+                                vec![Transition::AnyByte]
+                            }
+                            Instruction::Begin => {
+                                // Mark op for argument validation
+                                self.validation_stack
+                                    .push((Instruction::Begin.repr(), true));
+
+                                ARITY_TRANSITIONS.to_vec()
+                            }
+                            Instruction::Call => {
+                                // Mark op for argument validation
+                                self.validation_stack.push((Instruction::Call.repr(), true));
+
+                                // The next two bytes are the function index
+                                vec![Transition::AnyByte]
+                            }
+                            Instruction::DeclareFunction => {
+                                // Mark op for argument validation
+                                self.validation_stack
+                                    .push((Instruction::DeclareFunction.repr(), true));
+
+                                ARITY_TRANSITIONS.to_vec()
+                            }
+                            Instruction::Break | Instruction::BreakIf => {
                                 // Mark op for argument validation
-                                self.validation_stack.push((Instruction::Loop.repr(), true));
+                                self.validation_stack.push((op.repr(), true));
+
+                                // The next byte is the depth of the targeted loop,
+                                // counting from the innermost one.
+                                let loops = self.call_stack.iter().filter(|f| f.is_loop()).count();
+
+                                (0..loops).map(|d| Transition::Byte(d as u8)).collect()
+                            }
+                            Instruction::Loop | Instruction::While => {
+                                // Mark op for argument validation
+                                self.validation_stack.push((op.repr(), true));
 
                                 ARITY_TRANSITIONS.to_vec()
                             }
+                            Instruction::Block => {
+                                // Mark op for argument validation
+                                self.validation_stack
+                                    .push((Instruction::Block.repr(), true));
+
+                                // The next byte is the result type of the block
+                                ARG_DECLARATIONS.to_vec()
+                            }
                             Instruction::If => {
                                 // Mark op for argument validation
                                 self.validation_stack.push((Instruction::If.repr(), true));
@@ -176,14 +590,73 @@ impl Validator {
 
                                 ARITY_TRANSITIONS.to_vec()
                             }
+                            Instruction::Swap => {
+                                self.validate_swap();
+
+                                if self.done() {
+                                    return;
+                                }
+
+                                op.transitions()
+                            }
+                            Instruction::Rot => {
+                                self.validate_rot();
+
+                                if self.done() {
+                                    return;
+                                }
+
+                                op.transitions()
+                            }
+                            Instruction::MemGrow | Instruction::MemSize => {
+                                self.validate_memory_size_op(op);
+
+                                if self.done() {
+                                    return;
+                                }
+
+                                op.transitions()
+                            }
+                            Instruction::i32Wrapi64
+                            | Instruction::i64ExtendSignedi32
+                            | Instruction::i64ExtendUnsignedi32
+                            | Instruction::f32Demotef64
+                            | Instruction::f64Promotef32
+                            | Instruction::i32TruncSignedf32
+                            | Instruction::i32TruncSignedf64
+                            | Instruction::i64TruncSignedf32
+                            | Instruction::i64TruncSignedf64
+                            | Instruction::i32Reinterpretf32
+                            | Instruction::i64Reinterpretf64
+                            | Instruction::f32Reinterpreti32
+                            | Instruction::f64Reinterpreti64 => {
+                                self.validate_conversion(op);
+
+                                if self.done() {
+                                    return;
+                                }
+
+                                op.transitions()
+                            }
+                            Instruction::UntypedSelect => {
+                                self.validate_untyped_select();
+
+                                if self.done() {
+                                    return;
+                                }
+
+                                op.transitions()
+                            }
+                            Instruction::StackDepth => {
+                                // The depth is pushed as an `I32`
+                                self.operand_stack.push(VmType::I32);
+
+                                op.transitions()
+                            }
                             _ => op.transitions(),
                         };
 
-                        let has_loop = self
-                            .call_stack
-                            .as_slice()
-                            .iter()
-                            .any(|o| o.scope_type == Some(CfOperator::Loop));
+                        let has_loop = self.call_stack.iter().any(|o| o.is_loop());
 
                         let last_was_if = self.transitions.iter().any(|t| {
                             if let Transition::Op(Instruction::Else) = t {
@@ -207,16 +680,29 @@ impl Validator {
                             }
                         }
 
+                        // A function body is a `Begin`-like frame which
+                        // receives the arguments of its declared signature.
+                        if let Instruction::DefineFunction = op {
+                            self.define_function();
+
+                            if self.done() {
+                                return;
+                            }
+                        }
+
+                        // Ops are not accepted while validating arguments
+                        let validating_args = !self.validation_stack.is_empty();
+
                         // If there is any loop operator in the stack,
                         // allow `Break` and `BreakIf` instructions.
-                        if has_loop {
+                        if has_loop && !validating_args {
                             next.push(Transition::Op(Instruction::Break));
                             next.push(Transition::Op(Instruction::BreakIf));
                         }
 
                         // Allow `Else` op in case the topmost item
                         // in the stack was an `If` instruction.
-                        if allow_else {
+                        if allow_else && !validating_args {
                             next.push(Transition::Op(Instruction::Else));
                         }
 
@@ -224,7 +710,10 @@ impl Validator {
                         next_transitions = Some(next);
                     }
                 }
-                Some(Transition::Byte(_)) | Some(Transition::AnyByte) => {
+                Some(Transition::Byte(_))
+                | Some(Transition::Range(_, _))
+                | Some(Transition::NonZeroByte)
+                | Some(Transition::AnyByte) => {
                     let (operand, _) = self.validation_stack.as_slice()[0];
 
                     match Instruction::from_repr(operand) {
@@ -233,20 +722,24 @@ impl Validator {
                                 panic!(format!("The validation stack can only have 1 element at this point! Got: {}", self.validation_stack.len()));
                             }
 
-                            let byte = if let Some(Transition::Byte(byte)) = transition {
-                                byte
-                            } else {
-                                panic!("Invalid transition! Expected a byte transition!");
-                            };
+                            let byte = op;
 
                             self.validation_stack.pop();
 
+                            // The frame of the first begin block is pushed before
+                            // validating its arity, which can only be `0x00`. Nested
+                            // begin blocks accept any arity in `ARITY_TRANSITIONS`.
+                            let is_first_begin = match transition {
+                                Some(Transition::Byte(0x00)) => self.call_stack.len() == 1,
+                                _ => false,
+                            };
+
                             // Only allow 0 arity for first begin block
-                            if self.call_stack.len() == 1 && byte == 0x00 {
+                            if is_first_begin && byte == 0x00 {
                                 // Continue validation
                                 self.state = Validity::Invalid;
                                 next_transitions = Some(Instruction::Begin.transitions());
-                            } else if self.call_stack.len() == 1 {
+                            } else if is_first_begin {
                                 // The arity is not 0 so anything further
                                 // is invalid as well.
                                 self.state = Validity::IrrefutablyInvalid;
@@ -254,8 +747,8 @@ impl Validator {
                                 let valid = ARITY_TRANSITIONS.iter().find(|t| t.accepts_byte(op));
 
                                 match valid {
-                                    Some(Transition::Byte(arity)) => {
-                                        let arity = *arity;
+                                    Some(_) => {
+                                        let arity = op;
 
                                         self.last_arity = Some(arity);
 
@@ -275,11 +768,16 @@ impl Validator {
                                                 buf.reverse();
                                             }
 
-                                            self.call_stack.push(Frame::new(
+                                            let mut frame = Frame::new(
                                                 Some(CfOperator::Begin),
                                                 None,
                                                 Some(buf),
-                                            ));
+                                            );
+
+                                            // A `Begin` frame must leave its arguments
+                                            // on the operand stack once it is ended.
+                                            frame.return_types = frame.argument_types.clone();
+                                            self.call_stack.push(frame);
 
                                             // Continue validation
                                             self.state = Validity::Invalid;
@@ -295,7 +793,7 @@ impl Validator {
                                 }
                             }
                         }
-                        Some(Instruction::Loop) => {
+                        Some(Instruction::Loop) | Some(Instruction::While) => {
                             if self.validation_stack.len() != 1 {
                                 panic!(format!("The validation stack can only have 1 element at this point! Got: {}", self.validation_stack.len()));
                             }
@@ -305,8 +803,8 @@ impl Validator {
                             let valid = ARITY_TRANSITIONS.iter().find(|t| t.accepts_byte(op));
 
                             match valid {
-                                Some(Transition::Byte(arity)) => {
-                                    let arity = *arity;
+                                Some(_) => {
+                                    let arity = op;
 
                                     self.last_arity = Some(arity);
 
@@ -326,8 +824,16 @@ impl Validator {
                                             buf.reverse();
                                         }
 
+                                        // A `While` frame is managed just like a `Loop`
+                                        // frame but is kept distinguishable from it.
+                                        let scope_type = if operand == Instruction::While.repr() {
+                                            CfOperator::While
+                                        } else {
+                                            CfOperator::Loop
+                                        };
+
                                         self.call_stack.push(Frame::new(
-                                            Some(CfOperator::Loop),
+                                            Some(scope_type),
                                             None,
                                             Some(buf),
                                         ));
@@ -345,10 +851,10 @@ impl Validator {
                             }
                         }
                         Some(Instruction::PushOperand) => {
-                            self.validate_push(op, &transition, &mut next_transitions);
+                            self.validate_push(op, &mut next_transitions);
                         }
                         Some(Instruction::PushLocal) => {
-                            self.validate_push(op, &transition, &mut next_transitions);
+                            self.validate_push(op, &mut next_transitions);
                         }
                         Some(Instruction::PickLocal) => {
                             self.validation_buffer.push(op);
@@ -372,6 +878,95 @@ impl Validator {
                                 }
                             }
                         }
+                        Some(Instruction::Block) => {
+                            if self.validation_stack.len() != 1 {
+                                panic!(format!("The validation stack can only have 1 element at this point! Got: {}", self.validation_stack.len()));
+                            }
+
+                            self.validation_stack.pop();
+
+                            match VmType::from_op(op) {
+                                Some(result_type) => {
+                                    self.block_result_types.push(result_type);
+                                    self.call_stack.push(Frame::new(
+                                        Some(CfOperator::Block),
+                                        None,
+                                        None,
+                                    ));
+
+                                    // Continue validation
+                                    self.state = Validity::Invalid;
+                                    next_transitions = Some(Instruction::Block.transitions());
+                                }
+                                None => {
+                                    self.state = Validity::IrrefutablyInvalid;
+                                }
+                            }
+                        }
+                        Some(Instruction::BranchTable) => {
+                            self.validate_branch_table(op, &mut next_transitions);
+                        }
+                        Some(Instruction::TableGet) | Some(Instruction::TableSet) => {
+                            self.validate_table_access(op, &mut next_transitions);
+                        }
+                        Some(Instruction::GlobalGet) | Some(Instruction::GlobalSet) => {
+                            self.validate_global(op, &mut next_transitions);
+                        }
+                        Some(Instruction::PushI32Const)
+                        | Some(Instruction::PushI64Const)
+                        | Some(Instruction::PushF32Const)
+                        | Some(Instruction::PushF64Const) => {
+                            self.validate_typed_const(op, &mut next_transitions);
+                        }
+                        Some(Instruction::MemLoad) | Some(Instruction::MemStore) => {
+                            self.validate_memory_access(op, &mut next_transitions);
+                        }
+                        Some(Instruction::And)
+                        | Some(Instruction::Or)
+                        | Some(Instruction::Xor)
+                        | Some(Instruction::Not)
+                        | Some(Instruction::Shl)
+                        | Some(Instruction::ShrSigned)
+                        | Some(Instruction::ShrUnsigned)
+                        | Some(Instruction::Rotl)
+                        | Some(Instruction::Rotr)
+                        | Some(Instruction::Clz)
+                        | Some(Instruction::Ctz)
+                        | Some(Instruction::Popcnt)
+                        | Some(Instruction::Min)
+                        | Some(Instruction::Max)
+                        | Some(Instruction::CopySign)
+                        | Some(Instruction::Sqrt)
+                        | Some(Instruction::Abs)
+                        | Some(Instruction::Neg)
+                        | Some(Instruction::Ceil)
+                        | Some(Instruction::Floor)
+                        | Some(Instruction::Trunc)
+                        | Some(Instruction::Nearest)
+                        | Some(Instruction::Eqz) => {
+                            self.validate_typed_op(op, &mut next_transitions);
+                        }
+                        Some(Instruction::Compare) => {
+                            self.validate_compare(op, &mut next_transitions);
+                        }
+                        Some(Instruction::Convert) => {
+                            self.validate_convert(op, &mut next_transitions);
+                        }
+                        Some(Instruction::Break) | Some(Instruction::BreakIf) => {
+                            // The depth has been validated by the transitions
+                            self.validation_stack.pop();
+
+                            // Continue validating
+                            self.state = Validity::Invalid;
+                            next_transitions =
+                                Some(Instruction::from_repr(operand).unwrap().transitions());
+                        }
+                        Some(Instruction::Call) => {
+                            self.validate_call(op, &mut next_transitions);
+                        }
+                        Some(Instruction::DeclareFunction) => {
+                            self.validate_function_declaration(op, &mut next_transitions);
+                        }
                         Some(Instruction::If) => {
                             if self.validation_stack.len() != 1 {
                                 panic!(format!("The validation stack can only have 1 element at this point! Got: {}", self.validation_stack.len()));
@@ -382,8 +977,8 @@ impl Validator {
                             let valid = ARITY_TRANSITIONS.iter().find(|t| t.accepts_byte(op));
 
                             match valid {
-                                Some(Transition::Byte(arity)) => {
-                                    let arity = *arity;
+                                Some(_) => {
+                                    let arity = op;
 
                                     self.last_arity = Some(arity);
 
@@ -437,8 +1032,8 @@ impl Validator {
                             let valid = ARITY_TRANSITIONS.iter().find(|t| t.accepts_byte(op));
 
                             match valid {
-                                Some(Transition::Byte(arity)) => {
-                                    let arity = *arity;
+                                Some(_) => {
+                                    let arity = op;
 
                                     self.last_arity = Some(arity);
 
@@ -481,6 +1076,16 @@ impl Validator {
                 }
                 None => {
                     self.state = Validity::IrrefutablyInvalid;
+
+                    let expects_op = self.transitions.iter().any(|t| match t {
+                        Transition::Op(_) => true,
+                        _ => false,
+                    });
+
+                    // Only report the opcode if the byte is not an argument
+                    if expects_op && Instruction::is_reserved(op) {
+                        self.invalid_reason = Some(ValidationError::UnknownOpcode(op));
+                    }
                 }
             }
 
@@ -489,6 +1094,15 @@ impl Validator {
                 self.transitions = next_transitions;
             }
         }
+
+        // Update the nesting high-water mark
+        if self.call_stack.len() > self.max_depth {
+            self.max_depth = self.call_stack.len();
+        }
+
+        if !self.done() {
+            self.ops_validated += 1;
+        }
     }
 
     pub fn done(&self) -> bool {
@@ -505,65 +1119,647 @@ impl Validator {
         }
     }
 
-    fn validate_push(
-        &mut self,
-        op: u8,
-        transition: &Option<Transition>,
-        next_transitions: &mut Option<Vec<Transition>>,
-    ) {
-        // Based on the length of the validation stack,
-        // we perform different validations.
-        match self.validation_stack.len() {
-            // Validate arity
-            1 => {
-                let arity = if let Some(Transition::Byte(byte)) = transition {
-                    byte
-                } else {
-                    panic!("Invalid transition! Expected a byte transition!");
-                };
+    fn validate_branch_table(&mut self, op: u8, next_transitions: &mut Option<Vec<Transition>>) {
+        // The first byte after the op is the targets count
+        if self.validation_stack.len() == 1 {
+            self.validation_stack.push((op, true));
+        } else {
+            self.validation_buffer.push(op);
 
-                // Push arity to validation stack
-                self.validation_stack.push((*arity, true));
+            // Each target is a 16bit index into the call stack
+            if self.validation_buffer.len() == 2 {
+                let target = decode_be_u16!(&self.validation_buffer).unwrap() as usize;
 
-                // Continue validating
-                self.state = Validity::Invalid;
+                // Cleanup
+                self.validation_buffer = vec![];
 
-                // Next byte will be the bitmask so we allow any
-                *next_transitions = Some(vec![Transition::AnyByte]);
-            }
+                // We cannot jump outside the current call stack
+                if target >= self.call_stack.len() {
+                    self.state = Validity::IrrefutablyInvalid;
+                    self.validation_stack = Stack::new();
 
-            // Validate bitmask
-            2 => {
-                let bitmask = op;
+                    return;
+                }
 
-                // Push bitmask to validation stack
-                self.validation_stack.push((bitmask, true));
+                // Mark target as validated
+                self.validation_stack.push((op, true));
+            }
+        }
 
-                // Continue validating
-                self.state = Validity::Invalid;
+        let (count, _) = self.validation_stack.as_slice()[1];
+        let validated_targets = self.validation_stack.len() - 2;
 
-                // The next transitions are the argument types
-                *next_transitions = Some(ARG_DECLARATIONS.to_vec());
+        if self.validation_buffer.is_empty() && validated_targets == count as usize {
+            // Cleanup
+            self.validation_stack = Stack::new();
+
+            // Consume the dispatch value
+            if self.operand_stack.is_empty() || *self.operand_stack.peek() != VmType::I32 {
+                self.state = Validity::IrrefutablyInvalid;
+                return;
             }
 
-            len => {
-                let (arity, _) = self.validation_stack.as_slice()[1];
+            self.operand_stack.pop();
+            *next_transitions = Some(Instruction::BranchTable.transitions());
+        }
 
-                // This is the intended length of the validation stack
-                let offset = (arity + 2) as usize;
+        // Continue validating
+        self.state = Validity::Invalid;
+    }
 
-                if len >= 3 && len <= offset {
-                    // Validate argument types
-                    self.validation_stack.push((op, false));
+    fn validate_table_access(&mut self, op: u8, next_transitions: &mut Option<Vec<Transition>>) {
+        let (table_op, _) = self.validation_stack.as_slice()[0];
 
-                    if len == offset {
-                        let (bitmask, _) = self.validation_stack.as_slice()[2];
+        self.validation_buffer.push(op);
 
-                        if bitmask.get(0) {
-                            // The first argument is popped from a stack
-                            // so we only allow pop operations.
-                            *next_transitions = Some(vec![
-                                Transition::Byte(Instruction::PopLocal.repr()),
+        match (
+            Instruction::from_repr(table_op),
+            self.validation_buffer.len(),
+        ) {
+            // The first two bytes are the 16bit index of the table
+            (_, 2) => {
+                let idx = decode_be_u16!(&self.validation_buffer[..2]).unwrap() as usize;
+
+                // The table must be declared
+                if idx >= self.table_sizes.len() {
+                    self.state = Validity::IrrefutablyInvalid;
+                    self.validation_buffer = vec![];
+                    self.validation_stack = Stack::new();
+
+                    return;
+                }
+
+                if let Some(Instruction::TableGet) = Instruction::from_repr(table_op) {
+                    // Cleanup
+                    self.validation_buffer = vec![];
+                    self.validation_stack = Stack::new();
+
+                    // Push the function reference
+                    self.operand_stack.push(VmType::I32);
+                    *next_transitions = Some(Instruction::Begin.transitions());
+                }
+            }
+            // `TableSet` is followed by the 16bit index of the element
+            (Some(Instruction::TableSet), 4) => {
+                let idx = decode_be_u16!(&self.validation_buffer[..2]).unwrap() as usize;
+                let elem_idx = decode_be_u16!(&self.validation_buffer[2..]).unwrap() as u32;
+
+                // Cleanup
+                self.validation_buffer = vec![];
+                self.validation_stack = Stack::new();
+
+                // The element must be within the bounds of the table
+                if elem_idx >= self.table_sizes[idx] {
+                    self.state = Validity::IrrefutablyInvalid;
+                    return;
+                }
+
+                // Pop the function reference
+                if self.operand_stack.is_empty() || *self.operand_stack.peek() != VmType::I32 {
+                    self.state = Validity::IrrefutablyInvalid;
+                    return;
+                }
+
+                self.operand_stack.pop();
+                *next_transitions = Some(Instruction::Begin.transitions());
+            }
+            _ => {
+                // Do nothing
+            }
+        }
+
+        // Continue validating
+        self.state = Validity::Invalid;
+    }
+
+    fn validate_global(&mut self, op: u8, next_transitions: &mut Option<Vec<Transition>>) {
+        self.validation_buffer.push(op);
+
+        // The index of the global is a 16bit integer
+        if self.validation_buffer.len() == 2 {
+            let (global_op, _) = self.validation_stack.as_slice()[0];
+            let idx = decode_be_u16!(&self.validation_buffer).unwrap() as usize;
+
+            // Cleanup
+            self.validation_buffer = vec![];
+            self.validation_stack = Stack::new();
+
+            // The global must be declared
+            if idx >= self.global_types.len() {
+                self.state = Validity::IrrefutablyInvalid;
+                return;
+            }
+
+            let global_type = self.global_types[idx];
+
+            match Instruction::from_repr(global_op) {
+                Some(Instruction::GlobalGet) => {
+                    // Push the declared type to the operand stack
+                    self.operand_stack.push(global_type);
+                }
+                Some(Instruction::GlobalSet) => {
+                    // The topmost operand must match the declared type
+                    if self.operand_stack.is_empty() || *self.operand_stack.peek() != global_type {
+                        self.state = Validity::IrrefutablyInvalid;
+                        return;
+                    }
+
+                    self.operand_stack.pop();
+                }
+                _ => unreachable!(),
+            }
+
+            *next_transitions = Some(Instruction::Begin.transitions());
+            self.state = Validity::Invalid;
+        }
+    }
+
+    fn validate_typed_const(&mut self, op: u8, next_transitions: &mut Option<Vec<Transition>>) {
+        self.validation_buffer.push(op);
+
+        let (const_op, _) = self.validation_stack.as_slice()[0];
+        let const_type = match Instruction::from_repr(const_op) {
+            Some(Instruction::PushI32Const) => VmType::I32,
+            Some(Instruction::PushI64Const) => VmType::I64,
+            Some(Instruction::PushF32Const) => VmType::F32,
+            Some(Instruction::PushF64Const) => VmType::F64,
+            _ => unreachable!(),
+        };
+
+        // The value of the constant is encoded
+        // with the byte size of its type.
+        if self.validation_buffer.len() == const_type.byte_size() {
+            // Cleanup
+            self.validation_buffer = vec![];
+            self.validation_stack = Stack::new();
+
+            self.operand_stack.push(const_type);
+
+            *next_transitions = Some(Instruction::Begin.transitions());
+        }
+
+        // Continue validating
+        self.state = Validity::Invalid;
+    }
+
+    fn validate_memory_access(&mut self, op: u8, next_transitions: &mut Option<Vec<Transition>>) {
+        self.validation_buffer.push(op);
+
+        match self.validation_buffer.len() {
+            // The first argument is the type of the accessed value
+            1 => {
+                if VmType::from_op(op).is_none() {
+                    self.state = Validity::IrrefutablyInvalid;
+                    self.validation_buffer = vec![];
+                    self.validation_stack = Stack::new();
+
+                    return;
+                }
+            }
+            // The second argument is the alignment hint
+            2 => {
+                if !MEM_ALIGNMENTS.contains(&op) {
+                    self.state = Validity::IrrefutablyInvalid;
+                    self.validation_buffer = vec![];
+                    self.validation_stack = Stack::new();
+
+                    return;
+                }
+            }
+            // The last four bytes are the 32bit static
+            // offset, any value of which is accepted.
+            6 => {
+                let (mem_op, _) = self.validation_stack.as_slice()[0];
+                let value_type = VmType::from_op(self.validation_buffer[0]).unwrap();
+
+                // Cleanup
+                self.validation_buffer = vec![];
+                self.validation_stack = Stack::new();
+
+                match Instruction::from_repr(mem_op) {
+                    Some(Instruction::MemLoad) => {
+                        // Push the loaded value to the operand stack
+                        self.operand_stack.push(value_type);
+                    }
+                    Some(Instruction::MemStore) => {
+                        // The topmost operand is the stored value
+                        // followed by the address it is stored at.
+                        if self.operand_stack.len() < 2 {
+                            self.state = Validity::IrrefutablyInvalid;
+                            return;
+                        }
+
+                        let value = *self.operand_stack.peek_nth(0);
+                        let address = *self.operand_stack.peek_nth(1);
+
+                        if value != value_type || address != VmType::I32 {
+                            self.state = Validity::IrrefutablyInvalid;
+                            return;
+                        }
+
+                        self.operand_stack.pop();
+                        self.operand_stack.pop();
+                    }
+                    _ => unreachable!(),
+                }
+
+                *next_transitions = Some(Instruction::Begin.transitions());
+            }
+            _ => {
+                // Do nothing
+            }
+        }
+
+        // Continue validating
+        self.state = Validity::Invalid;
+    }
+
+    fn validate_typed_op(&mut self, op: u8, next_transitions: &mut Option<Vec<Transition>>) {
+        let (typed_op, _) = self.validation_stack.as_slice()[0];
+        let operand_type = VmType::from_op(op).unwrap();
+
+        // Cleanup
+        self.validation_stack = Stack::new();
+
+        // The accepted operand types and the number of popped operands
+        let (accepted_types, operands_count) = match Instruction::from_repr(typed_op) {
+            Some(Instruction::And) | Some(Instruction::Or) | Some(Instruction::Xor) => {
+                (INTEGER_TYPES, 2)
+            }
+            Some(Instruction::Not)
+            | Some(Instruction::Clz)
+            | Some(Instruction::Ctz)
+            | Some(Instruction::Popcnt)
+            | Some(Instruction::Eqz) => (INTEGER_TYPES, 1),
+            // The value is followed by the shift amount
+            Some(Instruction::Shl)
+            | Some(Instruction::ShrSigned)
+            | Some(Instruction::ShrUnsigned)
+            | Some(Instruction::Rotl)
+            | Some(Instruction::Rotr) => (INTEGER_TYPES, 2),
+            // `CopySign` receives the magnitude followed by the sign
+            Some(Instruction::Min) | Some(Instruction::Max) | Some(Instruction::CopySign) => {
+                (FLOAT_TYPES, 2)
+            }
+            Some(Instruction::Sqrt)
+            | Some(Instruction::Abs)
+            | Some(Instruction::Neg)
+            | Some(Instruction::Ceil)
+            | Some(Instruction::Floor)
+            | Some(Instruction::Trunc)
+            | Some(Instruction::Nearest) => (FLOAT_TYPES, 1),
+            _ => unreachable!(),
+        };
+
+        if !accepted_types.contains(&operand_type) || self.operand_stack.len() < operands_count {
+            self.state = Validity::IrrefutablyInvalid;
+            return;
+        }
+
+        // All operands must be of the declared type
+        for i in 0..operands_count {
+            if *self.operand_stack.peek_nth(i) != operand_type {
+                self.state = Validity::IrrefutablyInvalid;
+                return;
+            }
+        }
+
+        for _ in 0..operands_count {
+            self.operand_stack.pop();
+        }
+
+        // Testing against zero always yields an `I32`
+        match Instruction::from_repr(typed_op) {
+            Some(Instruction::Eqz) => self.operand_stack.push(VmType::I32),
+            _ => self.operand_stack.push(operand_type),
+        }
+
+        *next_transitions = Some(Instruction::Begin.transitions());
+
+        // Continue validating
+        self.state = Validity::Invalid;
+    }
+
+    fn validate_compare(&mut self, op: u8, next_transitions: &mut Option<Vec<Transition>>) {
+        self.validation_buffer.push(op);
+
+        match self.validation_buffer.len() {
+            // The first argument is the type of the operands.
+            // The next byte is the comparison kind.
+            1 => {
+                *next_transitions = Some(vec![Transition::AnyByte]);
+            }
+            2 => {
+                let operand_type = VmType::from_op(self.validation_buffer[0]).unwrap();
+
+                // Cleanup
+                self.validation_buffer = vec![];
+                self.validation_stack = Stack::new();
+
+                if op >= COMPARISON_KINDS {
+                    self.state = Validity::IrrefutablyInvalid;
+                    return;
+                }
+
+                // Both operands must be of the declared type
+                if self.operand_stack.len() < 2 {
+                    self.state = Validity::IrrefutablyInvalid;
+                    return;
+                }
+
+                if *self.operand_stack.peek_nth(0) != operand_type
+                    || *self.operand_stack.peek_nth(1) != operand_type
+                {
+                    self.state = Validity::IrrefutablyInvalid;
+                    return;
+                }
+
+                self.operand_stack.pop();
+                self.operand_stack.pop();
+
+                // The result of a comparison is always an `I32`
+                self.operand_stack.push(VmType::I32);
+                *next_transitions = Some(Instruction::Begin.transitions());
+            }
+            _ => unreachable!(),
+        }
+
+        // Continue validating
+        self.state = Validity::Invalid;
+    }
+
+    fn validate_convert(&mut self, op: u8, next_transitions: &mut Option<Vec<Transition>>) {
+        self.validation_buffer.push(op);
+
+        if self.validation_buffer.len() == 2 {
+            let source = VmType::from_op(self.validation_buffer[0]);
+            let target = VmType::from_op(self.validation_buffer[1]);
+
+            // Cleanup
+            self.validation_buffer = vec![];
+            self.validation_stack = Stack::new();
+
+            let (source, target) = match (source, target) {
+                (Some(source), Some(target)) => (source, target),
+                _ => {
+                    self.state = Validity::IrrefutablyInvalid;
+                    return;
+                }
+            };
+
+            if !VALID_CONVERSIONS.contains(&(source, target)) {
+                self.state = Validity::IrrefutablyInvalid;
+                return;
+            }
+
+            // The converted operand must be of the source type
+            if self.operand_stack.is_empty() || *self.operand_stack.peek() != source {
+                self.state = Validity::IrrefutablyInvalid;
+                return;
+            }
+
+            self.operand_stack.pop();
+            self.operand_stack.push(target);
+            *next_transitions = Some(Instruction::Begin.transitions());
+        }
+
+        // Continue validating
+        self.state = Validity::Invalid;
+    }
+
+    fn validate_swap(&mut self) {
+        // Only operands of the same type can be swapped
+        if self.operand_stack.len() < 2
+            || self.operand_stack.peek_nth(0) != self.operand_stack.peek_nth(1)
+        {
+            self.state = Validity::IrrefutablyInvalid;
+        }
+    }
+
+    fn validate_untyped_select(&mut self) {
+        // The condition must be an `I32`
+        if self.operand_stack.len() < 3 || *self.operand_stack.peek() != VmType::I32 {
+            self.state = Validity::IrrefutablyInvalid;
+            return;
+        }
+
+        self.operand_stack.pop();
+
+        // The type of the selected operand is inferred
+        // from the two operands, which must match.
+        if self.operand_stack.peek_nth(0) != self.operand_stack.peek_nth(1) {
+            self.state = Validity::IrrefutablyInvalid;
+            return;
+        }
+
+        self.operand_stack.pop();
+    }
+
+    fn validate_conversion(&mut self, op: Instruction) {
+        let (from, to) = match op {
+            Instruction::i32Wrapi64 => (VmType::I64, VmType::I32),
+            Instruction::i64ExtendSignedi32 => (VmType::I32, VmType::I64),
+            Instruction::i64ExtendUnsignedi32 => (VmType::I32, VmType::I64),
+            Instruction::f32Demotef64 => (VmType::F64, VmType::F32),
+            Instruction::f64Promotef32 => (VmType::F32, VmType::F64),
+            Instruction::i32TruncSignedf32 => (VmType::F32, VmType::I32),
+            Instruction::i32TruncSignedf64 => (VmType::F64, VmType::I32),
+            Instruction::i64TruncSignedf32 => (VmType::F32, VmType::I64),
+            Instruction::i64TruncSignedf64 => (VmType::F64, VmType::I64),
+            Instruction::i32Reinterpretf32 => (VmType::F32, VmType::I32),
+            Instruction::i64Reinterpretf64 => (VmType::F64, VmType::I64),
+            Instruction::f32Reinterpreti32 => (VmType::I32, VmType::F32),
+            Instruction::f64Reinterpreti64 => (VmType::I64, VmType::F64),
+            _ => unreachable!(),
+        };
+
+        // The operand must be of the converted type
+        if self.operand_stack.is_empty() || *self.operand_stack.peek() != from {
+            self.state = Validity::IrrefutablyInvalid;
+            return;
+        }
+
+        self.operand_stack.pop();
+        self.operand_stack.push(to);
+    }
+
+    fn validate_memory_size_op(&mut self, op: Instruction) {
+        // Memory size instructions require a declared memory
+        if !self.has_memory {
+            self.state = Validity::IrrefutablyInvalid;
+            return;
+        }
+
+        match op {
+            Instruction::MemSize => {
+                // Push the page count
+                self.operand_stack.push(VmType::I32);
+            }
+            Instruction::MemGrow => {
+                // The delta pages are replaced by the previous size
+                if self.operand_stack.is_empty() || *self.operand_stack.peek() != VmType::I32 {
+                    self.state = Validity::IrrefutablyInvalid;
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn validate_rot(&mut self) {
+        let len = self.operand_stack.len();
+
+        if len < 3 {
+            self.state = Validity::IrrefutablyInvalid;
+            return;
+        }
+
+        // Move the third operand to the top
+        self.operand_stack.as_mut_slice()[len - 3..].rotate_left(1);
+    }
+
+    fn validate_function_declaration(
+        &mut self,
+        op: u8,
+        next_transitions: &mut Option<Vec<Transition>>,
+    ) {
+        // The first byte is the arity of the function
+        // followed by the types of its arguments.
+        self.validation_stack.push((op, true));
+
+        let (arity, _) = self.validation_stack.as_slice()[1];
+
+        if self.validation_stack.len() - 2 == arity as usize {
+            let arguments: Vec<VmType> = self.validation_stack.as_slice()[2..]
+                .iter()
+                .map(|(t, _)| VmType::from_op(*t).unwrap())
+                .collect();
+
+            // Cleanup
+            self.validation_stack = Stack::new();
+
+            self.function_table.push(FunctionSignature {
+                arity: arity,
+                arguments: arguments,
+            });
+
+            *next_transitions = Some(Instruction::Begin.transitions());
+        } else {
+            *next_transitions = Some(ARG_DECLARATIONS.to_vec());
+        }
+
+        // Continue validating
+        self.state = Validity::Invalid;
+    }
+
+    fn define_function(&mut self) {
+        // Functions are defined in the order they are declared
+        if self.defined_functions >= self.function_table.len() {
+            self.state = Validity::IrrefutablyInvalid;
+            return;
+        }
+
+        let arguments = self.function_table[self.defined_functions]
+            .arguments
+            .clone();
+
+        self.call_stack
+            .push(Frame::new(Some(CfOperator::Begin), None, Some(arguments)));
+
+        self.defined_functions += 1;
+    }
+
+    fn validate_call(&mut self, op: u8, next_transitions: &mut Option<Vec<Transition>>) {
+        self.validation_buffer.push(op);
+
+        // The index of the function is a 16bit integer
+        if self.validation_buffer.len() == 2 {
+            let idx = decode_be_u16!(&self.validation_buffer).unwrap() as usize;
+
+            // Cleanup
+            self.validation_buffer = vec![];
+            self.validation_stack = Stack::new();
+
+            // The function must be declared
+            if idx >= self.function_table.len() {
+                self.state = Validity::IrrefutablyInvalid;
+                return;
+            }
+
+            let arguments = &self.function_table[idx].arguments;
+            let frame = self.call_stack.peek_mut();
+
+            // Call arguments are taken from the locals stack
+            if frame.locals.len() < arguments.len() {
+                self.state = Validity::IrrefutablyInvalid;
+                return;
+            }
+
+            for (i, arg_type) in arguments.iter().rev().enumerate() {
+                if frame.locals.peek_nth(i) != arg_type {
+                    self.state = Validity::IrrefutablyInvalid;
+                    return;
+                }
+            }
+
+            for _ in 0..arguments.len() {
+                frame.locals.pop();
+            }
+
+            *next_transitions = Some(Instruction::Begin.transitions());
+        }
+
+        // Continue validating
+        self.state = Validity::Invalid;
+    }
+
+    fn validate_push(&mut self, op: u8, next_transitions: &mut Option<Vec<Transition>>) {
+        // Based on the length of the validation stack,
+        // we perform different validations.
+        match self.validation_stack.len() {
+            // Validate arity
+            1 => {
+                // Push arity to validation stack
+                self.validation_stack.push((op, true));
+
+                // Continue validating
+                self.state = Validity::Invalid;
+
+                // Next byte will be the bitmask so we allow any
+                *next_transitions = Some(vec![Transition::AnyByte]);
+            }
+
+            // Validate bitmask
+            2 => {
+                let bitmask = op;
+
+                // Push bitmask to validation stack
+                self.validation_stack.push((bitmask, true));
+
+                // Continue validating
+                self.state = Validity::Invalid;
+
+                // The next transitions are the argument types
+                *next_transitions = Some(ARG_DECLARATIONS.to_vec());
+            }
+
+            len => {
+                let (arity, _) = self.validation_stack.as_slice()[1];
+
+                // This is the intended length of the validation stack
+                let offset = (arity + 2) as usize;
+
+                if len >= 3 && len <= offset {
+                    // Validate argument types
+                    self.validation_stack.push((op, false));
+
+                    if len == offset {
+                        let (bitmask, _) = self.validation_stack.as_slice()[2];
+
+                        if bitmask.get(0) {
+                            // The first argument is popped from a stack
+                            // so we only allow pop operations.
+                            *next_transitions = Some(vec![
+                                Transition::Byte(Instruction::PopLocal.repr()),
                                 Transition::Byte(Instruction::PopOperand.repr()),
                             ]);
                         } else {
@@ -634,7 +1830,7 @@ impl Validator {
                                 // Check the type of the popped item
                                 match instr {
                                     Some(Instruction::PopOperand) => {
-                                        let at = self.operand_stack.peek();
+                                        let at = self.operand_stack.peek_nth(0);
 
                                         if *at != arg_type {
                                             self.state = Validity::IrrefutablyInvalid;
@@ -648,7 +1844,7 @@ impl Validator {
                                     }
                                     Some(Instruction::PopLocal) => {
                                         let frame = self.call_stack.peek();
-                                        let at = frame.locals.peek();
+                                        let at = frame.locals.peek_nth(0);
 
                                         if *at != arg_type {
                                             self.state = Validity::IrrefutablyInvalid;
@@ -800,8 +1996,7 @@ fn get_next_elem(val_stack: &Stack<(u8, bool)>) -> (VmType, usize) {
 }
 
 lazy_static! {
-    static ref ARITY_TRANSITIONS: Vec<Transition> =
-        (0..9).into_iter().map(|x| Transition::Byte(x)).collect();
+    static ref ARITY_TRANSITIONS: Vec<Transition> = vec![Transition::Range(0, 8)];
     static ref ARG_DECLARATIONS: Vec<Transition> = vec![
         Transition::Byte(Instruction::i32Const.repr()),
         Transition::Byte(Instruction::i64Const.repr()),
@@ -810,6 +2005,36 @@ lazy_static! {
     ];
 }
 
+/// Operand types accepted by integer only operations.
+const INTEGER_TYPES: &'static [VmType] = &[VmType::I32, VmType::I64];
+
+/// Operand types accepted by float only operations.
+const FLOAT_TYPES: &'static [VmType] = &[VmType::F32, VmType::F64];
+
+/// Number of comparison kinds accepted by `Compare`. These are,
+/// in order: eq, ne, lt, gt, le and ge.
+const COMPARISON_KINDS: u8 = 6;
+
+/// Conversions accepted by `Convert` in the form of `(source, target)`.
+/// Only conversions between different numeric types are allowed.
+const VALID_CONVERSIONS: &'static [(VmType, VmType)] = &[
+    (VmType::I32, VmType::I64),
+    (VmType::I32, VmType::F32),
+    (VmType::I32, VmType::F64),
+    (VmType::I64, VmType::I32),
+    (VmType::I64, VmType::F32),
+    (VmType::I64, VmType::F64),
+    (VmType::F32, VmType::I32),
+    (VmType::F32, VmType::I64),
+    (VmType::F32, VmType::F64),
+    (VmType::F64, VmType::I32),
+    (VmType::F64, VmType::I64),
+    (VmType::F64, VmType::F32),
+];
+
+/// Accepted alignment hints of memory access instructions.
+const MEM_ALIGNMENTS: &'static [u8] = &[1, 2, 4, 8];
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -956,6 +2181,7 @@ mod tests {
             0x02,                            // Arity 2
             Instruction::Eq.repr(),
             Instruction::Break.repr(),       // Break loop
+            0x00,                            // Loop depth
             Instruction::End.repr(),
             Instruction::Else.repr(),
             0x02,
@@ -1075,6 +2301,7 @@ mod tests {
             0x02,                            // Arity 0
             Instruction::Eq.repr(),
             Instruction::Break.repr(),       // Break loop
+            0x00,                            // Loop depth
             Instruction::End.repr(),
             Instruction::Else.repr(),
             0x02,
@@ -1198,6 +2425,7 @@ mod tests {
             0x02,                            // Arity 0
             Instruction::Eq.repr(),
             Instruction::Break.repr(),       // Break loop
+            0x00,                            // Loop depth
             Instruction::End.repr(),
             Instruction::Else.repr(),
             0x02,
@@ -1321,6 +2549,7 @@ mod tests {
             0x02,                            // Arity 0
             Instruction::Eq.repr(),
             Instruction::Break.repr(),       // Break loop
+            0x00,                            // Loop depth
             Instruction::End.repr(),
             Instruction::Else.repr(),
             0x02,
@@ -1444,6 +2673,7 @@ mod tests {
             0x02,                            // Arity 0
             Instruction::Eq.repr(),
             Instruction::Break.repr(),       // Break loop
+            0x00,                            // Loop depth
             Instruction::End.repr(),
             Instruction::Else.repr(),
             0x02,
@@ -1567,6 +2797,7 @@ mod tests {
             0x02,                            // Arity 0
             Instruction::Eq.repr(),
             Instruction::Break.repr(),       // Break loop
+            0x00,                            // Loop depth
             Instruction::End.repr(),
             Instruction::Else.repr(),
             0x02,
@@ -1642,6 +2873,7 @@ mod tests {
             0x02,                            // Arity 0
             Instruction::Eq.repr(),
             Instruction::Break.repr(),       // Break loop
+            0x00,                            // Loop depth
             Instruction::End.repr(),
             Instruction::Else.repr(),
             0x02,
@@ -1679,4 +2911,2759 @@ mod tests {
 
         assert!(!validator.valid());
     }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_global_get_and_set() {
+        let mut validator = Validator::new();
+        validator.declare_global(VmType::I32);
+        validator.declare_global(VmType::I64);
+
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::GlobalGet.repr(),
+            0x00,                             // Global idx 1
+            0x01,
+            Instruction::GlobalSet.repr(),
+            0x00,                             // Global idx 1
+            0x01,
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_out_of_bounds_global_index() {
+        let mut validator = Validator::new();
+        validator.declare_global(VmType::I32);
+
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::GlobalGet.repr(),
+            0x00,                             // Global idx 1
+            0x01,
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+        assert!(!validator.valid());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_global_set_type_mismatch() {
+        let mut validator = Validator::new();
+        validator.declare_global(VmType::I32);
+        validator.declare_global(VmType::I64);
+
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::GlobalGet.repr(),    // Push i32 global
+            0x00,
+            0x00,
+            Instruction::GlobalSet.repr(),    // Set i64 global
+            0x00,
+            0x01,
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+        assert!(!validator.valid());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_block_with_result_type() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Block.repr(),
+            Instruction::i64Const.repr(),     // Result type
+            Instruction::Nop.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        // The block left its result on the operand stack
+        assert_eq!(*validator.operand_stack.peek(), VmType::I64);
+
+        // Without the outer `End` the code is incomplete
+        assert!(!validator.valid());
+        assert!(!validator.done());
+
+        validator.push_op(Instruction::End.repr());
+
+        assert!(validator.valid());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_does_not_validate_unterminated_block() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Block.repr(),
+            Instruction::i32Const.repr(),     // Result type
+            Instruction::Nop.repr(),
+            Instruction::End.repr()           // Only closes the block
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(!validator.valid());
+        assert!(!validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_invalid_block_result_type() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Block.repr(),
+            Instruction::Nop.repr(),          // Not a type
+            Instruction::End.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_branch_table() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Loop.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push dispatch value
+            0x01,
+            0x00,
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            Instruction::BranchTable.repr(),
+            0x02,                             // 2 targets
+            0x00,                             // Target 0
+            0x00,
+            0x00,                             // Target 1
+            0x01,
+            Instruction::End.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+        assert!(validator.operand_stack.is_empty());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_branch_table_without_targets() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push dispatch value
+            0x01,
+            0x00,
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            Instruction::BranchTable.repr(),
+            0x00,                             // 0 targets
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_branch_table_without_dispatch_value() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::BranchTable.repr(),
+            0x00,                             // 0 targets
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_out_of_bounds_branch_table_target() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push dispatch value
+            0x01,
+            0x00,
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            Instruction::BranchTable.repr(),
+            0x02,                             // 2 targets
+            0x00,                             // Target 0
+            0x00,
+            0x00,                             // Target 1 is out of bounds
+            0x01,
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_mem_load_and_store() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push address
+            0x01,
+            0x00,
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x10,
+            Instruction::MemLoad.repr(),
+            Instruction::i64Const.repr(),     // Value type
+            0x08,                             // Alignment
+            0x00,                             // Offset
+            0x00,
+            0x00,
+            0x04,
+            Instruction::MemStore.repr(),
+            Instruction::i64Const.repr(),     // Value type
+            0x08,                             // Alignment
+            0x00,                             // Offset
+            0x00,
+            0x00,
+            0x00,
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+        assert!(validator.operand_stack.is_empty());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_invalid_mem_alignment() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::MemLoad.repr(),
+            Instruction::i32Const.repr(),     // Value type
+            0x03,                             // Invalid alignment
+            0x00,                             // Offset
+            0x00,
+            0x00,
+            0x00,
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_mem_store_type_mismatch() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push address and value
+            0x02,
+            0x00,
+            Instruction::i32Const.repr(),
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x10,
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            Instruction::MemStore.repr(),
+            Instruction::f32Const.repr(),     // Value type
+            0x04,                             // Alignment
+            0x00,                             // Offset
+            0x00,
+            0x00,
+            0x00,
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_all_comparison_kinds() {
+        for kind in 0..COMPARISON_KINDS {
+            let mut validator = Validator::new();
+            let block: Vec<u8> = vec![
+                Instruction::Begin.repr(),
+                0x00,                             // 0 Arity
+                Instruction::PushOperand.repr(),  // Push operands
+                0x02,
+                0x00,
+                Instruction::i64Const.repr(),
+                Instruction::i64Const.repr(),
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x01,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x02,
+                Instruction::Compare.repr(),
+                Instruction::i64Const.repr(),     // Operand type
+                kind,                             // Comparison kind
+                Instruction::End.repr()
+            ];
+
+            for byte in block {
+                validator.push_op(byte);
+            }
+
+            assert!(validator.valid());
+            assert_eq!(validator.operand_stack.as_slice(), &[VmType::I32]);
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_unknown_comparison_kind() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push operands
+            0x02,
+            0x00,
+            Instruction::i32Const.repr(),
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            0x00,
+            0x00,
+            0x00,
+            0x02,
+            Instruction::Compare.repr(),
+            Instruction::i32Const.repr(),     // Operand type
+            0x06,                             // Unknown comparison kind
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_compare_type_mismatch() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push operands
+            0x02,
+            0x00,
+            Instruction::i32Const.repr(),
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            0x00,
+            0x00,
+            0x00,
+            0x02,
+            Instruction::Compare.repr(),
+            Instruction::f32Const.repr(),     // Operand type
+            0x00,                             // eq
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_compare_as_if_condition() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push operands
+            0x02,
+            0x00,
+            Instruction::i32Const.repr(),
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            0x00,
+            0x00,
+            0x00,
+            0x02,
+            Instruction::If.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Compare.repr(),      // Not a comparison operator
+            Instruction::i32Const.repr(),
+            0x02,
+            Instruction::Nop.repr(),
+            Instruction::End.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+        assert!(!validator.valid());
+    }
+
+    fn type_op(t: VmType) -> u8 {
+        match t {
+            VmType::I32 => Instruction::i32Const.repr(),
+            VmType::I64 => Instruction::i64Const.repr(),
+            VmType::F32 => Instruction::f32Const.repr(),
+            VmType::F64 => Instruction::f64Const.repr(),
+            _ => unimplemented!(),
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_all_valid_conversions() {
+        for (source, target) in VALID_CONVERSIONS {
+            let mut validator = Validator::new();
+            let mut block: Vec<u8> = vec![
+                Instruction::Begin.repr(),
+                0x00,                             // 0 Arity
+                Instruction::PushOperand.repr(),  // Push operand
+                0x01,
+                0x00,
+                type_op(*source),
+            ];
+
+            block.extend_from_slice(&vec![0x00; source.byte_size()]);
+            block.extend_from_slice(&[
+                Instruction::Convert.repr(),
+                type_op(*source),                 // Source type
+                type_op(*target),                 // Target type
+                Instruction::End.repr()
+            ]);
+
+            for byte in block {
+                validator.push_op(byte);
+            }
+
+            assert!(validator.valid());
+            assert_eq!(validator.operand_stack.as_slice(), &[*target]);
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_identity_conversion() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push operand
+            0x01,
+            0x00,
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            Instruction::Convert.repr(),
+            Instruction::i32Const.repr(),     // Source type
+            Instruction::i32Const.repr(),     // Target type
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_array_to_numeric_conversion() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Convert.repr(),
+            Instruction::i32Array2.repr(),    // Source type
+            Instruction::i32Const.repr(),     // Target type
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_convert_source_type_mismatch() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push operand
+            0x01,
+            0x00,
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            Instruction::Convert.repr(),
+            Instruction::f32Const.repr(),     // Source type
+            Instruction::f64Const.repr(),     // Target type
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_swap() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push operands
+            0x02,
+            0x00,
+            Instruction::i32Const.repr(),
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            0x00,
+            0x00,
+            0x00,
+            0x02,
+            Instruction::Swap.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+        assert_eq!(validator.operand_stack.len(), 2);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_swap_on_empty_stack() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Swap.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_swap_on_single_element_stack() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push operand
+            0x01,
+            0x00,
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            Instruction::Swap.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_swap_of_different_types() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push operands
+            0x02,
+            0x00,
+            Instruction::i32Const.repr(),
+            Instruction::f32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            Instruction::Swap.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_rot() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push operands
+            0x03,
+            0x00,
+            Instruction::i64Const.repr(),
+            Instruction::i32Const.repr(),
+            Instruction::f32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            0x00,
+            0x00,
+            0x00,
+            0x02,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            Instruction::Rot.repr(),
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert_eq!(validator.operand_stack.len(), 3);
+        assert_eq!(*validator.operand_stack.peek(), VmType::I64);
+        assert_eq!(validator.operand_stack.as_slice(), &[VmType::I32, VmType::F32, VmType::I64]);
+
+        validator.push_op(Instruction::End.repr());
+        assert!(validator.valid());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_rot_on_less_than_three_elements() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push operands
+            0x02,
+            0x00,
+            Instruction::i32Const.repr(),
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            0x00,
+            0x00,
+            0x00,
+            0x02,
+            Instruction::Rot.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_stack_depth() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push operand
+            0x01,
+            0x00,
+            Instruction::f64Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert_eq!(validator.operand_stack_depth(), 1);
+
+        validator.push_op(Instruction::StackDepth.repr());
+
+        assert_eq!(validator.operand_stack_depth(), 2);
+        assert_eq!(*validator.operand_stack.peek(), VmType::I32);
+
+        validator.push_op(Instruction::End.repr());
+        assert!(validator.valid());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_begin_ended_with_missing_return_values() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushLocal.repr(),    // Push arguments
+            0x02,
+            0x00,
+            Instruction::i32Const.repr(),
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            0x00,
+            0x00,
+            0x00,
+            0x02,
+            Instruction::Begin.repr(),
+            0x02,                             // 2 Arity
+            Instruction::PushOperand.repr(),  // Push a single operand
+            0x01,
+            0x00,
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            Instruction::End.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_begin_ended_with_return_values() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushLocal.repr(),    // Push arguments
+            0x02,
+            0x00,
+            Instruction::i32Const.repr(),
+            Instruction::i64Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x02,
+            Instruction::Begin.repr(),
+            0x02,                             // 2 Arity
+            Instruction::PushOperand.repr(),  // Push return values
+            0x02,
+            0x00,
+            Instruction::i32Const.repr(),
+            Instruction::i64Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x02,
+            Instruction::End.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_declared_and_defined_functions() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                                 // 0 Arity
+            Instruction::DeclareFunction.repr(),  // Declare function 0
+            0x00,                                 // 0 Arity
+            Instruction::DeclareFunction.repr(),  // Declare function 1
+            0x01,                                 // 1 Arity
+            Instruction::i32Const.repr(),
+            Instruction::DefineFunction.repr(),   // Define function 0
+            Instruction::Nop.repr(),
+            Instruction::End.repr(),
+            Instruction::DefineFunction.repr(),   // Define function 1
+            Instruction::Call.repr(),             // Call function 0
+            0x00,
+            0x00,
+            Instruction::End.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+        assert_eq!(validator.function_table.len(), 2);
+        assert_eq!(validator.function_table[1].arguments, vec![VmType::I32]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_undeclared_function_definition() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                                 // 0 Arity
+            Instruction::DefineFunction.repr(),
+            Instruction::End.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_call_to_undeclared_function() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                                 // 0 Arity
+            Instruction::DeclareFunction.repr(),  // Declare function 0
+            0x00,                                 // 0 Arity
+            Instruction::Call.repr(),             // Call function 1
+            0x00,
+            0x01,
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_bitwise_ops() {
+        for bitwise_op in &[Instruction::And, Instruction::Or, Instruction::Xor] {
+            let mut validator = Validator::new();
+            let block: Vec<u8> = vec![
+                Instruction::Begin.repr(),
+                0x00,                             // 0 Arity
+                Instruction::PushOperand.repr(),  // Push operands
+                0x02,
+                0x00,
+                Instruction::i32Const.repr(),
+                Instruction::i32Const.repr(),
+                0x00,
+                0x00,
+                0x00,
+                0x01,
+                0x00,
+                0x00,
+                0x00,
+                0x02,
+                bitwise_op.repr(),
+                Instruction::i32Const.repr(),     // Operand type
+                Instruction::Not.repr(),
+                Instruction::i32Const.repr(),     // Operand type
+                Instruction::End.repr()
+            ];
+
+            for byte in block {
+                validator.push_op(byte);
+            }
+
+            assert!(validator.valid());
+            assert_eq!(validator.operand_stack.as_slice(), &[VmType::I32]);
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_float_bitwise_op() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push operands
+            0x02,
+            0x00,
+            Instruction::f32Const.repr(),
+            Instruction::f32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            Instruction::Xor.repr(),
+            Instruction::f32Const.repr(),     // Operand type
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_bitwise_op_stack_underflow() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::And.repr(),
+            Instruction::i64Const.repr(),     // Operand type
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_shift_ops() {
+        let shift_ops = &[
+            Instruction::Shl,
+            Instruction::ShrSigned,
+            Instruction::ShrUnsigned,
+            Instruction::Rotl,
+            Instruction::Rotr
+        ];
+
+        for shift_op in shift_ops {
+            let mut validator = Validator::new();
+            let block: Vec<u8> = vec![
+                Instruction::Begin.repr(),
+                0x00,                             // 0 Arity
+                Instruction::PushOperand.repr(),  // Push value and shift amount
+                0x02,
+                0x00,
+                Instruction::i64Const.repr(),
+                Instruction::i64Const.repr(),
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0xff,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x04,
+                shift_op.repr(),
+                Instruction::i64Const.repr(),     // Operand type
+            ];
+
+            for byte in block {
+                validator.push_op(byte);
+            }
+
+            assert_eq!(validator.operand_stack.as_slice(), &[VmType::I64]);
+
+            validator.push_op(Instruction::End.repr());
+            assert!(validator.valid());
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_mismatched_shift_amount() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push value and shift amount
+            0x02,
+            0x00,
+            Instruction::i64Const.repr(),
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0xff,
+            0x00,
+            0x00,
+            0x00,
+            0x04,
+            Instruction::Shl.repr(),
+            Instruction::i64Const.repr(),     // Operand type
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_clz() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push operand
+            0x01,
+            0x00,
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            Instruction::Clz.repr(),
+            Instruction::i32Const.repr(),     // Operand type
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+        assert_eq!(validator.operand_stack.as_slice(), &[VmType::I32]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_ctz_and_popcnt() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push operand
+            0x01,
+            0x00,
+            Instruction::i64Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            Instruction::Ctz.repr(),
+            Instruction::i64Const.repr(),     // Operand type
+            Instruction::Popcnt.repr(),
+            Instruction::i64Const.repr(),     // Operand type
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+        assert_eq!(validator.operand_stack.as_slice(), &[VmType::I64]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_float_popcnt() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push operand
+            0x01,
+            0x00,
+            Instruction::f32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            Instruction::Popcnt.repr(),
+            Instruction::f32Const.repr(),     // Operand type
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_f32_min_max_and_copy_sign() {
+        for float_op in &[Instruction::Min, Instruction::Max, Instruction::CopySign] {
+            let mut validator = Validator::new();
+            let block: Vec<u8> = vec![
+                Instruction::Begin.repr(),
+                0x00,                             // 0 Arity
+                Instruction::PushOperand.repr(),  // Push operands
+                0x02,
+                0x00,
+                Instruction::f32Const.repr(),
+                Instruction::f32Const.repr(),
+                0x3f,
+                0x80,
+                0x00,
+                0x00,
+                0xbf,
+                0x80,
+                0x00,
+                0x00,
+                float_op.repr(),
+                Instruction::f32Const.repr(),     // Operand type
+                Instruction::End.repr()
+            ];
+
+            for byte in block {
+                validator.push_op(byte);
+            }
+
+            assert!(validator.valid());
+            assert_eq!(validator.operand_stack.as_slice(), &[VmType::F32]);
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_f64_min_max_and_copy_sign() {
+        for float_op in &[Instruction::Min, Instruction::Max, Instruction::CopySign] {
+            let mut validator = Validator::new();
+            let block: Vec<u8> = vec![
+                Instruction::Begin.repr(),
+                0x00,                             // 0 Arity
+                Instruction::PushOperand.repr(),  // Push operands
+                0x02,
+                0x00,
+                Instruction::f64Const.repr(),
+                Instruction::f64Const.repr(),
+                0x3f,
+                0xf0,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0xbf,
+                0xf0,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                float_op.repr(),
+                Instruction::f64Const.repr(),     // Operand type
+                Instruction::End.repr()
+            ];
+
+            for byte in block {
+                validator.push_op(byte);
+            }
+
+            assert!(validator.valid());
+            assert_eq!(validator.operand_stack.as_slice(), &[VmType::F64]);
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_integer_min() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push operands
+            0x02,
+            0x00,
+            Instruction::i32Const.repr(),
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            0x00,
+            0x00,
+            0x00,
+            0x02,
+            Instruction::Min.repr(),
+            Instruction::i32Const.repr(),     // Operand type
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_unary_float_ops() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push operand
+            0x01,
+            0x00,
+            Instruction::f64Const.repr(),
+            0x40,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            Instruction::Sqrt.repr(),
+            Instruction::f64Const.repr(),     // Operand type
+            Instruction::Abs.repr(),
+            Instruction::f64Const.repr(),     // Operand type
+            Instruction::Neg.repr(),
+            Instruction::f64Const.repr(),     // Operand type
+            Instruction::Ceil.repr(),
+            Instruction::f64Const.repr(),     // Operand type
+            Instruction::Floor.repr(),
+            Instruction::f64Const.repr(),     // Operand type
+            Instruction::Trunc.repr(),
+            Instruction::f64Const.repr(),     // Operand type
+            Instruction::Nearest.repr(),
+            Instruction::f64Const.repr(),     // Operand type
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+        assert_eq!(validator.operand_stack.as_slice(), &[VmType::F64]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_integer_sqrt() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push operand
+            0x01,
+            0x00,
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x04,
+            Instruction::Sqrt.repr(),
+            Instruction::i32Const.repr(),     // Operand type
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_i32_eqz() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push operand
+            0x01,
+            0x00,
+            Instruction::i32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            Instruction::Eqz.repr(),
+            Instruction::i32Const.repr(),     // Operand type
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+        assert_eq!(validator.operand_stack.as_slice(), &[VmType::I32]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_i64_eqz() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push operand
+            0x01,
+            0x00,
+            Instruction::i64Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            Instruction::Eqz.repr(),
+            Instruction::i64Const.repr(),     // Operand type
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+        assert_eq!(validator.operand_stack.as_slice(), &[VmType::I32]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_float_eqz() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushOperand.repr(),  // Push operand
+            0x01,
+            0x00,
+            Instruction::f32Const.repr(),
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            Instruction::Eqz.repr(),
+            Instruction::f32Const.repr(),     // Operand type
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_mem_size_and_grow_with_declared_memory() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::MemSize.repr(),
+            Instruction::MemGrow.repr(),
+            Instruction::End.repr()
+        ];
+
+        validator.declare_memory();
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+        assert_eq!(validator.operand_stack.as_slice(), &[VmType::I32]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_mem_size_without_declared_memory() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::MemSize.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_mem_grow_without_delta() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::MemGrow.repr(),
+            Instruction::End.repr()
+        ];
+
+        validator.declare_memory();
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_tracks_nesting_depth() {
+        let mut validator = Validator::new();
+
+        assert_eq!(validator.depth(), 0);
+
+        validator.push_op(Instruction::Begin.repr());
+        validator.push_op(0x00);
+
+        assert_eq!(validator.depth(), 1);
+
+        validator.push_op(Instruction::Begin.repr());
+        validator.push_op(0x00);
+        validator.push_op(Instruction::Loop.repr());
+        validator.push_op(0x00);
+
+        assert_eq!(validator.depth(), 3);
+
+        validator.push_op(Instruction::End.repr());
+        validator.push_op(Instruction::End.repr());
+
+        assert_eq!(validator.depth(), 1);
+
+        validator.push_op(Instruction::Begin.repr());
+        validator.push_op(0x00);
+
+        assert_eq!(validator.depth(), 2);
+        assert_eq!(validator.max_nesting_depth(), 3);
+
+        validator.push_op(Instruction::End.repr());
+        validator.push_op(Instruction::End.repr());
+
+        assert_eq!(validator.depth(), 0);
+        assert_eq!(validator.max_nesting_depth(), 3);
+        assert!(validator.valid());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_break_depths_in_nested_loops() {
+        for depth in 0..2 {
+            let mut validator = Validator::new();
+            let block: Vec<u8> = vec![
+                Instruction::Begin.repr(),
+                0x00,                             // 0 Arity
+                Instruction::Loop.repr(),
+                0x00,                             // 0 Arity
+                Instruction::Loop.repr(),
+                0x00,                             // 0 Arity
+                Instruction::Nop.repr(),
+                Instruction::Break.repr(),
+                depth,                            // Loop depth
+                Instruction::End.repr(),
+                Instruction::End.repr(),
+                Instruction::End.repr()
+            ];
+
+            for byte in block {
+                validator.push_op(byte);
+            }
+
+            assert!(validator.valid());
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_break_if_depth() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Loop.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Loop.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Nop.repr(),
+            Instruction::BreakIf.repr(),
+            0x01,                             // Loop depth
+            Instruction::Eq.repr(),
+            Instruction::End.repr(),
+            Instruction::End.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_out_of_bounds_break_depth() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Loop.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Loop.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Nop.repr(),
+            Instruction::Break.repr(),
+            0x02,                             // Loop depth
+            Instruction::End.repr(),
+            Instruction::End.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_table_get_and_set() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::TableGet.repr(),
+            0x00,                             // Table 0
+            0x00,
+            Instruction::TableSet.repr(),
+            0x00,                             // Table 0
+            0x00,
+            0x00,                             // Element 3
+            0x03,
+            Instruction::End.repr()
+        ];
+
+        validator.declare_table(4);
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+        assert!(validator.operand_stack.is_empty());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_out_of_bounds_table_index() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::TableGet.repr(),
+            0x00,                             // Table 1
+            0x01,
+            Instruction::End.repr()
+        ];
+
+        validator.declare_table(4);
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_with_out_of_bounds_table_element() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::TableGet.repr(),
+            0x00,                             // Table 0
+            0x00,
+            Instruction::TableSet.repr(),
+            0x00,                             // Table 0
+            0x00,
+            0x00,                             // Element 4
+            0x04,
+            Instruction::End.repr()
+        ];
+
+        validator.declare_table(4);
+
+        for byte in block {
+            validator.push_op(byte);
+
+            if validator.done() {
+                break;
+            }
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_up_to_max_ops() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Nop.repr(),
+            Instruction::Nop.repr(),
+            Instruction::Nop.repr(),
+            Instruction::Nop.repr(),
+            Instruction::Nop.repr(),
+            Instruction::Nop.repr(),
+            Instruction::Nop.repr(),
+            Instruction::End.repr()
+        ];
+
+        validator.set_max_ops(10);
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+        assert_eq!(validator.ops_validated(), 10);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_when_exceeding_max_ops() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Nop.repr(),
+            Instruction::Nop.repr(),
+            Instruction::Nop.repr(),
+            Instruction::Nop.repr(),
+            Instruction::Nop.repr(),
+            Instruction::Nop.repr(),
+            Instruction::Nop.repr(),
+            Instruction::Nop.repr(),
+            Instruction::End.repr()
+        ];
+
+        validator.set_max_ops(10);
+
+        for (i, byte) in block.iter().enumerate() {
+            validator.push_op(*byte);
+
+            if validator.done() {
+                assert_eq!(i, 10);
+                break;
+            }
+        }
+
+        assert!(validator.done());
+        assert_eq!(validator.ops_validated(), 10);
+        assert_eq!(validator.invalid_reason(), Some(&ValidationError::TooManyInstructions));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_break_inside_while() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::While.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Nop.repr(),
+            Instruction::Break.repr(),
+            0x00,                             // Loop depth
+            Instruction::End.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_marks_while_frames() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::While.repr(),
+            0x00                              // 0 Arity
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert_eq!(validator.depth(), 2);
+        assert_eq!(validator.call_stack.peek().scope_type, Some(CfOperator::While));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_pushes_bytes() {
+        let mut validator = Validator::new();
+        let header: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00                              // 0 Arity
+        ];
+        let push: Vec<u8> = vec![
+            Instruction::PushLocal.repr(),
+            0x01,                             // 1 Arity
+            0x00,                             // Reference bits
+            Instruction::i32Const.repr(),
+            0x00,                             // i32 value
+            0x00,
+            0x00,
+            0x05
+        ];
+
+        assert_eq!(validator.push_bytes(&header), Ok(()));
+        assert_eq!(validator.push_bytes(&push), Ok(()));
+        assert_eq!(validator.call_stack.peek().locals.as_slice(), &[VmType::I32]);
+        assert_eq!(validator.push_bytes(&[Instruction::End.repr()]), Ok(()));
+        assert!(validator.valid());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_returns_the_offset_of_the_rejected_byte() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Nop.repr(),
+            Instruction::Begin.repr(),        // Begin without arity
+            Instruction::Nop.repr()
+        ];
+
+        assert_eq!(validator.push_bytes(&block), Err(ValidationError::InvalidByte(4)));
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_typed_constants() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushI32Const.repr(),
+            0x00,                             // 5i32
+            0x00,
+            0x00,
+            0x05,
+            Instruction::PushI64Const.repr(),
+            0x00,                             // 5i64
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x05,
+            Instruction::PushF32Const.repr(),
+            0x40,                             // 5f32
+            0xa0,
+            0x00,
+            0x00,
+            Instruction::PushF64Const.repr(),
+            0x40,                             // 5f64
+            0x14,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+        assert_eq!(validator.operand_stack.as_slice(), &[VmType::I32, VmType::I64, VmType::F32, VmType::F64]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_does_not_validate_truncated_typed_constant() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushI32Const.repr(),
+            0x00,                             // Only 3 bytes
+            0x00,
+            0x05,
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(!validator.valid());
+        assert_eq!(validator.operand_stack.as_slice(), &[VmType::I32]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_code_section() {
+        let mut validator = Validator::new();
+        let section: Vec<u8> = vec![
+            0x02,                             // 2 Functions
+            0x00,                             // Block length
+            0x04,
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Nop.repr(),
+            Instruction::End.repr(),
+            0x00,                             // Block length
+            0x06,
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Loop.repr(),
+            0x00,                             // 0 Arity
+            Instruction::End.repr(),
+            Instruction::End.repr()
+        ];
+
+        assert_eq!(validator.validate_section(SectionType::CodeSection, &section), Ok(()));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_fails_on_invalid_function_in_code_section() {
+        let mut validator = Validator::new();
+        let section: Vec<u8> = vec![
+            0x02,                             // 2 Functions
+            0x00,                             // Block length
+            0x04,
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Nop.repr(),
+            Instruction::End.repr(),
+            0x00,                             // Block length
+            0x03,
+            Instruction::Nop.repr(),          // Must start with begin
+            Instruction::Nop.repr(),
+            Instruction::End.repr()
+        ];
+
+        assert_eq!(validator.validate_section(SectionType::CodeSection, &section), Err(ValidationError::InvalidByte(9)));
+        assert_eq!(validator.validate_section(SectionType::CodeSection, &section[..8]), Err(ValidationError::Incomplete));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_infers_untyped_select_type() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushI64Const.repr(),
+            0x00,                             // 1i64
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            Instruction::PushI64Const.repr(),
+            0x00,                             // 2i64
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x02,
+            Instruction::PushI32Const.repr(),
+            0x00,                             // Condition
+            0x00,
+            0x00,
+            0x01,
+            Instruction::UntypedSelect.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+        assert_eq!(validator.operand_stack.as_slice(), &[VmType::I64]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_does_not_validate_untyped_select_with_mismatched_operands() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushI64Const.repr(),
+            0x00,                             // 1i64
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            Instruction::PushF32Const.repr(),
+            0x3f,                             // 1f32
+            0x80,
+            0x00,
+            0x00,
+            Instruction::PushI32Const.repr(),
+            0x00,                             // Condition
+            0x00,
+            0x00,
+            0x01,
+            Instruction::UntypedSelect.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_op_batch() {
+        let mut validator = Validator::new();
+        let mut block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00                              // 0 Arity
+        ];
+
+        block.extend_from_slice(&[Instruction::Nop.repr(); 17]);
+        block.push(Instruction::End.repr());
+
+        assert_eq!(block.len(), 20);
+        assert_eq!(validator.push_op_batch(&block), Ok(20));
+        assert!(validator.valid());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_reports_op_batch_error_offset() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Nop.repr(),
+            Instruction::Nop.repr(),
+            Instruction::Nop.repr(),
+            Instruction::Nop.repr(),
+            Instruction::Nop.repr(),
+            Instruction::UntypedSelect.repr(), // Empty operand stack
+            Instruction::Nop.repr(),
+            Instruction::End.repr()
+        ];
+
+        assert_eq!(validator.push_op_batch(&block), Err((7, ValidationError::InvalidByte(7))));
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_integer_size_conversions() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushI64Const.repr(),
+            0x00,                             // 1i64
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            Instruction::i32Wrapi64.repr(),
+            Instruction::i64ExtendSignedi32.repr(),
+            Instruction::i32Wrapi64.repr(),
+            Instruction::i64ExtendUnsignedi32.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+        assert_eq!(validator.operand_stack.as_slice(), &[VmType::I64]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_does_not_validate_wrap_of_i32() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushI32Const.repr(),
+            0x00,                             // 1i32
+            0x00,
+            0x00,
+            0x01,
+            Instruction::i32Wrapi64.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_does_not_validate_extend_of_i64() {
+        for extend in [Instruction::i64ExtendSignedi32, Instruction::i64ExtendUnsignedi32].iter() {
+            let mut validator = Validator::new();
+            let block: Vec<u8> = vec![
+                Instruction::Begin.repr(),
+                0x00,                         // 0 Arity
+                Instruction::PushI64Const.repr(),
+                0x00,                         // 1i64
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+                0x01,
+                extend.repr()
+            ];
+
+            for byte in block {
+                validator.push_op(byte);
+            }
+
+            assert!(validator.done());
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_float_precision_conversions() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushF64Const.repr(),
+            0x3f,                             // 1f64
+            0xf0,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            Instruction::f32Demotef64.repr(),
+            Instruction::f64Promotef32.repr(),
+            Instruction::f32Demotef64.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+        assert_eq!(validator.operand_stack.as_slice(), &[VmType::F32]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_does_not_validate_promote_of_f64() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushF64Const.repr(),
+            0x3f,                             // 1f64
+            0xf0,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            Instruction::f64Promotef32.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_does_not_validate_demote_of_integer() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushI64Const.repr(),
+            0x00,                             // 1i64
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            Instruction::f32Demotef64.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_float_truncations() {
+        let cases = [
+            (Instruction::i32TruncSignedf32, Instruction::PushF32Const, VmType::I32),
+            (Instruction::i32TruncSignedf64, Instruction::PushF64Const, VmType::I32),
+            (Instruction::i64TruncSignedf32, Instruction::PushF32Const, VmType::I64),
+            (Instruction::i64TruncSignedf64, Instruction::PushF64Const, VmType::I64),
+        ];
+
+        for (trunc, constant, result) in cases.iter() {
+            let mut validator = Validator::new();
+            let mut block: Vec<u8> = vec![
+                Instruction::Begin.repr(),
+                0x00,                         // 0 Arity
+                constant.repr()
+            ];
+
+            // Zero float of the constant's size
+            if let Instruction::PushF32Const = constant {
+                block.extend_from_slice(&[0x00; 4]);
+            } else {
+                block.extend_from_slice(&[0x00; 8]);
+            }
+
+            block.push(trunc.repr());
+            block.push(Instruction::End.repr());
+
+            for byte in block {
+                validator.push_op(byte);
+            }
+
+            assert!(validator.valid());
+            assert_eq!(validator.operand_stack.as_slice(), &[*result]);
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_does_not_validate_truncation_of_wrong_float() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::PushF64Const.repr(),
+            0x3f,                             // 1f64
+            0xf0,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            Instruction::i32TruncSignedf32.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_reinterpret_of_floats() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                                // 0 Arity
+            Instruction::PushF32Const.repr(),
+            0x3f,                                // 1f32
+            0x80,
+            0x00,
+            0x00,
+            Instruction::i32Reinterpretf32.repr(),
+            Instruction::PushF64Const.repr(),
+            0x3f,                                // 1f64
+            0xf0,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            Instruction::i64Reinterpretf64.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+        assert_eq!(validator.operand_stack.as_slice(), &[VmType::I32, VmType::I64]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_does_not_validate_reinterpret_of_wrong_float() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                                // 0 Arity
+            Instruction::PushF32Const.repr(),
+            0x3f,                                // 1f32
+            0x80,
+            0x00,
+            0x00,
+            Instruction::i64Reinterpretf64.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_reinterpret_round_trip() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                                // 0 Arity
+            Instruction::PushF32Const.repr(),
+            0x3f,                                // 1f32
+            0x80,
+            0x00,
+            0x00,
+            Instruction::i32Reinterpretf32.repr(),
+            Instruction::f32Reinterpreti32.repr(),
+            Instruction::PushI64Const.repr(),
+            0x00,                                // 1i64
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x01,
+            Instruction::f64Reinterpreti64.repr(),
+            Instruction::i64Reinterpretf64.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+        assert_eq!(validator.operand_stack.as_slice(), &[VmType::F32, VmType::I64]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_does_not_validate_reinterpret_of_wrong_integer() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                                // 0 Arity
+            Instruction::PushI32Const.repr(),
+            0x00,                                // 1i32
+            0x00,
+            0x00,
+            0x01,
+            Instruction::f64Reinterpreti64.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.done());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_after_reset() {
+        let mut validator = Validator::new();
+        validator.push_op(Instruction::Nop.repr());
+
+        assert!(validator.done());
+
+        validator.reset();
+
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                                // 0 Arity
+            Instruction::Nop.repr(),
+            Instruction::End.repr()
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        assert!(validator.valid());
+        assert_eq!(validator.ops_validated(), 4);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_validates_speculatively_on_clone() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                                // 0 Arity
+            Instruction::PushI32Const.repr(),
+            0x00,                                // 1i32
+            0x00,
+            0x00,
+            0x01
+        ];
+
+        for byte in block {
+            validator.push_op(byte);
+        }
+
+        let mut speculative = validator.clone();
+
+        // An i32 can't be demoted so the clone becomes invalid
+        speculative.push_op(Instruction::f32Demotef64.repr());
+
+        assert!(speculative.done());
+        assert!(!validator.done());
+        assert_eq!(validator.operand_stack.as_slice(), &[VmType::I32]);
+
+        validator.push_op(Instruction::End.repr());
+
+        assert!(validator.valid());
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_does_not_validate_reserved_opcodes() {
+        let mut validator = Validator::new();
+        let block: Vec<u8> = vec![
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Nop.repr(),
+            0xef,                             // Reserved opcode
+            Instruction::Nop.repr(),
+            Instruction::End.repr()
+        ];
+
+        assert!(Instruction::is_reserved(0xef));
+        assert!(!Instruction::is_reserved(Instruction::Nop.repr()));
+        assert_eq!(validator.push_op_batch(&block), Err((3, ValidationError::UnknownOpcode(0xef))));
+        assert!(validator.done());
+        assert_eq!(validator.invalid_reason(), Some(&ValidationError::UnknownOpcode(0xef)));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn it_consumes_module() {
+        let module: Vec<u8> = vec![
+            0x03,                             // 3 Functions
+            0x00,                             // Function length
+            0x00,
+            0x00,
+            0x04,
+            Instruction::Begin.repr(),
+            0x00,                             // 0 Arity
+            Instruction::Nop.repr(),
+            Instruction::End.repr(),
+            0x00,                             // Function length
+            0x00,
+            0x00,
+            0x02,
+            Instruction::Nop.repr(),          // Doesn't start with Begin
+            Instruction::End.repr(),
+            0x00,                             // Function length
+            0x00,
+            0x00,
+            0x04,
+            Instruction::Begin.repr()         // Truncated function
+        ];
+
+        assert_eq!(Validator::consume_module(&module), vec![
+            Ok(()),
+            Err(ValidationError::InvalidByte(0)),
+            Err(ValidationError::Incomplete)
+        ]);
+        assert!(Validator::consume_module(&[]).is_empty());
+    }
 }
@@ -40,3 +40,22 @@ pub enum VmError {
     /// Integer overflow
     Overflow,
 }
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValidationError {
+    /// The bytecode is empty.
+    Empty,
+
+    /// The validator rejected the byte at the given offset.
+    InvalidByte(usize),
+
+    /// The bytecode ended before forming a valid block.
+    Incomplete,
+
+    /// The bytecode contains more instructions than allowed.
+    TooManyInstructions,
+
+    /// The bytecode contains an opcode which is not
+    /// assigned to any instruction.
+    UnknownOpcode(u8),
+}
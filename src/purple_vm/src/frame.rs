@@ -26,6 +26,12 @@ pub struct Frame<T: Clone> {
     pub locals: Stack<T>,
     pub scope_type: Option<CfOperator>,
     pub return_address: Option<Address>,
+
+    /// The types of the arguments the frame was created with
+    pub argument_types: Vec<T>,
+
+    /// The types the frame must leave on the operand stack when ended
+    pub return_types: Vec<T>,
 }
 
 impl<T: fmt::Debug + Clone> Frame<T> {
@@ -35,18 +41,29 @@ impl<T: fmt::Debug + Clone> Frame<T> {
         argv: Option<Vec<T>>,
     ) -> Frame<T> {
         let mut locals = Stack::new();
+        let mut argument_types = Vec::new();
 
         if let Some(argv) = argv {
             // Push args to locals stack
-            for arg in argv {
-                locals.push(arg);
-            }
+            locals.extend(argv.iter().cloned());
+            argument_types = argv;
         }
 
         Frame {
             locals: locals,
             scope_type: scope_type,
             return_address: return_address,
+            argument_types: argument_types,
+            return_types: Vec::new(),
+        }
+    }
+
+    /// Returns true if the frame belongs to a `Loop`
+    /// or a `While` block.
+    pub fn is_loop(&self) -> bool {
+        match self.scope_type {
+            Some(CfOperator::Loop) | Some(CfOperator::While) => true,
+            _ => false,
         }
     }
 }
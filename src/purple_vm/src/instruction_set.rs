@@ -92,6 +92,8 @@ pub enum Instruction {
     DivUnsigned           = 0x34,
     RemSigned             = 0x35,
     RemUnsigned           = 0x36,
+
+    // Float only binary operations
     Min                   = 0x37,
     Max                   = 0x38,
 
@@ -194,6 +196,59 @@ pub enum Instruction {
     f32Reinterpreti32     = 0x9f,
     f64Reinterpreti64     = 0xa0,
 
+    // Globals
+    GlobalGet             = 0xa1,
+    GlobalSet             = 0xa2,
+
+    // Structured blocks
+    Block                 = 0xa3,
+    BranchTable           = 0xa4,
+
+    // Linear memory
+    MemLoad               = 0xa5,
+    MemStore              = 0xa6,
+
+    // Typed comparison
+    Compare               = 0xa7,
+
+    // Typed conversion
+    Convert               = 0xa8,
+
+    // Operand stack manipulation
+    Swap                  = 0xa9,
+    Rot                   = 0xaa,
+    StackDepth            = 0xab,
+
+    // Function declarations
+    DeclareFunction       = 0xac,
+    DefineFunction        = 0xad,
+
+    // Integer only unary operations
+    Not                   = 0xae,
+    Clz                   = 0xaf,
+    Ctz                   = 0xb0,
+    Popcnt                = 0xb1,
+
+    // Linear memory size
+    MemGrow               = 0xb4,
+    MemSize               = 0xb5,
+
+    // Function tables
+    TableGet              = 0xb6,
+    TableSet              = 0xb7,
+
+    // Loop variants
+    While                 = 0xb8,
+
+    // Typed constants
+    PushI32Const          = 0xb9,
+    PushI64Const          = 0xba,
+    PushF32Const          = 0xbb,
+    PushF64Const          = 0xbc,
+
+    // Parametric operators
+    UntypedSelect         = 0xbd,
+
     // Blockchain api
     AssetInfo             = 0xf0,
     GetBalance            = 0xf1,
@@ -215,6 +270,12 @@ pub enum Instruction {
 
 #[rustfmt::skip]
 impl Instruction {
+    /// Returns `true` if the given opcode is not
+    /// assigned to any instruction.
+    pub fn is_reserved(op: u8) -> bool {
+        Instruction::from_repr(op).is_none()
+    }
+
     pub fn transitions(&self) -> Vec<Transition> {
         match *self {
             // TODO: Add transitions for all ops
@@ -285,6 +346,8 @@ impl Instruction {
             Instruction::DivUnsigned            => DEFAULT_TRANSITIONS.to_vec(),
             Instruction::RemSigned              => DEFAULT_TRANSITIONS.to_vec(),
             Instruction::RemUnsigned            => DEFAULT_TRANSITIONS.to_vec(),
+
+            // Float only binary operations
             Instruction::Min                    => DEFAULT_TRANSITIONS.to_vec(),
             Instruction::Max                    => DEFAULT_TRANSITIONS.to_vec(),
 
@@ -349,6 +412,62 @@ impl Instruction {
             Instruction::f32Reinterpreti32      => DEFAULT_TRANSITIONS.to_vec(),
             Instruction::f64Reinterpreti64      => DEFAULT_TRANSITIONS.to_vec(),
 
+            // Globals
+            Instruction::GlobalGet              => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::GlobalSet              => DEFAULT_TRANSITIONS.to_vec(),
+
+            // Structured blocks
+            Instruction::Block                  => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::BranchTable => {
+                // Any code following a branch table is unreachable
+                vec![Transition::Op(Instruction::End)]
+            },
+
+            // Linear memory
+            Instruction::MemLoad                => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::MemStore               => DEFAULT_TRANSITIONS.to_vec(),
+
+            // Typed comparison
+            Instruction::Compare                => DEFAULT_TRANSITIONS.to_vec(),
+
+            // Typed conversion
+            Instruction::Convert                => DEFAULT_TRANSITIONS.to_vec(),
+
+            // Operand stack manipulation
+            Instruction::Swap                   => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::Rot                    => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::StackDepth             => DEFAULT_TRANSITIONS.to_vec(),
+
+            // Function declarations
+            Instruction::DeclareFunction        => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::DefineFunction         => DEFAULT_TRANSITIONS.to_vec(),
+
+            // Integer only unary operations
+            Instruction::Not                    => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::Clz                    => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::Ctz                    => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::Popcnt                 => DEFAULT_TRANSITIONS.to_vec(),
+
+            // Linear memory size
+            Instruction::MemGrow                => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::MemSize                => DEFAULT_TRANSITIONS.to_vec(),
+
+            // Function tables
+            Instruction::TableGet               => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::TableSet               => DEFAULT_TRANSITIONS.to_vec(),
+
+            // Loop variants
+            Instruction::While                  => DEFAULT_TRANSITIONS.to_vec(),
+
+            // Typed constants
+            Instruction::PushI32Const           => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::PushI64Const           => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::PushF32Const           => DEFAULT_TRANSITIONS.to_vec(),
+            Instruction::PushF64Const           => DEFAULT_TRANSITIONS.to_vec(),
+
+            // Parametric operators
+            Instruction::UntypedSelect          => DEFAULT_TRANSITIONS.to_vec(),
+
             // Blockchain api
             Instruction::AssetInfo              => DEFAULT_TRANSITIONS.to_vec(),
             Instruction::GetBalance             => DEFAULT_TRANSITIONS.to_vec(),
@@ -389,8 +508,10 @@ lazy_static! {
 pub const CT_FLOW_OPS: &'static [Instruction] = &[
     Instruction::Begin,
     Instruction::Loop,
+    Instruction::While,
     Instruction::If,
     Instruction::Else,
+    Instruction::Block,
 ];
 
 #[rustfmt::skip]
@@ -475,6 +596,8 @@ pub const OPS_LIST: &'static [Instruction] = &[
     Instruction::DivUnsigned           ,
     Instruction::RemSigned             ,
     Instruction::RemUnsigned           ,
+
+    // Float only binary operations
     Instruction::Min                   ,
     Instruction::Max                   ,
 
@@ -539,6 +662,59 @@ pub const OPS_LIST: &'static [Instruction] = &[
     Instruction::f32Reinterpreti32     ,
     Instruction::f64Reinterpreti64     ,
 
+    // Globals
+    Instruction::GlobalGet             ,
+    Instruction::GlobalSet             ,
+
+    // Structured blocks
+    Instruction::Block                 ,
+    Instruction::BranchTable           ,
+
+    // Linear memory
+    Instruction::MemLoad               ,
+    Instruction::MemStore              ,
+
+    // Typed comparison
+    Instruction::Compare               ,
+
+    // Typed conversion
+    Instruction::Convert               ,
+
+    // Operand stack manipulation
+    Instruction::Swap                  ,
+    Instruction::Rot                   ,
+    Instruction::StackDepth            ,
+
+    // Function declarations
+    Instruction::DeclareFunction       ,
+    Instruction::DefineFunction        ,
+
+    // Integer only unary operations
+    Instruction::Not                   ,
+    Instruction::Clz                   ,
+    Instruction::Ctz                   ,
+    Instruction::Popcnt                ,
+
+    // Linear memory size
+    Instruction::MemGrow               ,
+    Instruction::MemSize               ,
+
+    // Function tables
+    Instruction::TableGet              ,
+    Instruction::TableSet              ,
+
+    // Loop variants
+    Instruction::While                 ,
+
+    // Typed constants
+    Instruction::PushI32Const          ,
+    Instruction::PushI64Const          ,
+    Instruction::PushF32Const          ,
+    Instruction::PushF64Const          ,
+
+    // Parametric operators
+    Instruction::UntypedSelect         ,
+
     // Blockchain api
     Instruction::GetBalance            ,
     Instruction::SendCurrency          ,
@@ -20,6 +20,8 @@
 pub enum CfOperator {
     Begin,
     Loop,
+    While,
     If,
     Else,
+    Block,
 }
@@ -65,6 +65,24 @@ impl<T: fmt::Debug + Clone> Stack<T> {
         &mut self.0[len - 1]
     }
 
+    /// Returns a reference to the item at depth `n`, where `0` is the top.
+    pub fn peek_nth(&self, n: usize) -> &T {
+        let len = self.0.len();
+        if n >= len {
+            panic!("There is no item at the given depth!")
+        }
+        &self.0[len - 1 - n]
+    }
+
+    /// Returns a mutable reference to the item at depth `n`, where `0` is the top.
+    pub fn peek_nth_mut(&mut self, n: usize) -> &mut T {
+        let len = self.0.len();
+        if n >= len {
+            panic!("There is no item at the given depth!")
+        }
+        &mut self.0[len - 1 - n]
+    }
+
     pub fn as_slice(&self) -> &[T] {
         self.0.as_slice()
     }
@@ -76,6 +94,34 @@ impl<T: fmt::Debug + Clone> Stack<T> {
     pub fn len(&self) -> usize {
         self.0.len()
     }
+
+    /// Returns an iterator over the items of the
+    /// stack, from the top to the bottom.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.0.iter().rev()
+    }
+
+    /// Returns a mutable iterator over the items of
+    /// the stack, from the top to the bottom.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.0.iter_mut().rev()
+    }
+}
+
+impl<T> Default for Stack<T> {
+    fn default() -> Stack<T> {
+        Stack(vec![])
+    }
+}
+
+impl<T> Extend<T> for Stack<T> {
+    /// Pushes the items in iteration order, leaving
+    /// the last item on top of the stack.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.0.push(item);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -139,10 +185,77 @@ mod tests {
         stack.peek();
     }
 
+    #[test]
+    fn peek_nth() {
+        let mut stack: Stack<usize> = Stack::new();
+        stack.push(12);
+        stack.push(13);
+        assert_eq!(stack.peek_nth(0), stack.peek());
+        assert_eq!(*stack.peek_nth(1), 12);
+    }
+
+    #[test]
+    fn peek_nth_mut() {
+        let mut stack: Stack<usize> = Stack::new();
+        stack.push(12);
+        stack.push(13);
+        *stack.peek_nth_mut(1) = 11;
+        assert_eq!(stack.as_slice(), &[11, 13]);
+    }
+
+    #[test]
+    #[should_panic(expected = "no item at the given depth")]
+    fn out_of_bounds_peek_nth() {
+        let mut stack: Stack<usize> = Stack::new();
+        stack.push(13);
+        stack.peek_nth(1);
+    }
+
+    #[test]
+    fn iter() {
+        let mut stack: Stack<usize> = Stack::new();
+        stack.push(11);
+        stack.push(12);
+        stack.push(13);
+
+        let items: Vec<&usize> = stack.iter().collect();
+        assert_eq!(items, vec![&13, &12, &11]);
+    }
+
+    #[test]
+    fn iter_mut() {
+        let mut stack: Stack<usize> = Stack::new();
+        stack.push(11);
+        stack.push(12);
+
+        for item in stack.iter_mut() {
+            *item += 1;
+        }
+
+        assert_eq!(stack.as_slice(), &[12, 13]);
+    }
+
     #[test]
     #[should_panic(expected = "no item at the given index")]
     fn empty_pick() {
         let mut stack: Stack<usize> = Stack::new();
         stack.pick(0);
     }
+
+    #[test]
+    fn default() {
+        let stack: Stack<usize> = Default::default();
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn extend() {
+        let mut stack: Stack<usize> = Stack::new();
+        stack.extend(vec![1, 2, 3]);
+
+        assert_eq!(*stack.peek(), 3);
+        assert_eq!(stack.pop(), 3);
+        assert_eq!(stack.pop(), 2);
+        assert_eq!(stack.pop(), 1);
+    }
 }
@@ -245,6 +245,16 @@ impl Vm {
                             &argv,
                         );
                     }
+                    Some(Instruction::While) => {
+                        handle_begin_block(
+                            CfOperator::While,
+                            ip,
+                            &mut self.call_stack,
+                            &mut self.operand_stack,
+                            &fun,
+                            &argv,
+                        );
+                    }
                     Some(Instruction::If) => {
                         handle_begin_block(
                             CfOperator::If,
@@ -336,6 +346,13 @@ impl Vm {
                         self.operand_stack.pop();
                         ip.increment();
                     }
+                    Some(Instruction::StackDepth) => {
+                        let depth = self.operand_stack.len() as i32;
+
+                        // Push the depth of the operand stack
+                        self.operand_stack.push(VmValue::I32(depth));
+                        ip.increment();
+                    }
                     Some(Instruction::PopLocal) => {
                         let frame = self.call_stack.peek_mut();
 
@@ -390,7 +407,7 @@ impl Vm {
                             let current_ip = ip.ip;
 
                             match scope_type {
-                                Some(CfOperator::Loop) => {
+                                Some(CfOperator::Loop) | Some(CfOperator::While) => {
                                     // Push frame back to the call stack
                                     self.call_stack.push(frame);
 
@@ -409,11 +426,22 @@ impl Vm {
                         }
                     }
                     Some(Instruction::Break) => {
-                        // Pop frames until one has a `Loop` scope type
+                        ip.increment();
+
+                        // The next byte is the depth of the targeted
+                        // loop, counting from the innermost one.
+                        let mut depth = fun.fetch(ip.ip);
+
+                        // Pop frames until the targeted `Loop` frame
                         loop {
                             let frame = self.call_stack.pop();
 
-                            if let Some(CfOperator::Loop) = frame.scope_type {
+                            if frame.is_loop() {
+                                if depth > 0 {
+                                    depth -= 1;
+                                    continue;
+                                }
+
                                 // Replace operand stack with an empty one
                                 self.operand_stack = Stack::new();
 
@@ -439,6 +467,12 @@ impl Vm {
                     Some(Instruction::BreakIf) => {
                         ip.increment();
 
+                        // The next byte is the depth of the targeted
+                        // loop, counting from the innermost one.
+                        let mut depth = fun.fetch(ip.ip);
+
+                        ip.increment();
+
                         let op = fun.fetch(ip.ip);
 
                         if let Some(instruction) = Instruction::from_repr(op) {
@@ -458,11 +492,16 @@ impl Vm {
 
                                 // Return to stored caller address if comparison is successful
                                 if result {
-                                    // Pop frames until we find one with a `Loop` scope type
+                                    // Pop frames until the targeted `Loop` frame
                                     loop {
                                         let frame = self.call_stack.pop();
 
-                                        if let Some(CfOperator::Loop) = frame.scope_type {
+                                        if frame.is_loop() {
+                                            if depth > 0 {
+                                                depth -= 1;
+                                                continue;
+                                            }
+
                                             // Replace operand stack with an empty one
                                             self.operand_stack = Stack::new();
 
@@ -953,7 +992,7 @@ fn handle_begin_block(
         }
 
         // Loop as first instruction.
-        (&CfOperator::Loop, _, 0) => {
+        (&CfOperator::Loop, _, 0) | (&CfOperator::While, _, 0) => {
             panic!("The first instruction cannot be a Loop instruction!");
         }
 
@@ -3274,6 +3313,7 @@ mod tests {
             0x00,
             0x04,
             Instruction::BreakIf.repr(),     // Break if items on the operand stack are equal  
+            0x00,                            // Loop depth
             Instruction::Eq.repr(),
             Instruction::PopOperand.repr(),
             Instruction::PushOperand.repr(), // Increment counter
@@ -3412,6 +3452,7 @@ mod tests {
             0x00,                            // Arity 0
             Instruction::Eq.repr(),
             Instruction::Break.repr(),       // Break loop
+            0x00,                            // Loop depth
             Instruction::End.repr(),
             Instruction::Else.repr(),
             0x00,
@@ -3555,6 +3596,7 @@ mod tests {
             0x00,
             0x04,
             Instruction::BreakIf.repr(),     // Break if items on the operand stack are equal  
+            0x00,                            // Loop depth
             Instruction::Eq.repr(),
             Instruction::End.repr(),
             Instruction::PushOperand.repr(), // Increment counter
@@ -3686,6 +3728,7 @@ mod tests {
             0x02,                            // Arity 0
             Instruction::Eq.repr(),
             Instruction::Break.repr(),       // Break loop
+            0x00,                            // Loop depth
             Instruction::End.repr(),
             Instruction::Else.repr(),
             0x02,
@@ -3776,6 +3819,7 @@ mod tests {
             0x00,
             0x04,
             Instruction::BreakIf.repr(),
+            0x00,
             Instruction::Eq.repr(),
             Instruction::End.repr(),
             Instruction::End.repr(),
@@ -3877,6 +3921,7 @@ mod tests {
             0x00,
             0x04,
             Instruction::BreakIf.repr(),
+            0x00,
             Instruction::Eq.repr(),
             Instruction::End.repr(),
             Instruction::End.repr(),
@@ -3991,6 +4036,7 @@ mod tests {
             0x00,
             0x04,
             Instruction::BreakIf.repr(),
+            0x00,
             Instruction::Eq.repr(),
             Instruction::PushOperand.repr(),
             0x01,
@@ -4110,6 +4156,7 @@ mod tests {
             0x00,
             0x04,
             Instruction::BreakIf.repr(),
+            0x00,
             Instruction::Eq.repr(),
             Instruction::PushOperand.repr(),
             0x01,